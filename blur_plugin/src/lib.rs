@@ -1,17 +1,166 @@
-use log::error;
+use log::{LevelFilter, Log, Metadata, Record, debug, error};
 use serde::Deserialize;
-use std::ffi::{CStr, c_char};
+use std::cell::{Cell, RefCell};
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::panic::{self, UnwindSafe};
+use std::sync::{Mutex, PoisonError};
 
 /// Error codes returned by the blur plugin.
+///
+/// Values match the well-known codes shared by all plugins (see `describe_error_code`
+/// in `image_processor`'s plugin loader). The human-readable reason for a failure is
+/// available through `plugin_last_error`.
 #[repr(i32)]
 pub enum BlurError {
     Success = 0,
     ParseError = -1,
     SizeOverflow = -2,
     InvalidRadius = -3,
+    Panic = -4,
+    UnsupportedFormat = -5,
+    InvalidStride = -6,
+    Cancelled = -7,
+}
+
+/// Version of the host/plugin ABI this plugin implements.
+/// Must match a version supported by `image_processor`'s plugin loader.
+const PLUGIN_ABI_VERSION: u32 = 12;
+
+/// Pixel format codes of `process_pixels`. Must match `PixelFormat` in `image_processor`.
+const PIXEL_FORMAT_RGBA8: u32 = 0;
+const PIXEL_FORMAT_RGBA16: u32 = 1;
+const PIXEL_FORMAT_RGBA32F: u32 = 2;
+const PIXEL_FORMAT_L8: u32 = 3;
+const PIXEL_FORMAT_LA8: u32 = 4;
+const PIXEL_FORMAT_RGB8: u32 = 5;
+
+/// Host callback receiving the fraction of a call completed so far, with the host's context.
+type ProgressCallback = unsafe extern "C" fn(*mut c_void, f32);
+
+/// Host callback returning true once the current call should stop, with the host's context.
+type CancelCallback = unsafe extern "C" fn(*mut c_void) -> bool;
+
+thread_local! {
+    /// Message describing the last failed `process_image` call on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+
+    /// Progress callback the host installed for calls on this thread.
+    static PROGRESS: Cell<Option<(ProgressCallback, *mut c_void)>> = const { Cell::new(None) };
+
+    /// Cancellation callback the host installed for calls on this thread.
+    static CANCEL: Cell<Option<(CancelCallback, *mut c_void)>> = const { Cell::new(None) };
+}
+
+/// Host callback receiving one `log` record: its level (1 = error to 5 = trace), target and
+/// message as null-terminated strings.
+type LogCallback = unsafe extern "C" fn(u32, *const c_char, *const c_char);
+
+/// Log callback the host installed. Unlike the other callbacks it is shared by all threads,
+/// since `log` has a single global logger.
+static HOST_LOG: Mutex<Option<LogCallback>> = Mutex::new(None);
+
+/// `log` backend that forwards records to the host's logger.
+struct HostLogger;
+
+impl Log for HostLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        let callback = *HOST_LOG.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(callback) = callback.filter(|_| self.enabled(record.metadata())) else {
+            return;
+        };
+        // Interior null bytes cannot be represented in a C string; drop them.
+        let target = CString::new(record.target().replace('\0', "")).unwrap_or_default();
+        let message = CString::new(record.args().to_string().replace('\0', "")).unwrap_or_default();
+        // SAFETY: The host keeps the callback valid for as long as the library is loaded, and
+        // both strings outlive the call.
+        unsafe { callback(record.level() as u32, target.as_ptr(), message.as_ptr()) };
+    }
+
+    fn flush(&self) {}
 }
 
+/// Logs an error, records it for `plugin_last_error` and returns its code.
+fn fail(code: BlurError, message: String) -> i32 {
+    error!("{}", message);
+    // Interior null bytes cannot be represented in a C string; drop them.
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+    code as i32
+}
+
+/// Runs `body`, converting a panic into `BlurError::Panic` so it never unwinds into the host.
+fn catch_panic(body: impl FnOnce() -> i32 + UnwindSafe) -> i32 {
+    match panic::catch_unwind(body) {
+        Ok(code) => code,
+        Err(payload) => {
+            let reason = payload
+                .downcast_ref::<&str>()
+                .map(|reason| reason.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic payload".to_string());
+            fail(BlurError::Panic, format!("panicked: {}", reason))
+        }
+    }
+}
+
+/// Static plugin metadata returned by `plugin_info`.
+/// Layout must match `RawPluginInfo` in `image_processor`'s plugin loader.
+#[repr(C)]
+pub struct PluginInfo {
+    pub name: *const c_char,
+    pub version: *const c_char,
+    pub description: *const c_char,
+    pub author: *const c_char,
+    /// Comma-separated list of supported operations.
+    pub operations: *const c_char,
+}
+
+// SAFETY: PluginInfo only points to immutable 'static string literals, so sharing it
+// between threads is sound.
+unsafe impl Sync for PluginInfo {}
+
+static PLUGIN_INFO: PluginInfo = PluginInfo {
+    name: concat!(env!("CARGO_PKG_NAME"), "\0").as_ptr().cast(),
+    version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast(),
+    description: concat!(env!("CARGO_PKG_DESCRIPTION"), "\0").as_ptr().cast(),
+    author: concat!(env!("CARGO_PKG_AUTHORS"), "\0").as_ptr().cast(),
+    operations: c"blur".as_ptr(),
+};
+
+/// JSON Schema describing `Params`, returned by `plugin_params_schema`.
+/// Keep in sync with the struct below.
+const PARAMS_SCHEMA: &str = concat!(
+    r#"{
+    "$schema": "https://json-schema.org/draft/2020-12/schema",
+    "title": "blur_plugin parameters",
+    "type": "object",
+    "properties": {
+        "radius": {
+            "type": "integer",
+            "minimum": 0,
+            "maximum": 2147483647,
+            "default": 1,
+            "description": "Neighborhood radius in pixels (0 disables the blur)"
+        },
+        "iterations": {
+            "type": "integer",
+            "minimum": 0,
+            "maximum": 4294967295,
+            "default": 1,
+            "description": "Number of blur passes"
+        }
+    },
+    "additionalProperties": false
+}"#,
+    "\0"
+);
+
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 struct Params {
     #[serde(default = "default_radius")]
     radius: u32,
@@ -27,6 +176,145 @@ fn default_iterations() -> u32 {
     1
 }
 
+/// Returns the plugin ABI version. The host checks this before resolving any other symbol.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+/// Returns a pointer to the plugin's static metadata descriptor.
+/// The pointer stays valid for as long as the library is loaded.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_info() -> *const PluginInfo {
+    &PLUGIN_INFO
+}
+
+/// Returns the JSON Schema of the plugin's parameters as a static null-terminated string.
+/// The pointer stays valid for as long as the library is loaded.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_params_schema() -> *const c_char {
+    PARAMS_SCHEMA.as_ptr().cast()
+}
+
+/// Returns the pixel formats `process_pixels` and `process_strided` accept, one bit (`1 << code`) per format.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_pixel_formats() -> u32 {
+    1 << PIXEL_FORMAT_RGBA8
+        | 1 << PIXEL_FORMAT_RGBA16
+        | 1 << PIXEL_FORMAT_RGBA32F
+        | 1 << PIXEL_FORMAT_L8
+        | 1 << PIXEL_FORMAT_LA8
+        | 1 << PIXEL_FORMAT_RGB8
+}
+
+/// Returns the message describing why the last `process_image` call on the calling thread
+/// failed, or null if it succeeded. The pointer stays valid until the next `process_image`
+/// call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Sends this plugin's `log` records up to `max_level` (0 = off, 1 = error to 5 = trace) to
+/// the host's `callback`, or drops them again when `callback` is null.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_set_log_callback(callback: Option<LogCallback>, max_level: u32) {
+    *HOST_LOG.lock().unwrap_or_else(PoisonError::into_inner) = callback;
+    // Only the first call installs the logger; later ones replace the callback and level
+    let _ = log::set_logger(&HostLogger);
+    log::set_max_level(
+        LevelFilter::iter()
+            .nth(max_level as usize)
+            .unwrap_or(LevelFilter::Trace),
+    );
+}
+
+/// Installs the host's progress callback for calls on the calling thread, or removes it when
+/// `callback` is null. `context` is passed back to every call of `callback`.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_set_progress_callback(
+    callback: Option<ProgressCallback>,
+    context: *mut c_void,
+) {
+    PROGRESS.with(|progress| progress.set(callback.map(|callback| (callback, context))));
+}
+
+/// Tells the host that `done` of `total` units of work are complete, if it asked to know.
+fn report_progress(done: usize, total: usize) {
+    if let Some((callback, context)) = PROGRESS.with(Cell::get) {
+        // SAFETY: The host keeps the callback and its context valid for as long as they are
+        // installed, and this runs on the thread they were installed for.
+        unsafe { callback(context, done as f32 / total as f32) };
+    }
+}
+
+/// Installs the host's cancellation callback for calls on the calling thread, or removes it
+/// when `callback` is null. `context` is passed back to every call of `callback`.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_set_cancel_callback(
+    callback: Option<CancelCallback>,
+    context: *mut c_void,
+) {
+    CANCEL.with(|cancel| cancel.set(callback.map(|callback| (callback, context))));
+}
+
+/// Asks the host whether the current call should stop.
+fn cancelled() -> bool {
+    CANCEL.with(Cell::get).is_some_and(|(callback, context)| {
+        // SAFETY: The host keeps the callback and its context valid for as long as they are
+        // installed, and this runs on the thread they were installed for.
+        unsafe { callback(context) }
+    })
+}
+
+/// Reports how many pixels beyond its border a tile needs for the given params: each pass
+/// mixes in colors from up to `radius` pixels away.
+///
+/// # Safety
+///
+/// The caller must ensure:
+/// - `params` is a valid null-terminated C string
+/// - `halo` is a valid pointer to a writable `u32`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plugin_halo(params: *const c_char, halo: *mut u32) -> i32 {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+
+    catch_panic(|| {
+        // SAFETY: params is forwarded unchanged from the host.
+        let params = match unsafe { parse_params(params) } {
+            Ok(params) => params,
+            Err(code) => return code,
+        };
+
+        // SAFETY: The caller guarantees the pointer is valid for writes.
+        unsafe { *halo = params.radius.saturating_mul(params.iterations) };
+        BlurError::Success as i32
+    })
+}
+
+/// Parses the JSON params passed by the host, recording the error on failure.
+///
+/// # Safety
+///
+/// `params` must be a valid null-terminated C string.
+unsafe fn parse_params(params: *const c_char) -> Result<Params, i32> {
+    // SAFETY: params is a valid null-terminated C string passed by the host.
+    // The plugin loader guarantees this pointer is valid for the duration of this call.
+    let params_str = unsafe { CStr::from_ptr(params) }.to_str().unwrap_or("");
+
+    serde_json::from_str(params_str).map_err(|e| {
+        fail(
+            BlurError::ParseError,
+            format!("failed to parse params JSON: {}", e),
+        )
+    })
+}
+
 /// Processes an image by applying a blur effect.
 ///
 /// # Safety
@@ -42,58 +330,269 @@ pub unsafe extern "C" fn process_image(
     rgba_data: *mut u8,
     params: *const c_char,
 ) -> i32 {
-    // SAFETY: params is a valid null-terminated C string passed by the host.
-    // The plugin loader guarantees this pointer is valid for the duration of this call.
-    let params_str = unsafe { CStr::from_ptr(params) }.to_str().unwrap_or("");
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+
+    // SAFETY: The arguments are forwarded unchanged, so the caller's guarantees for
+    // process_image carry over to apply_blur.
+    catch_panic(|| unsafe {
+        apply_blur(width, height, PIXEL_FORMAT_RGBA8, None, rgba_data, params)
+    })
+}
+
+/// Processes an image in any format reported by `plugin_pixel_formats`.
+///
+/// # Safety
+///
+/// The caller must ensure:
+/// - `data` is a valid pointer to a buffer of exactly `width * height` pixels of `format`
+/// - `params` is a valid null-terminated C string
+/// - The buffer remains valid for the duration of this call
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn process_pixels(
+    width: u32,
+    height: u32,
+    format: u32,
+    data: *mut u8,
+    params: *const c_char,
+) -> i32 {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+
+    // SAFETY: The arguments are forwarded unchanged, so the caller's guarantees for
+    // process_pixels carry over to apply_blur.
+    catch_panic(|| unsafe { apply_blur(width, height, format, None, data, params) })
+}
+
+/// Processes an image whose rows start `stride` bytes apart, in any format reported by
+/// `plugin_pixel_formats`.
+///
+/// # Safety
+///
+/// The caller must ensure:
+/// - `data` is a valid pointer to `height` rows of `width` pixels of `format`, each row
+///   starting `stride` bytes after the previous one (`stride * (height - 1) + width * bytes
+///   per pixel` bytes in total); the bytes between rows are left untouched
+/// - `params` is a valid null-terminated C string
+/// - The buffer remains valid for the duration of this call
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn process_strided(
+    width: u32,
+    height: u32,
+    format: u32,
+    stride: usize,
+    data: *mut u8,
+    params: *const c_char,
+) -> i32 {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+
+    // SAFETY: The arguments are forwarded unchanged, so the caller's guarantees for
+    // process_strided carry over to apply_blur.
+    catch_panic(|| unsafe { apply_blur(width, height, format, Some(stride), data, params) })
+}
+
+/// One channel value as stored in the host's buffer (native byte order, no alignment
+/// guarantee).
+trait Sample: Copy + Default {
+    const SIZE: usize;
+
+    fn read(bytes: &[u8]) -> Self;
+    fn write(self, bytes: &mut [u8]);
+    fn to_f64(self) -> f64;
+    /// Converts a weighted average back, rounding integer samples.
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Sample for u8 {
+    const SIZE: usize = 1;
+
+    fn read(bytes: &[u8]) -> Self {
+        bytes[0]
+    }
+    fn write(self, bytes: &mut [u8]) {
+        bytes[0] = self;
+    }
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn from_f64(value: f64) -> Self {
+        value.round() as u8
+    }
+}
+
+impl Sample for u16 {
+    const SIZE: usize = 2;
+
+    fn read(bytes: &[u8]) -> Self {
+        u16::from_ne_bytes([bytes[0], bytes[1]])
+    }
+    fn write(self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self.to_ne_bytes());
+    }
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn from_f64(value: f64) -> Self {
+        value.round() as u16
+    }
+}
+
+impl Sample for f32 {
+    const SIZE: usize = 4;
+
+    fn read(bytes: &[u8]) -> Self {
+        f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+    fn write(self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self.to_ne_bytes());
+    }
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+/// Blurs the image in place. Rows start `stride` bytes apart, or are tightly packed for
+/// `None`. Panics are caught by the callers.
+///
+/// # Safety
+///
+/// Same contract as `process_strided`, with packed rows for a `None` stride.
+unsafe fn apply_blur(
+    width: u32,
+    height: u32,
+    format: u32,
+    stride: Option<usize>,
+    data: *mut u8,
+    params: *const c_char,
+) -> i32 {
+    // SAFETY: params is forwarded unchanged from the host.
+    let params = match unsafe { parse_params(params) } {
+        Ok(params) => params,
+        Err(code) => return code,
+    };
 
-    let params: Params = match serde_json::from_str(params_str) {
-        Ok(p) => p,
-        Err(e) => {
-            error!("blur_plugin: failed to parse params JSON: {}", e);
-            return BlurError::ParseError as i32;
+    let (channels, sample_size) = match format {
+        PIXEL_FORMAT_L8 => (1, u8::SIZE),
+        PIXEL_FORMAT_LA8 => (2, u8::SIZE),
+        PIXEL_FORMAT_RGB8 => (3, u8::SIZE),
+        PIXEL_FORMAT_RGBA8 => (4, u8::SIZE),
+        PIXEL_FORMAT_RGBA16 => (4, u16::SIZE),
+        PIXEL_FORMAT_RGBA32F => (4, f32::SIZE),
+        _ => {
+            return fail(
+                BlurError::UnsupportedFormat,
+                format!("unsupported pixel format code {}", format),
+            );
         }
     };
 
+    debug!(
+        "Blurring {}x{} image with radius {} and {} iterations",
+        width, height, params.radius, params.iterations
+    );
+
     // Early return if no blur needed
     if params.radius == 0 || params.iterations == 0 {
         return BlurError::Success as i32;
     }
 
-    let width_usize = width as usize;
-    let height_usize = height as usize;
-    let len = match width_usize
-        .checked_mul(height_usize)
-        .and_then(|n| n.checked_mul(4))
+    let Some(row_bytes) = (width as usize).checked_mul(channels * sample_size) else {
+        return fail(
+            BlurError::SizeOverflow,
+            "size overflow calculating row bytes".to_string(),
+        );
+    };
+    let stride = stride.unwrap_or(row_bytes);
+    if stride < row_bytes {
+        return fail(
+            BlurError::InvalidStride,
+            format!(
+                "stride {} is shorter than a row of {} bytes",
+                stride, row_bytes
+            ),
+        );
+    }
+    let len = match stride
+        .checked_mul((height as usize).saturating_sub(1))
+        .and_then(|n| n.checked_add(if height == 0 { 0 } else { row_bytes }))
     {
         Some(len) => len,
         None => {
-            error!("blur_plugin: size overflow calculating buffer length");
-            return BlurError::SizeOverflow as i32;
+            return fail(
+                BlurError::SizeOverflow,
+                "size overflow calculating buffer length".to_string(),
+            );
         }
     };
     let radius: i32 = match params.radius.try_into() {
         Ok(r) => r,
         Err(_) => {
-            error!("blur_plugin: radius {} exceeds i32::MAX", params.radius);
-            return BlurError::InvalidRadius as i32;
+            return fail(
+                BlurError::InvalidRadius,
+                format!("radius {} exceeds i32::MAX", params.radius),
+            );
         }
     };
 
-    // SAFETY: rgba_data is a valid pointer to a buffer of exactly width * height * 4 bytes,
-    // owned by the host. The plugin loader guarantees this buffer is valid and properly
-    // aligned for the duration of this call. We only access indices within bounds.
-    let data = unsafe { std::slice::from_raw_parts_mut(rgba_data, len) };
+    // SAFETY: data is a valid pointer to height rows of width pixels of `format`, stride
+    // bytes apart, i.e. len bytes, owned by the host. The plugin loader guarantees this buffer
+    // is valid for the duration of this call. We only access indices within bounds and never
+    // touch the bytes between rows.
+    let data = unsafe { std::slice::from_raw_parts_mut(data, len) };
+
+    let (width, height) = (width as usize, height as usize);
+    let iterations = params.iterations;
+    let finished = match format {
+        PIXEL_FORMAT_RGBA16 => {
+            blur::<u16>(data, width, height, stride, channels, radius, iterations)
+        }
+        PIXEL_FORMAT_RGBA32F => {
+            blur::<f32>(data, width, height, stride, channels, radius, iterations)
+        }
+        _ => blur::<u8>(data, width, height, stride, channels, radius, iterations),
+    };
+    if !finished {
+        return fail(BlurError::Cancelled, "cancelled by the host".to_string());
+    }
+
+    BlurError::Success as i32
+}
+
+/// Applies `iterations` passes of the weighted average blur to an image of `channels`
+/// interleaved `S` samples per pixel (1 to 4), with rows `stride` bytes apart.
+///
+/// Returns false, leaving `data` untouched, if the host cancels the call.
+fn blur<S: Sample>(
+    data: &mut [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    channels: usize,
+    radius: i32,
+    iterations: u32,
+) -> bool {
+    let row_bytes = width * channels * S::SIZE;
+    let rows = || (0..height).map(|y| y * stride..y * stride + row_bytes);
+    let mut samples: Vec<S> = rows()
+        .flat_map(|row| data[row].chunks_exact(S::SIZE).map(S::read))
+        .collect();
 
     // Allocate temporary buffer for intermediate results
-    let mut temp_buffer = vec![0u8; len];
+    let mut temp_buffer = vec![S::default(); samples.len()];
 
     // Apply blur for the specified number of iterations
-    for _ in 0..params.iterations {
+    let total_rows = height * iterations as usize;
+    for iteration in 0..iterations as usize {
         // For each pixel, compute weighted average of neighbors within radius
-        for cy in 0..height_usize {
-            for cx in 0..width_usize {
+        for cy in 0..height {
+            if cancelled() {
+                return false;
+            }
+            report_progress(iteration * height + cy, total_rows);
+            for cx in 0..width {
                 let mut weight_sum = 0.0_f64;
-                let mut color_sum = [0.0_f64; 4]; // R, G, B, A
+                let mut color_sum = [0.0_f64; 4]; // Up to R, G, B, A
 
                 // Iterate over neighbors within radius
                 for dy in -radius..=radius {
@@ -102,8 +601,7 @@ pub unsafe extern "C" fn process_image(
                         let ny = cy as i32 + dy;
 
                         // Check bounds
-                        if nx >= 0 && nx < width_usize as i32 && ny >= 0 && ny < height_usize as i32
-                        {
+                        if nx >= 0 && nx < width as i32 && ny >= 0 && ny < height as i32 {
                             let nx = nx as usize;
                             let ny = ny as usize;
 
@@ -114,28 +612,34 @@ pub unsafe extern "C" fn process_image(
                             weight_sum += weight;
 
                             // Accumulate weighted color values
-                            let neighbor_idx = (ny * width_usize + nx) * 4;
-                            for channel in 0..4 {
-                                color_sum[channel] += weight * data[neighbor_idx + channel] as f64;
+                            let neighbor_idx = (ny * width + nx) * channels;
+                            for channel in 0..channels {
+                                color_sum[channel] +=
+                                    weight * samples[neighbor_idx + channel].to_f64();
                             }
                         }
                     }
                 }
 
                 // Store weighted average in temp buffer
-                let pixel_idx = (cy * width_usize + cx) * 4;
-                for channel in 0..4 {
-                    temp_buffer[pixel_idx + channel] =
-                        (color_sum[channel] / weight_sum).round() as u8;
+                let pixel_idx = (cy * width + cx) * channels;
+                for channel in 0..channels {
+                    temp_buffer[pixel_idx + channel] = S::from_f64(color_sum[channel] / weight_sum);
                 }
             }
         }
 
-        // Copy temp buffer back to original data
-        data.copy_from_slice(&temp_buffer);
+        // Copy temp buffer back to the samples
+        samples.copy_from_slice(&temp_buffer);
     }
+    report_progress(total_rows, total_rows);
 
-    BlurError::Success as i32
+    for (row, row_samples) in rows().zip(samples.chunks_exact(width * channels)) {
+        for (sample, bytes) in row_samples.iter().zip(data[row].chunks_exact_mut(S::SIZE)) {
+            sample.write(bytes);
+        }
+    }
+    true
 }
 
 #[cfg(test)]
@@ -361,4 +865,303 @@ mod tests {
         let result = blur_image(&mut data, 4, 4, r#"{"radius": 0, "iterations": 1}"#);
         assert_eq!(result, BlurError::Success as i32);
     }
+
+    /// Calls process_pixels on a 2x1 image with a black and a white pixel of `S` samples and
+    /// returns the red channel of both pixels.
+    fn blur_black_white<S: Sample>(format: u32, white: S) -> (S, S) {
+        let mut data = vec![0u8; 8 * S::SIZE];
+        for channel in 0..8 {
+            let value = if channel < 4 { S::default() } else { white };
+            value.write(&mut data[channel * S::SIZE..(channel + 1) * S::SIZE]);
+        }
+        let c_params = CString::new(r#"{"radius": 1}"#).expect("CString creation failed");
+
+        // SAFETY: data holds 2 * 1 pixels of `format` and c_params is a valid C string.
+        let result = unsafe { process_pixels(2, 1, format, data.as_mut_ptr(), c_params.as_ptr()) };
+
+        assert_eq!(result, BlurError::Success as i32);
+        (S::read(&data[..S::SIZE]), S::read(&data[4 * S::SIZE..]))
+    }
+
+    #[test]
+    fn test_process_pixels_keeps_16_bit_precision() {
+        // Each pixel averages itself (weight 1) with its neighbor (weight 1/2)
+        assert_eq!(
+            blur_black_white(PIXEL_FORMAT_RGBA16, u16::MAX),
+            (21845, 43690)
+        );
+    }
+
+    #[test]
+    fn test_process_pixels_blurs_float_samples() {
+        let (black, white) = blur_black_white(PIXEL_FORMAT_RGBA32F, 1.0_f32);
+
+        assert!((black - 1.0 / 3.0).abs() < 1e-6, "got {}", black);
+        assert!((white - 2.0 / 3.0).abs() < 1e-6, "got {}", white);
+    }
+
+    #[test]
+    fn test_process_pixels_blurs_gray_pixels() {
+        // 3x1 L8 image: one byte per pixel
+        let mut data = vec![0u8, 255, 0];
+        let c_params = CString::new(r#"{"radius": 1}"#).expect("CString creation failed");
+
+        // SAFETY: data holds 3 * 1 L8 pixels and c_params is a valid C string.
+        let result =
+            unsafe { process_pixels(3, 1, PIXEL_FORMAT_L8, data.as_mut_ptr(), c_params.as_ptr()) };
+
+        assert_eq!(result, BlurError::Success as i32);
+        // Edge pixels: (0 + 255/2) / 1.5 = 85; center: 255 / 2 = 127.5
+        assert_eq!(data, vec![85, 128, 85]);
+    }
+
+    #[test]
+    fn test_process_strided_leaves_padding_untouched() {
+        // 3x1 L8 image followed by a padding byte, then a second row
+        let mut data = vec![0u8, 255, 0, 42, 0, 255, 0];
+        let c_params = CString::new(r#"{"radius": 1}"#).expect("CString creation failed");
+
+        // SAFETY: data holds 2 rows of 3 L8 pixels, 4 bytes apart, and c_params is a valid C
+        // string.
+        let result = unsafe {
+            process_strided(
+                3,
+                2,
+                PIXEL_FORMAT_L8,
+                4,
+                data.as_mut_ptr(),
+                c_params.as_ptr(),
+            )
+        };
+
+        assert_eq!(result, BlurError::Success as i32);
+        assert_eq!(data[3], 42, "padding must not be modified");
+        assert_eq!(data[..3], data[4..], "both rows should blur the same");
+    }
+
+    #[test]
+    fn test_process_pixels_rejects_unknown_format() {
+        let mut data = vec![0u8; 4];
+        let c_params = CString::new("{}").expect("CString creation failed");
+
+        // SAFETY: The plugin rejects the format before touching the buffer.
+        let result = unsafe { process_pixels(1, 1, 9, data.as_mut_ptr(), c_params.as_ptr()) };
+
+        assert_eq!(result, BlurError::UnsupportedFormat as i32);
+    }
+
+    #[test]
+    fn test_progress_reaches_one_once_installed() {
+        unsafe extern "C" fn record(context: *mut c_void, fraction: f32) {
+            // SAFETY: The test installs this callback with a pointer to its local Vec.
+            unsafe { (*context.cast::<Vec<f32>>()).push(fraction) };
+        }
+
+        let mut reports: Vec<f32> = Vec::new();
+        let mut data = vec![0u8, 255, 0, 255];
+        let c_params =
+            CString::new(r#"{"radius": 1, "iterations": 2}"#).expect("CString creation failed");
+        plugin_set_progress_callback(Some(record), (&raw mut reports).cast());
+        // SAFETY: data holds 2 * 2 L8 pixels and c_params is a valid C string.
+        let result =
+            unsafe { process_pixels(2, 2, PIXEL_FORMAT_L8, data.as_mut_ptr(), c_params.as_ptr()) };
+        plugin_set_progress_callback(None, std::ptr::null_mut());
+
+        assert_eq!(result, BlurError::Success as i32);
+        assert_eq!(reports, vec![0.0, 0.25, 0.5, 0.75, 1.0]);
+
+        // Once removed, nothing is reported
+        // SAFETY: As above.
+        unsafe { process_pixels(2, 2, PIXEL_FORMAT_L8, data.as_mut_ptr(), c_params.as_ptr()) };
+        assert_eq!(reports.len(), 5);
+    }
+
+    #[test]
+    fn test_log_records_reach_host_callback() {
+        static RECORDS: Mutex<Vec<(u32, String, String)>> = Mutex::new(Vec::new());
+        unsafe extern "C" fn record(level: u32, target: *const c_char, message: *const c_char) {
+            // SAFETY: The logger passes null-terminated strings that outlive the call.
+            let (target, message) = unsafe { (CStr::from_ptr(target), CStr::from_ptr(message)) };
+            RECORDS.lock().expect("records lock").push((
+                level,
+                target.to_string_lossy().into_owned(),
+                message.to_string_lossy().into_owned(),
+            ));
+        }
+
+        plugin_set_log_callback(Some(record), 1);
+        let mut data = vec![0u8; 4];
+        assert_eq!(
+            blur_image(&mut data, 1, 1, "not json"),
+            BlurError::ParseError as i32
+        );
+        plugin_set_log_callback(None, 0);
+
+        let records = RECORDS.lock().expect("records lock");
+        assert!(
+            records.iter().any(|(level, target, message)| *level == 1
+                && target == "blur_plugin"
+                && message.contains("failed to parse params JSON")),
+            "got: {:?}",
+            records
+        );
+        // Debug records are above the level the host asked for
+        assert!(!records.iter().any(|(level, _, _)| *level > 1));
+    }
+
+    #[test]
+    fn test_cancel_leaves_data_untouched() {
+        unsafe extern "C" fn always(_context: *mut c_void) -> bool {
+            true
+        }
+
+        let mut data = vec![0u8, 255, 0, 255];
+        let c_params = CString::new(r#"{"radius": 1}"#).expect("CString creation failed");
+        plugin_set_cancel_callback(Some(always), std::ptr::null_mut());
+        // SAFETY: data holds 2 * 2 L8 pixels and c_params is a valid C string.
+        let result =
+            unsafe { process_pixels(2, 2, PIXEL_FORMAT_L8, data.as_mut_ptr(), c_params.as_ptr()) };
+        plugin_set_cancel_callback(None, std::ptr::null_mut());
+
+        assert_eq!(result, BlurError::Cancelled as i32);
+        assert_eq!(data, vec![0, 255, 0, 255]);
+
+        // Once removed, the blur runs to completion
+        // SAFETY: As above.
+        let result =
+            unsafe { process_pixels(2, 2, PIXEL_FORMAT_L8, data.as_mut_ptr(), c_params.as_ptr()) };
+        assert_eq!(result, BlurError::Success as i32);
+        assert_ne!(data, vec![0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn test_plugin_halo_grows_with_radius_and_iterations() {
+        let halo_for = |params: &str| {
+            let c_params = CString::new(params).expect("CString creation failed");
+            let mut halo = 0u32;
+            // SAFETY: c_params is a valid C string and halo a writable u32.
+            let result = unsafe { plugin_halo(c_params.as_ptr(), &mut halo) };
+            (result, halo)
+        };
+
+        assert_eq!(halo_for("{}"), (BlurError::Success as i32, 1));
+        assert_eq!(
+            halo_for(r#"{"radius": 3, "iterations": 4}"#),
+            (BlurError::Success as i32, 12)
+        );
+        assert_eq!(halo_for(r#"{"radius": 0}"#), (BlurError::Success as i32, 0));
+        assert_eq!(halo_for("not json").0, BlurError::ParseError as i32);
+    }
+
+    #[test]
+    fn test_plugin_abi_version() {
+        assert_eq!(plugin_abi_version(), PLUGIN_ABI_VERSION);
+    }
+
+    #[test]
+    fn test_plugin_info_descriptor() {
+        // SAFETY: plugin_info returns a pointer to a 'static descriptor whose fields are
+        // null-terminated string literals.
+        let (name, operations) = unsafe {
+            let info = &*plugin_info();
+            (
+                CStr::from_ptr(info.name).to_str().expect("valid UTF-8"),
+                CStr::from_ptr(info.operations)
+                    .to_str()
+                    .expect("valid UTF-8"),
+            )
+        };
+
+        assert_eq!(name, "blur_plugin");
+        assert_eq!(operations, "blur");
+    }
+
+    #[test]
+    fn test_params_schema_is_valid_json() {
+        // SAFETY: plugin_params_schema returns a pointer to a static null-terminated string.
+        let schema = unsafe { CStr::from_ptr(plugin_params_schema()) }
+            .to_str()
+            .expect("valid UTF-8");
+        let schema: serde_json::Value = serde_json::from_str(schema).expect("valid JSON");
+
+        let properties = schema["properties"].as_object().expect("properties object");
+        assert_eq!(
+            properties.len(),
+            2,
+            "schema must declare every Params field"
+        );
+        assert_eq!(schema["additionalProperties"], false);
+    }
+
+    #[test]
+    fn test_unknown_param_rejected() {
+        let mut data = create_4x4_sharp_edge();
+        let original = data.clone();
+
+        let result = blur_image(&mut data, 4, 4, r#"{"radus": 2}"#);
+
+        assert_eq!(result, BlurError::ParseError as i32);
+        assert_eq!(data, original);
+    }
+
+    fn last_error() -> Option<String> {
+        let ptr = plugin_last_error();
+        if ptr.is_null() {
+            return None;
+        }
+        // SAFETY: ptr is non-null and points to the thread-local CString, which is not
+        // modified until the next process_image call.
+        Some(
+            unsafe { CStr::from_ptr(ptr) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+
+    #[test]
+    fn test_last_error_reports_parse_failure() {
+        let mut data = create_4x4_sharp_edge();
+        let result = blur_image(&mut data, 4, 4, "not valid json {{{");
+
+        assert_eq!(result, BlurError::ParseError as i32);
+        let message = last_error().expect("failure should set last error");
+        assert!(
+            message.contains("failed to parse params JSON"),
+            "unexpected message: {}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_last_error_cleared_on_success() {
+        let mut data = create_4x4_sharp_edge();
+        blur_image(&mut data, 4, 4, "not valid json {{{");
+        assert!(last_error().is_some());
+
+        let result = blur_image(&mut data, 4, 4, "{}");
+
+        assert_eq!(result, BlurError::Success as i32);
+        assert!(last_error().is_none());
+    }
+
+    #[test]
+    fn test_catch_panic_converts_panic_to_error_code() {
+        let result = catch_panic(|| panic!("index out of bounds"));
+
+        assert_eq!(result, BlurError::Panic as i32);
+        let message = last_error().expect("panic should set last error");
+        assert!(
+            message.contains("panicked: index out of bounds"),
+            "unexpected message: {}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_catch_panic_passes_through_result() {
+        assert_eq!(
+            catch_panic(|| BlurError::Success as i32),
+            BlurError::Success as i32
+        );
+    }
 }
@@ -0,0 +1,562 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use image::ImageFormat;
+use log::{error, info};
+
+/// An input image found by a batch scan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchItem {
+    pub input: PathBuf,
+    /// Path relative to the scanned directory (or the fixed prefix of a glob), preserved
+    /// under the output directory.
+    pub relative: PathBuf,
+}
+
+/// Outcome of a batch run.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub processed: usize,
+    pub skipped: usize,
+    pub failed: Vec<(PathBuf, anyhow::Error)>,
+}
+
+impl Summary {
+    pub fn total(&self) -> usize {
+        self.processed + self.skipped + self.failed.len()
+    }
+
+    /// Prints the summary to stdout, listing every failure with its reason.
+    pub fn print(&self) {
+        println!("Processed: {}", self.processed);
+        println!("Skipped:   {} (up to date)", self.skipped);
+        println!("Failed:    {}", self.failed.len());
+        for (input, err) in &self.failed {
+            println!("  {}: {:#}", input.display(), err);
+        }
+    }
+}
+
+/// Returns true when `input` names a directory or a glob pattern rather than a single file.
+pub fn is_batch_input(input: &Path) -> bool {
+    input.is_dir() || has_wildcards(&input.to_string_lossy())
+}
+
+fn has_wildcards(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// One image to process.
+#[derive(Debug)]
+struct Job {
+    input: PathBuf,
+    output: PathBuf,
+}
+
+/// Images to process, collected from explicit `--input`/`--output` pairs and from directory or
+/// glob expansion.
+#[derive(Debug, Default)]
+pub struct Plan {
+    jobs: Vec<Job>,
+    /// Expanded images whose output is already up to date.
+    skipped: usize,
+}
+
+impl Plan {
+    /// Adds a single input/output pair.
+    pub fn add(&mut self, input: &Path, output: &Path) {
+        self.jobs.push(Job {
+            input: input.to_path_buf(),
+            output: output.to_path_buf(),
+        });
+    }
+
+    /// Adds every image matched by `input` (a directory or glob), writing into `output_dir`
+    /// with relative paths preserved. With an output `format`, each output takes that
+    /// format's extension instead of the input's.
+    ///
+    /// Outputs newer than their input and every file in `dependencies` (params, pipeline and
+    /// plugin library files) are skipped unless `force` is set.
+    pub fn add_batch(
+        &mut self,
+        input: &Path,
+        output_dir: &Path,
+        format: Option<ImageFormat>,
+        force: bool,
+        dependencies: &[PathBuf],
+    ) -> Result<()> {
+        if output_dir.is_file() {
+            anyhow::bail!(
+                "Batch output must be a directory, but {} is a file",
+                output_dir.display()
+            );
+        }
+
+        let items = collect_inputs(input, Some(output_dir))?;
+        if items.is_empty() {
+            anyhow::bail!("No input images found for {}", input.display());
+        }
+        info!("Batch: {} input images in {}", items.len(), input.display());
+
+        for item in items {
+            let mut output = output_dir.join(&item.relative);
+            if let Some(format) = format {
+                output.set_extension(format.extensions_str()[0]);
+            }
+            if !force && is_up_to_date(&item.input, &output, dependencies) {
+                info!("Skipping up-to-date {}", output.display());
+                self.skipped += 1;
+                continue;
+            }
+            self.jobs.push(Job {
+                input: item.input,
+                output,
+            });
+        }
+        Ok(())
+    }
+
+    /// Processes all jobs on up to `threads` threads and returns the summary.
+    ///
+    /// `process` is shared by all threads. Outcomes are logged and reported in plan order,
+    /// whatever order the jobs finish in. Failures are collected instead of aborting.
+    pub fn execute(
+        self,
+        threads: usize,
+        process: impl Fn(&Path, &Path) -> Result<()> + Sync,
+    ) -> Summary {
+        let jobs = self.jobs;
+        let total = jobs.len();
+        let threads = threads.clamp(1, total.max(1));
+        if total > 1 {
+            info!("Processing {} images on {} threads", total, threads);
+        }
+
+        let next_job = AtomicUsize::new(0);
+        let mut results: Vec<Option<Result<()>>> = (0..total).map(|_| None).collect();
+        let mut summary = Summary {
+            skipped: self.skipped,
+            ..Summary::default()
+        };
+
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            for _ in 0..threads {
+                let sender = sender.clone();
+                let (jobs, next_job, process) = (&jobs, &next_job, &process);
+                scope.spawn(move || {
+                    loop {
+                        let index = next_job.fetch_add(1, Ordering::Relaxed);
+                        let Some(job) = jobs.get(index) else {
+                            break;
+                        };
+                        let result = job
+                            .output
+                            .parent()
+                            .filter(|parent| !parent.as_os_str().is_empty())
+                            .map_or(Ok(()), std::fs::create_dir_all)
+                            .with_context(|| {
+                                format!(
+                                    "Failed to create output directory for {}",
+                                    job.output.display()
+                                )
+                            })
+                            .and_then(|()| process(&job.input, &job.output));
+                        if sender.send((index, result)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            // Report the longest finished prefix so logs follow plan order.
+            let mut reported = 0;
+            for (index, result) in receiver {
+                results[index] = Some(result);
+                while let Some(Some(result)) = results.get_mut(reported) {
+                    let job = &jobs[reported];
+                    let position = format!("[{}/{}]", reported + 1, total);
+                    match result {
+                        Ok(()) => info!(
+                            "{} {} -> {}",
+                            position,
+                            job.input.display(),
+                            job.output.display()
+                        ),
+                        Err(err) => {
+                            error!("{} {} failed: {:#}", position, job.input.display(), err)
+                        }
+                    }
+                    reported += 1;
+                }
+            }
+        });
+
+        for (job, result) in jobs.into_iter().zip(results) {
+            match result.expect("every job reports a result") {
+                Ok(()) => summary.processed += 1,
+                Err(err) => summary.failed.push((job.input, err)),
+            }
+        }
+        summary
+    }
+}
+
+/// Lists the images matched by a directory or glob pattern, sorted by path.
+///
+/// A directory matches every file with a known image extension below it. A glob matches
+/// files whose path fits the pattern: `*` and `?` match within one path component, `**`
+/// matches any number of components. Anything below `exclude` (the output directory) is skipped.
+pub fn collect_inputs(input: &Path, exclude: Option<&Path>) -> Result<Vec<BatchItem>> {
+    let exclude = exclude.and_then(|dir| dir.canonicalize().ok());
+
+    let (root, pattern) = if input.is_dir() {
+        (input.to_path_buf(), None)
+    } else {
+        let (root, pattern) = split_glob(input);
+        (root, Some(pattern))
+    };
+
+    let mut files = Vec::new();
+    walk(&root, exclude.as_deref(), &mut files)
+        .with_context(|| format!("Failed to scan {}", root.display()))?;
+
+    let mut items: Vec<BatchItem> = files
+        .into_iter()
+        .filter_map(|path| {
+            let relative = path.strip_prefix(&root).ok()?.to_path_buf();
+            let matches = match &pattern {
+                Some(pattern) => glob_match(pattern, &components(&relative)),
+                None => image::ImageFormat::from_path(&path).is_ok(),
+            };
+            matches.then_some(BatchItem {
+                input: path,
+                relative,
+            })
+        })
+        .collect();
+    items.sort_by(|a, b| a.relative.cmp(&b.relative));
+    Ok(items)
+}
+
+/// Splits a glob into the directory to scan (its leading components without wildcards) and
+/// the remaining pattern components.
+fn split_glob(pattern: &Path) -> (PathBuf, Vec<String>) {
+    let mut root = PathBuf::new();
+    let mut rest = Vec::new();
+    for component in pattern.components() {
+        let text = component.as_os_str().to_string_lossy();
+        if rest.is_empty() && !has_wildcards(&text) {
+            root.push(component);
+        } else {
+            rest.push(text.into_owned());
+        }
+    }
+    if root.as_os_str().is_empty() {
+        root.push(".");
+    }
+    (root, rest)
+}
+
+fn components(path: &Path) -> Vec<String> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Recursively collects the files below `dir`, in sorted order.
+fn walk(dir: &Path, exclude: Option<&Path>, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if exclude.is_some() && path.canonicalize().ok().as_deref() == exclude {
+                continue;
+            }
+            walk(&path, exclude, files)?;
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Matches path components against glob components, where `**` spans any number of them.
+fn glob_match(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| glob_match(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((component, path_rest)) => {
+                wildcard_match(first, component) && glob_match(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Matches one path component against a pattern with `*` (any run) and `?` (one character).
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text index it is currently matched up to.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, t));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// An output is up to date when it is at least as new as its input and every dependency.
+fn is_up_to_date(input: &Path, output: &Path, dependencies: &[PathBuf]) -> bool {
+    let modified = |path: &Path| -> Option<SystemTime> { path.metadata().ok()?.modified().ok() };
+
+    let Some(output_time) = modified(output) else {
+        return false;
+    };
+    std::iter::once(input)
+        .chain(dependencies.iter().map(PathBuf::as_path))
+        .all(|path| modified(path).is_some_and(|time| time <= output_time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn strings(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(|part| part.to_string()).collect()
+    }
+
+    fn touch(path: &Path) {
+        std::fs::create_dir_all(path.parent().expect("test paths have a parent"))
+            .expect("failed to create directory");
+        std::fs::write(path, b"").expect("failed to write file");
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.png", "photo.png"));
+        assert!(wildcard_match("*.png", ".png"));
+        assert!(wildcard_match("img_??.jpg", "img_01.jpg"));
+        assert!(wildcard_match("a*b*c", "aXXbYYbc"));
+        assert!(!wildcard_match("*.png", "photo.jpg"));
+        assert!(!wildcard_match("img_??.jpg", "img_1.jpg"));
+        assert!(!wildcard_match("photo", "photo.png"));
+    }
+
+    #[test]
+    fn test_glob_match_double_star_spans_directories() {
+        let pattern = strings(&["**", "*.png"]);
+
+        assert!(glob_match(&pattern, &strings(&["a.png"])));
+        assert!(glob_match(&pattern, &strings(&["x", "y", "a.png"])));
+        assert!(!glob_match(&pattern, &strings(&["x", "a.jpg"])));
+
+        let pattern = strings(&["*.png"]);
+        assert!(!glob_match(&pattern, &strings(&["x", "a.png"])));
+    }
+
+    #[test]
+    fn test_split_glob() {
+        let (root, pattern) = split_glob(Path::new("photos/2024/**/*.png"));
+        assert_eq!(root, PathBuf::from("photos/2024"));
+        assert_eq!(pattern, strings(&["**", "*.png"]));
+
+        let (root, pattern) = split_glob(Path::new("*.png"));
+        assert_eq!(root, PathBuf::from("."));
+        assert_eq!(pattern, strings(&["*.png"]));
+    }
+
+    #[test]
+    fn test_collect_inputs_from_directory_skips_non_images_and_output() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        touch(&dir.path().join("b.png"));
+        touch(&dir.path().join("nested/a.jpg"));
+        touch(&dir.path().join("notes.txt"));
+        touch(&dir.path().join("out/old.png"));
+
+        let items =
+            collect_inputs(dir.path(), Some(&dir.path().join("out"))).expect("scan should succeed");
+
+        let relative: Vec<PathBuf> = items.into_iter().map(|item| item.relative).collect();
+        assert_eq!(
+            relative,
+            vec![PathBuf::from("b.png"), PathBuf::from("nested/a.jpg")]
+        );
+    }
+
+    #[test]
+    fn test_collect_inputs_from_glob_preserves_relative_paths() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        touch(&dir.path().join("a.png"));
+        touch(&dir.path().join("x/b.png"));
+        touch(&dir.path().join("x/c.jpg"));
+
+        let items =
+            collect_inputs(&dir.path().join("**/*.png"), None).expect("scan should succeed");
+
+        assert_eq!(
+            items,
+            vec![
+                BatchItem {
+                    input: dir.path().join("a.png"),
+                    relative: PathBuf::from("a.png"),
+                },
+                BatchItem {
+                    input: dir.path().join("x/b.png"),
+                    relative: PathBuf::from("x/b.png"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_execute_collects_failures_and_skips_up_to_date_outputs() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let input_dir = dir.path().join("in");
+        let output_dir = dir.path().join("out");
+        touch(&input_dir.join("bad.png"));
+        touch(&input_dir.join("good.png"));
+        touch(&input_dir.join("sub/done.png"));
+        touch(&output_dir.join("sub/done.png"));
+
+        let mut plan = Plan::default();
+        plan.add_batch(&input_dir, &output_dir, None, false, &[])
+            .expect("scan should succeed");
+        let calls = std::sync::Mutex::new(Vec::new());
+        let summary = plan.execute(1, |input, output| {
+            calls
+                .lock()
+                .expect("lock is never poisoned")
+                .push(output.to_path_buf());
+            if input.ends_with("bad.png") {
+                anyhow::bail!("broken image");
+            }
+            Ok(())
+        });
+
+        assert_eq!(summary.processed, 1);
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.total(), 3);
+        assert!(summary.failed[0].0.ends_with("bad.png"));
+        assert_eq!(
+            calls.into_inner().expect("lock is never poisoned"),
+            vec![output_dir.join("bad.png"), output_dir.join("good.png")]
+        );
+    }
+
+    #[test]
+    fn test_add_batch_force_keeps_up_to_date_outputs() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let input_dir = dir.path().join("in");
+        let output_dir = dir.path().join("out");
+        touch(&input_dir.join("done.png"));
+        touch(&output_dir.join("done.png"));
+
+        let mut plan = Plan::default();
+        plan.add_batch(&input_dir, &output_dir, None, true, &[])
+            .expect("scan should succeed");
+
+        assert_eq!(plan.jobs.len(), 1);
+        assert_eq!(plan.skipped, 0);
+    }
+
+    #[test]
+    fn test_add_batch_uses_output_format_extension() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let input_dir = dir.path().join("in");
+        let output_dir = dir.path().join("out");
+        touch(&input_dir.join("a.png"));
+        touch(&input_dir.join("sub/b.png"));
+        // Up to date under the input's name, but that is not the file being written
+        touch(&output_dir.join("a.png"));
+
+        let mut plan = Plan::default();
+        plan.add_batch(&input_dir, &output_dir, Some(ImageFormat::Jpeg), false, &[])
+            .expect("scan should succeed");
+
+        let outputs: Vec<PathBuf> = plan.jobs.iter().map(|job| job.output.clone()).collect();
+        assert_eq!(
+            outputs,
+            vec![output_dir.join("a.jpg"), output_dir.join("sub/b.jpg")]
+        );
+        assert_eq!(plan.skipped, 0);
+    }
+
+    #[test]
+    fn test_execute_in_parallel_reports_in_plan_order() {
+        let mut plan = Plan::default();
+        for index in 0..16 {
+            plan.add(
+                Path::new(&format!("in{}.png", index)),
+                Path::new(&format!("out{}.png", index)),
+            );
+        }
+
+        let summary = plan.execute(4, |input, _| {
+            // Later jobs finish first, so completion order differs from plan order.
+            let index: u64 = input.to_string_lossy()[2..]
+                .trim_end_matches(".png")
+                .parse()
+                .expect("numbered test input");
+            thread::sleep(std::time::Duration::from_millis(16 - index));
+            if index.is_multiple_of(5) {
+                anyhow::bail!("failure {}", index);
+            }
+            Ok(())
+        });
+
+        assert_eq!(summary.processed, 12);
+        let failed: Vec<String> = summary
+            .failed
+            .iter()
+            .map(|(input, err)| format!("{}: {}", input.display(), err))
+            .collect();
+        assert_eq!(
+            failed,
+            vec![
+                "in0.png: failure 0",
+                "in5.png: failure 5",
+                "in10.png: failure 10",
+                "in15.png: failure 15",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_up_to_date_requires_existing_output() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let input = dir.path().join("in.png");
+        touch(&input);
+
+        assert!(!is_up_to_date(&input, &dir.path().join("missing.png"), &[]));
+    }
+}
@@ -0,0 +1,87 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+/// Set by the first Ctrl-C; every running and later plugin call is cancelled.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Makes Ctrl-C cancel the running plugin calls instead of killing the process; a second
+/// Ctrl-C exits immediately. Only supported on Unix, elsewhere Ctrl-C keeps its default.
+#[cfg(unix)]
+pub fn handle_interrupts() -> Result<()> {
+    extern "C" fn on_interrupt(_signal: libc::c_int) {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            // SAFETY: _exit is async-signal-safe and skips the non-reentrant exit handlers.
+            unsafe { libc::_exit(130) };
+        }
+    }
+
+    let handler: extern "C" fn(libc::c_int) = on_interrupt;
+    // SAFETY: The handler only touches an atomic and calls _exit, both async-signal-safe.
+    let previous = unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
+    if previous == libc::SIG_ERR {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn handle_interrupts() -> Result<()> {
+    Ok(())
+}
+
+/// Fails once Ctrl-C has been pressed, so nothing is written after an interrupt.
+pub fn check_interrupted() -> Result<()> {
+    if INTERRUPTED.load(Ordering::Relaxed) {
+        anyhow::bail!("Processing was interrupted (Ctrl-C)");
+    }
+    Ok(())
+}
+
+/// Tells a plugin call to stop early: after Ctrl-C, or once it has run for longer than its
+/// timeout.
+#[derive(Debug, Clone, Copy)]
+pub struct Cancel {
+    started: Instant,
+    timeout: Option<Duration>,
+}
+
+impl Cancel {
+    /// Starts the clock for a call limited to `timeout`, if any.
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            started: Instant::now(),
+            timeout,
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.reason().is_some()
+    }
+
+    /// Describes why the call should stop, or returns `None` while it may go on.
+    pub fn reason(&self) -> Option<String> {
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            return Some("was interrupted (Ctrl-C)".to_string());
+        }
+        self.timeout
+            .filter(|&timeout| self.started.elapsed() >= timeout)
+            .map(|timeout| format!("timed out after {:?}", timeout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_after_timeout() {
+        assert_eq!(Cancel::new(None).reason(), None);
+        assert!(!Cancel::new(Some(Duration::from_secs(3600))).is_cancelled());
+
+        let cancel = Cancel::new(Some(Duration::ZERO));
+        assert!(cancel.is_cancelled());
+        assert_eq!(cancel.reason().as_deref(), Some("timed out after 0ns"));
+    }
+}
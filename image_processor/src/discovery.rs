@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use log::debug;
+
+use crate::plugin_loader::{self, PluginInfo};
+
+/// A library found by a plugin directory scan.
+#[derive(Debug)]
+pub struct Discovered {
+    /// Plugin name derived from the filename, as passed to `--plugin`.
+    pub name: String,
+    pub path: PathBuf,
+    /// Metadata read from the library, or why it could not be probed.
+    pub info: Result<PluginInfo>,
+}
+
+/// Lists every file in `dir` named like a plugin library and probes its metadata exports.
+///
+/// Files are sorted by name and subdirectories are not scanned. A library that fails to
+/// load, has an unsupported ABI or lacks metadata is returned with its error instead of
+/// aborting the scan; only an unreadable directory is an error.
+pub fn scan(dir: &Path) -> Result<Vec<Discovered>> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read plugin directory: {}", dir.display()))?;
+
+    let mut libraries = Vec::new();
+    for entry in entries {
+        let entry =
+            entry.with_context(|| format!("Failed to read plugin directory: {}", dir.display()))?;
+        let path = entry.path();
+        let Some(name) = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .and_then(plugin_loader::plugin_name_from_filename)
+        else {
+            continue;
+        };
+        if path.is_file() {
+            libraries.push((name.to_string(), path));
+        }
+    }
+    libraries.sort();
+
+    Ok(libraries
+        .into_iter()
+        .map(|(name, path)| {
+            debug!("Probing plugin library: {}", path.display());
+            let info = plugin_loader::info(&path);
+            Discovered { name, path, info }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scan_reports_invalid_libraries_and_ignores_other_files() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let broken = dir
+            .path()
+            .join(plugin_loader::library_filename("broken_plugin"));
+        std::fs::write(&broken, b"not a library").expect("failed to write file");
+        std::fs::write(dir.path().join("notes.txt"), b"text").expect("failed to write file");
+        std::fs::create_dir(
+            dir.path()
+                .join(plugin_loader::library_filename("dir_plugin")),
+        )
+        .expect("failed to create directory");
+
+        let found = scan(dir.path()).expect("scan should succeed");
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "broken_plugin");
+        assert_eq!(found[0].path, broken);
+        assert!(found[0].info.is_err());
+    }
+
+    #[test]
+    fn test_scan_missing_directory_fails() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let missing = dir.path().join("missing");
+
+        let err = scan(&missing).expect_err("missing directory should fail");
+
+        assert!(err.to_string().contains("Failed to read plugin directory"));
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_scan_finds_bundled_plugins() {
+        let found = scan(Path::new("../target/debug")).expect("scan should succeed");
+        let names: Vec<&str> = found.iter().map(|plugin| plugin.name.as_str()).collect();
+
+        for bundled in [
+            "blur_plugin",
+            "mirror_plugin",
+            "panic_plugin",
+            "rotate_plugin",
+        ] {
+            assert!(
+                names.contains(&bundled),
+                "missing {} in {:?}",
+                bundled,
+                names
+            );
+        }
+        let blur = found
+            .iter()
+            .find(|plugin| plugin.name == "blur_plugin")
+            .expect("blur_plugin should be found");
+        let info = blur.info.as_ref().expect("blur_plugin should be valid");
+        assert_eq!(info.name, "blur_plugin");
+        assert!(info.params_schema.is_some());
+    }
+}
@@ -0,0 +1,498 @@
+use std::fs;
+use std::io::{self, BufRead, Cursor, Read, Seek, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::{ColorType, DynamicImage, ImageFormat, ImageReader, ImageResult};
+use log::{debug, info};
+
+/// `--input` / `--output` value standing for stdin / stdout.
+pub const STDIO: &str = "-";
+
+/// Returns true when `path` is `-`, i.e. stdin as an input or stdout as an output.
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO
+}
+
+/// Parses an `--output-format` value: any extension of a format the `image` crate can
+/// write, such as `png`, `jpg` or `webp`.
+pub fn parse_format(value: &str) -> std::result::Result<ImageFormat, String> {
+    ImageFormat::from_extension(value)
+        .filter(ImageFormat::writing_enabled)
+        .ok_or_else(|| {
+            let supported: Vec<&str> = ImageFormat::all()
+                .filter(ImageFormat::writing_enabled)
+                .filter_map(|format| format.extensions_str().first().copied())
+                .collect();
+            format!(
+                "unsupported output format '{}' (supported: {})",
+                value,
+                supported.join(", ")
+            )
+        })
+}
+
+/// Output format and encoder settings; `None` keeps the default.
+///
+/// Settings for a format other than the one being written are ignored, so one set of options
+/// can serve a batch with mixed output formats.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EncodeOptions {
+    /// Overrides the format implied by the output file extension.
+    pub format: Option<ImageFormat>,
+    pub png_compression: Option<CompressionType>,
+    pub png_filter: Option<FilterType>,
+    /// JPEG quality, 1-100.
+    pub jpeg_quality: Option<u8>,
+}
+
+/// Parses a `--png-compression` value: `fast`, `default`, `best`, `none` or a level 1-9.
+pub fn parse_png_compression(value: &str) -> std::result::Result<CompressionType, String> {
+    match value {
+        "fast" => Ok(CompressionType::Fast),
+        "default" => Ok(CompressionType::Default),
+        "best" => Ok(CompressionType::Best),
+        "none" => Ok(CompressionType::Uncompressed),
+        _ => match value.parse::<u8>() {
+            Ok(level @ 1..=9) => Ok(CompressionType::Level(level)),
+            _ => Err(format!(
+                "invalid PNG compression '{}' (expected fast, default, best, none or 1-9)",
+                value
+            )),
+        },
+    }
+}
+
+/// Parses a `--png-filter` value.
+pub fn parse_png_filter(value: &str) -> std::result::Result<FilterType, String> {
+    match value {
+        "adaptive" => Ok(FilterType::Adaptive),
+        "none" => Ok(FilterType::NoFilter),
+        "sub" => Ok(FilterType::Sub),
+        "up" => Ok(FilterType::Up),
+        "avg" => Ok(FilterType::Avg),
+        "paeth" => Ok(FilterType::Paeth),
+        _ => Err(format!(
+            "invalid PNG filter '{}' (expected adaptive, none, sub, up, avg or paeth)",
+            value
+        )),
+    }
+}
+
+/// Loads an image from a file, or from stdin for `-`, at its original bit depth.
+///
+/// The format is detected from the content's magic bytes; a file's extension is only a
+/// fallback for formats without a signature.
+pub fn load(input: &Path) -> Result<DynamicImage> {
+    let image = if is_stdio(input) {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .lock()
+            .read_to_end(&mut bytes)
+            .context("Failed to read image from stdin")?;
+        debug!("Read {} bytes from stdin", bytes.len());
+        decode(ImageReader::new(Cursor::new(bytes))).context("Failed to load image from stdin")?
+    } else {
+        ImageReader::open(input)
+            .map_err(anyhow::Error::from)
+            .and_then(decode)
+            .with_context(|| format!("Failed to load image: {}", input.display()))?
+    };
+    debug!("Decoded {:?} image", image.color());
+    Ok(image)
+}
+
+fn decode<R: BufRead + Seek>(reader: ImageReader<R>) -> Result<DynamicImage> {
+    Ok(reader.with_guessed_format()?.decode()?)
+}
+
+/// Encodes `image` and writes it to a file, or to stdout for `-`.
+///
+/// Without `options.format` the file extension decides; stdout has no extension, so writing
+/// there requires one. Formats without an alpha channel (e.g. JPEG) drop it, and 16-bit or
+/// float images are reduced to the deepest layout the format can store.
+///
+/// The image is encoded in memory before anything is written, so an encoder error leaves no
+/// truncated file that a later batch run would take for up to date.
+pub fn save(image: DynamicImage, output: &Path, options: &EncodeOptions) -> Result<()> {
+    let format = output_format(output, options)?;
+    let mut bytes = Cursor::new(Vec::new());
+    let encoded = encode(&image, format, options, &mut bytes)
+        .with_context(|| format!("Failed to encode image as {:?}", format));
+    let bytes = bytes.into_inner();
+
+    if is_stdio(output) {
+        encoded?;
+        let mut stdout = io::stdout().lock();
+        stdout
+            .write_all(&bytes)
+            .and_then(|()| stdout.flush())
+            .context("Failed to write image to stdout")?;
+        info!("Wrote {} bytes to stdout", bytes.len());
+        return Ok(());
+    }
+
+    encoded
+        .and_then(|()| {
+            fs::write(output, &bytes).inspect_err(|_| {
+                // Do not leave a partly written file behind either
+                let _ = fs::remove_file(output);
+            })?;
+            Ok(())
+        })
+        .with_context(|| format!("Failed to save image: {}", output.display()))?;
+    info!("Saved image to: {}", output.display());
+    Ok(())
+}
+
+/// Returns the format an image saved to `output` gets: `options.format`, or the one implied
+/// by the file extension.
+pub fn output_format(output: &Path, options: &EncodeOptions) -> Result<ImageFormat> {
+    if let Some(format) = options.format {
+        return Ok(format);
+    }
+    if is_stdio(output) {
+        anyhow::bail!("--output-format is required when writing to stdout");
+    }
+    ImageFormat::from_path(output).with_context(|| {
+        format!(
+            "Cannot tell the output format from {}; use --output-format",
+            output.display()
+        )
+    })
+}
+
+/// Writes `image` as `format`, applying the encoder settings that belong to that format.
+fn encode<W: Write + Seek>(
+    image: &DynamicImage,
+    format: ImageFormat,
+    options: &EncodeOptions,
+    writer: &mut W,
+) -> ImageResult<()> {
+    let converted = storable(image, format);
+    let image = converted.as_ref().unwrap_or(image);
+    match (format, options.jpeg_quality) {
+        (ImageFormat::Png, _) => image.write_with_encoder(PngEncoder::new_with_quality(
+            writer,
+            options.png_compression.unwrap_or_default(),
+            options.png_filter.unwrap_or_default(),
+        )),
+        (ImageFormat::Jpeg, Some(quality)) => {
+            image.write_with_encoder(JpegEncoder::new_with_quality(writer, quality))
+        }
+        _ => image.write_to(writer, format),
+    }
+}
+
+/// Converts `image` to the deepest layout `format` can store, or returns `None` when the
+/// encoder takes it as is. JPEG, BMP, TGA and WebP encoders convert on their own.
+pub fn storable(image: &DynamicImage, format: ImageFormat) -> Option<DynamicImage> {
+    match (format, image.color()) {
+        (ImageFormat::OpenExr | ImageFormat::Tiff, ColorType::Rgba32F)
+        | (ImageFormat::OpenExr, ColorType::Rgb32F)
+        | (ImageFormat::Farbfeld, ColorType::Rgba16)
+        | (ImageFormat::Jpeg | ImageFormat::Bmp | ImageFormat::Tga | ImageFormat::WebP, _) => None,
+        (ImageFormat::OpenExr, _) => Some(image.to_rgba32f().into()),
+        // The TIFF encoder has no gray-with-alpha layout, the PNM one none at 16 bits
+        (ImageFormat::Tiff, ColorType::La8) => Some(image.to_rgba8().into()),
+        (ImageFormat::Tiff | ImageFormat::Pnm, ColorType::La16) => Some(image.to_rgba16().into()),
+        (
+            ImageFormat::Png | ImageFormat::Tiff | ImageFormat::Pnm,
+            ColorType::L8
+            | ColorType::La8
+            | ColorType::Rgb8
+            | ColorType::Rgba8
+            | ColorType::L16
+            | ColorType::La16
+            | ColorType::Rgb16
+            | ColorType::Rgba16,
+        )
+        | (_, ColorType::Rgba8) => None,
+        (ImageFormat::Png | ImageFormat::Tiff | ImageFormat::Pnm, ColorType::Rgb32F) => {
+            Some(image.to_rgb16().into())
+        }
+        (ImageFormat::Png | ImageFormat::Tiff | ImageFormat::Pnm | ImageFormat::Farbfeld, _) => {
+            Some(image.to_rgba16().into())
+        }
+        _ => Some(image.to_rgba8().into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+    use tempfile::TempDir;
+
+    fn checkerboard() -> RgbaImage {
+        RgbaImage::from_fn(4, 3, |x, y| {
+            if (x + y) % 2 == 0 {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                image::Rgba([0, 0, 255, 128])
+            }
+        })
+    }
+
+    #[test]
+    fn test_is_stdio_only_matches_dash() {
+        assert!(is_stdio(Path::new("-")));
+        assert!(!is_stdio(Path::new("./-")));
+        assert!(!is_stdio(Path::new("out.png")));
+    }
+
+    #[test]
+    fn test_parse_format_accepts_extensions() {
+        assert_eq!(parse_format("png"), Ok(ImageFormat::Png));
+        assert_eq!(parse_format("JPG"), Ok(ImageFormat::Jpeg));
+        assert_eq!(parse_format("jpeg"), Ok(ImageFormat::Jpeg));
+
+        let err = parse_format("docx").expect_err("not an image format");
+        assert!(
+            err.contains("unsupported output format 'docx'"),
+            "got: {}",
+            err
+        );
+        assert!(err.contains("png"), "got: {}", err);
+    }
+
+    #[test]
+    fn test_load_detects_format_from_content() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        // PNG bytes behind a misleading extension
+        let path = dir.path().join("image.jpg");
+        checkerboard()
+            .save_with_format(&path, ImageFormat::Png)
+            .expect("failed to write image");
+
+        let loaded = load(&path).expect("PNG content should be detected");
+
+        assert_eq!(loaded.into_rgba8(), checkerboard());
+    }
+
+    #[test]
+    fn test_save_with_explicit_format_ignores_extension() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let path = dir.path().join("image.out");
+
+        let options = EncodeOptions {
+            format: Some(ImageFormat::Png),
+            ..EncodeOptions::default()
+        };
+        save(checkerboard().into(), &path, &options).expect("save should succeed");
+
+        let bytes = std::fs::read(&path).expect("output should exist");
+        assert_eq!(image::guess_format(&bytes).ok(), Some(ImageFormat::Png));
+    }
+
+    #[test]
+    fn test_save_to_stdout_requires_format() {
+        let err = save(
+            checkerboard().into(),
+            Path::new("-"),
+            &EncodeOptions::default(),
+        )
+        .expect_err("format is required");
+
+        assert!(err.to_string().contains("--output-format is required"));
+    }
+
+    #[test]
+    fn test_save_without_extension_or_format_fails() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let path = dir.path().join("image");
+
+        let err = save(checkerboard().into(), &path, &EncodeOptions::default())
+            .expect_err("format cannot be determined");
+
+        assert!(err.to_string().contains("use --output-format"));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_failed_encode_writes_nothing() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let path = dir.path().join("wide.jpg");
+        // JPEG dimensions are limited to 65535 pixels
+        let wide = DynamicImage::new_luma8(70_000, 1);
+
+        let err = save(wide, &path, &EncodeOptions::default()).expect_err("too wide for JPEG");
+        assert!(
+            format!("{:#}", err).contains("Failed to encode image as Jpeg"),
+            "got: {:#}",
+            err
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_save_webp_is_lossless() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let path = dir.path().join("image.webp");
+
+        save(checkerboard().into(), &path, &EncodeOptions::default()).expect("save should succeed");
+        assert_eq!(
+            load(&path).expect("load should succeed").into_rgba8(),
+            checkerboard()
+        );
+    }
+
+    #[test]
+    fn test_save_keeps_16_bit_png_and_reduces_float() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let deep = DynamicImage::ImageRgba16(image::ImageBuffer::from_fn(3, 2, |x, y| {
+            image::Rgba([x as u16 * 20000 + 1, y as u16 * 30000 + 3, 12345, 65535])
+        }));
+
+        let path = dir.path().join("deep.png");
+        save(deep.clone(), &path, &EncodeOptions::default()).expect("save should succeed");
+        assert_eq!(load(&path).expect("load should succeed"), deep);
+
+        // PNG has no float samples; the image is stored as 16-bit instead
+        let float = DynamicImage::ImageRgba32F(deep.to_rgba32f());
+        let path = dir.path().join("float.png");
+        save(float, &path, &EncodeOptions::default()).expect("save should succeed");
+        assert_eq!(load(&path).expect("load should succeed"), deep);
+    }
+
+    #[test]
+    fn test_save_keeps_16_bit_gray_and_rgb_layouts() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let gray = DynamicImage::ImageLuma16(image::ImageBuffer::from_fn(3, 2, |x, y| {
+            image::Luma([x as u16 * 20000 + y as u16])
+        }));
+        for image in [
+            gray.clone(),
+            DynamicImage::ImageLumaA16(gray.to_luma_alpha16()),
+            DynamicImage::ImageRgb16(gray.to_rgb16()),
+        ] {
+            for extension in ["png", "tiff", "pnm"] {
+                let path = dir
+                    .path()
+                    .join(format!("{:?}.{}", image.color(), extension));
+                save(image.clone(), &path, &EncodeOptions::default()).expect("save should succeed");
+                let expected = match (extension, image.color()) {
+                    ("tiff" | "pnm", ColorType::La16) => {
+                        DynamicImage::ImageRgba16(image.to_rgba16())
+                    }
+                    _ => image.clone(),
+                };
+                assert_eq!(
+                    load(&path).expect("load should succeed"),
+                    expected,
+                    "{}",
+                    path.display()
+                );
+            }
+        }
+
+        let float = DynamicImage::ImageRgb32F(gray.to_rgb32f());
+        let path = dir.path().join("float.png");
+        save(float, &path, &EncodeOptions::default()).expect("save should succeed");
+        assert_eq!(
+            load(&path).expect("load should succeed"),
+            DynamicImage::ImageRgb16(gray.to_rgb16())
+        );
+    }
+
+    #[test]
+    fn test_save_keeps_gray_layouts_where_supported() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let gray_alpha = DynamicImage::ImageLumaA8(image::ImageBuffer::from_fn(3, 2, |x, y| {
+            image::LumaA([x as u8 * 80, y as u8 * 100 + 50])
+        }));
+
+        let path = dir.path().join("gray.png");
+        save(gray_alpha.clone(), &path, &EncodeOptions::default()).expect("save should succeed");
+        assert_eq!(load(&path).expect("load should succeed"), gray_alpha);
+
+        // TIFF has no gray-with-alpha layout; the image is stored as RGBA instead
+        let path = dir.path().join("gray.tiff");
+        save(gray_alpha.clone(), &path, &EncodeOptions::default()).expect("save should succeed");
+        assert_eq!(
+            load(&path).expect("load should succeed"),
+            DynamicImage::ImageRgba8(gray_alpha.to_rgba8())
+        );
+    }
+
+    #[test]
+    fn test_parse_png_options() {
+        assert_eq!(parse_png_compression("best"), Ok(CompressionType::Best));
+        assert_eq!(
+            parse_png_compression("none"),
+            Ok(CompressionType::Uncompressed)
+        );
+        assert_eq!(parse_png_compression("6"), Ok(CompressionType::Level(6)));
+        assert!(parse_png_compression("0").is_err());
+        assert!(parse_png_compression("10").is_err());
+        assert!(parse_png_compression("max").is_err());
+
+        assert_eq!(parse_png_filter("paeth"), Ok(FilterType::Paeth));
+        assert_eq!(parse_png_filter("none"), Ok(FilterType::NoFilter));
+        assert!(parse_png_filter("median").is_err());
+    }
+
+    /// Size of `image` encoded with `options`.
+    fn encoded_size(image: &RgbaImage, format: ImageFormat, options: EncodeOptions) -> usize {
+        let mut bytes = Cursor::new(Vec::new());
+        encode(
+            &DynamicImage::ImageRgba8(image.clone()),
+            format,
+            &options,
+            &mut bytes,
+        )
+        .expect("encoding should succeed");
+        bytes.into_inner().len()
+    }
+
+    #[test]
+    fn test_encoder_options_trade_size_for_quality() {
+        let gradient = RgbaImage::from_fn(64, 64, |x, y| {
+            image::Rgba([(x * 4) as u8, (y * 4) as u8, ((x ^ y) * 4) as u8, 255])
+        });
+
+        let uncompressed = encoded_size(
+            &gradient,
+            ImageFormat::Png,
+            EncodeOptions {
+                png_compression: Some(CompressionType::Uncompressed),
+                ..EncodeOptions::default()
+            },
+        );
+        let best = encoded_size(
+            &gradient,
+            ImageFormat::Png,
+            EncodeOptions {
+                png_compression: Some(CompressionType::Best),
+                png_filter: Some(FilterType::Paeth),
+                ..EncodeOptions::default()
+            },
+        );
+        assert!(
+            best < uncompressed,
+            "best {} >= none {}",
+            best,
+            uncompressed
+        );
+
+        let low = encoded_size(
+            &gradient,
+            ImageFormat::Jpeg,
+            EncodeOptions {
+                jpeg_quality: Some(10),
+                ..EncodeOptions::default()
+            },
+        );
+        let high = encoded_size(
+            &gradient,
+            ImageFormat::Jpeg,
+            EncodeOptions {
+                jpeg_quality: Some(95),
+                ..EncodeOptions::default()
+            },
+        );
+        assert!(low < high, "quality 10 ({}) >= quality 95 ({})", low, high);
+    }
+}
@@ -1,80 +1,459 @@
-use anyhow::{Context, Result};
-use clap::Parser;
-use image::RgbaImage;
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use image::ImageFormat;
+use image::codecs::png::{CompressionType, FilterType};
 use log::{debug, info};
-use std::path::PathBuf;
+use search_path::SearchPath;
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+mod batch;
+mod cancel;
+mod discovery;
+mod image_io;
+mod params_schema;
+mod pipeline;
+mod pixels;
 mod plugin_loader;
+mod progress;
+mod sandbox;
+mod search_path;
+mod tiles;
+
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    /// Log progress (-v) or every detail, including each plugin directory tried (-vv);
+    /// with a subcommand, put it after the subcommand name (`info -v`)
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    process: Option<Args>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show metadata exported by a plugin
+    Info {
+        /// Plugin name (without extension)
+        #[arg(long)]
+        plugin: String,
+
+        /// Directory to search for plugins before the configured ones; repeatable
+        #[arg(long)]
+        plugin_path: Vec<PathBuf>,
+    },
+
+    /// List the plugins found in plugin directories, with versions and parameters
+    ListPlugins {
+        /// Directory to scan before the configured ones; repeatable
+        #[arg(long)]
+        plugin_path: Vec<PathBuf>,
+    },
+
+    /// Internal: run one plugin call for an `--isolate` parent process
+    #[command(hide = true)]
+    Worker {
+        /// Full path to the plugin library
+        #[arg(long)]
+        library: PathBuf,
+
+        /// Address-space limit in MiB
+        #[arg(long)]
+        memory_limit: Option<u64>,
+    },
+}
 
 #[derive(Parser)]
 struct Args {
-    /// Path to input PNG image
-    #[arg(long)]
-    input: PathBuf,
+    /// Path to input image (`-` for stdin), or a directory or quoted glob pattern for batch
+    /// mode; repeat together with --output to process several inputs
+    #[arg(long, required = true)]
+    input: Vec<PathBuf>,
 
-    /// Path to save output PNG image
+    /// Path to save output image (`-` for stdout, which needs --output-format; the output
+    /// directory in batch mode); paired with the --input at the same position
+    #[arg(long, required = true)]
+    output: Vec<PathBuf>,
+
+    /// Number of images to process in parallel
+    #[arg(long, short = 'j', value_name = "N", default_value = "1")]
+    jobs: NonZeroUsize,
+
+    /// Output image format (png, jpg, webp, ...); defaults to the --output extension.
+    /// WebP is always written lossless
+    #[arg(long, value_name = "FORMAT", value_parser = image_io::parse_format)]
+    output_format: Option<ImageFormat>,
+
+    /// PNG compression: fast (default), default, best, none or a level from 1 to 9
+    #[arg(long, value_name = "LEVEL", value_parser = image_io::parse_png_compression)]
+    png_compression: Option<CompressionType>,
+
+    /// PNG row filter: adaptive (default), none, sub, up, avg or paeth
+    #[arg(long, value_name = "FILTER", value_parser = image_io::parse_png_filter)]
+    png_filter: Option<FilterType>,
+
+    /// JPEG quality from 1 (smallest) to 100 (best); the encoder default is 75
+    #[arg(long, value_name = "QUALITY", value_parser = clap::value_parser!(u8).range(1..=100))]
+    jpeg_quality: Option<u8>,
+
+    /// Batch mode: reprocess images whose output is already up to date
     #[arg(long)]
-    output: PathBuf,
+    force: bool,
 
     /// Plugin name (without extension)
+    #[arg(long, required_unless_present_any = ["steps", "pipeline"])]
+    plugin: Option<String>,
+
+    /// Path to JSON parameters file; without one the plugin's defaults apply
+    #[arg(long, requires = "plugin")]
+    params: Option<PathBuf>,
+
+    /// Inline JSON parameters object, merged over the params file
+    #[arg(long, value_name = "JSON", requires = "plugin")]
+    params_json: Option<String>,
+
+    /// Single parameter as KEY=VALUE, merged over the params file and --params-json;
+    /// the value is converted to the type the plugin declares. Repeatable
+    #[arg(long = "param", value_name = "KEY=VALUE", requires = "plugin")]
+    param_overrides: Vec<pipeline::ParamArg>,
+
+    /// Pipeline step as PLUGIN=PARAMS_FILE; repeat to run several plugins in order
+    #[arg(
+        long = "step",
+        value_name = "PLUGIN=PARAMS_FILE",
+        conflicts_with_all = ["plugin", "params", "params_json", "param_overrides"]
+    )]
+    steps: Vec<pipeline::StepArg>,
+
+    /// JSON pipeline file with named steps and inline parameters
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["plugin", "params", "params_json", "param_overrides", "steps"]
+    )]
+    pipeline: Option<PathBuf>,
+
+    /// Apply the plugins only to the rectangle X,Y,WIDTH,HEIGHT (in pixels from the top-left
+    /// corner), leaving the rest of the image untouched
+    #[arg(long, value_name = "X,Y,W,H")]
+    region: Option<pixels::Region>,
+
+    /// Process the image in SIZE x SIZE tiles; PNG files are streamed, so images larger than
+    /// memory can be processed. Every plugin must declare the overlap it needs
+    #[arg(
+        long,
+        value_name = "SIZE",
+        conflicts_with = "region",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    tile_size: Option<u32>,
+
+    /// Pixels of the neighbouring tiles each tile is processed with; defaults to what the
+    /// plugins declare, and is required with --isolate
+    #[arg(long, value_name = "PIXELS", requires = "tile_size")]
+    tile_overlap: Option<u32>,
+
+    /// Directory to search for plugins before the configured ones; repeatable
     #[arg(long)]
-    plugin: String,
+    plugin_path: Vec<PathBuf>,
 
-    /// Path to JSON parameters file
+    /// Run the plugin in a separate worker process so crashes cannot take down the host
     #[arg(long)]
-    params: PathBuf,
+    isolate: bool,
 
-    /// Directory containing plugins
-    #[arg(long, default_value = "target/debug")]
-    plugin_path: PathBuf,
+    /// Cancel any plugin call that runs longer than this many seconds; an isolated worker is
+    /// killed
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// Address-space limit for the isolated worker in MiB (Unix only)
+    #[arg(long, value_name = "MB", requires = "isolate")]
+    memory_limit: Option<u64>,
 }
 
 fn main() -> Result<()> {
-    env_logger::init();
+    let cli = Cli::parse();
+
+    // RUST_LOG, when set, overrides -v
+    let default_level = match cli.verbose {
+        0 => "error",
+        1 => "info",
+        _ => "debug",
+    };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(default_level))
+        .init();
+
+    match cli.command {
+        Some(Command::Info {
+            plugin,
+            plugin_path,
+        }) => print_plugin_info(&plugin, &SearchPath::resolve(&plugin_path)?),
+        Some(Command::ListPlugins { plugin_path }) => {
+            print_plugin_list(&SearchPath::resolve(&plugin_path)?);
+            Ok(())
+        }
+        Some(Command::Worker {
+            library,
+            memory_limit,
+        }) => sandbox::run_worker(&library, memory_limit),
+        None => run(cli
+            .process
+            .expect("clap requires processing arguments when no subcommand is given")),
+    }
+}
 
-    let args = Args::parse();
+/// Prints the metadata descriptor of a plugin.
+fn print_plugin_info(plugin: &str, search_path: &SearchPath) -> Result<()> {
+    let plugin_library_path = search_path.find(plugin)?;
+    let plugin_info = plugin_loader::info(&plugin_library_path)?;
 
-    // Load PNG image and convert to RGBA8
-    info!("Loading image from: {}", args.input.display());
-    let img = image::open(&args.input)
-        .with_context(|| format!("Failed to load image: {}", args.input.display()))?
-        .into_rgba8();
+    println!("Name:        {}", plugin_info.name);
+    println!("Version:     {}", plugin_info.version);
+    println!("Description: {}", plugin_info.description);
+    println!("Author:      {}", plugin_info.author);
+    println!("Operations:  {}", plugin_info.operations.join(", "));
+    println!("ABI version: {}", plugin_info.abi_version);
+    println!("Pixels:      {}", plugin_info.pixel_formats);
+    println!("Library:     {}", plugin_library_path.display());
 
-    // Extract dimensions and raw bytes
-    let (width, height) = img.dimensions();
-    let mut rgba_data: Vec<u8> = img.into_raw();
-    debug!(
-        "Loaded image: {}x{} ({} bytes)",
-        width,
-        height,
-        rgba_data.len()
-    );
+    match &plugin_info.params_schema {
+        Some(schema) => {
+            println!();
+            println!("Parameters:");
+            for line in schema.help_table().lines() {
+                println!("  {}", line);
+            }
+        }
+        None => println!("Parameters:  (plugin does not export a parameter schema)"),
+    }
+
+    Ok(())
+}
+
+/// Prints every plugin found on the search path, reporting libraries that cannot be probed
+/// and directories that cannot be read without stopping the scan.
+///
+/// Missing directories are only reported when given with `--plugin-path`; a plugin that
+/// also exists in an earlier directory is marked as shadowed.
+fn print_plugin_list(search_path: &SearchPath) {
+    let mut valid = 0;
+    let mut invalid = 0;
+    let mut seen = HashSet::new();
+
+    for candidate in search_path.dirs() {
+        if !candidate.dir.is_dir() && candidate.source != search_path::Source::CommandLine {
+            continue;
+        }
+        println!("{} ({})", candidate.dir.display(), candidate.source);
+        let plugins = match discovery::scan(&candidate.dir) {
+            Ok(plugins) => plugins,
+            Err(err) => {
+                println!("  (cannot be scanned: {:#})", err);
+                continue;
+            }
+        };
+        if plugins.is_empty() {
+            println!("  (no plugins)");
+        }
+
+        for plugin in plugins {
+            let shadowed = if seen.insert(plugin.name.clone()) {
+                ""
+            } else {
+                " [shadowed]"
+            };
+            match &plugin.info {
+                Ok(info) => {
+                    valid += 1;
+                    println!(
+                        "  {} {} (ABI v{}){} - {}",
+                        plugin.name, info.version, info.abi_version, shadowed, info.description
+                    );
+                    match &info.params_schema {
+                        Some(schema) => println!("    Parameters: {}", schema.summary()),
+                        None => println!("    Parameters: (no parameter schema)"),
+                    }
+                }
+                Err(err) => {
+                    invalid += 1;
+                    println!(
+                        "  {}{} - invalid: {:#}",
+                        plugin.path.display(),
+                        shadowed,
+                        err
+                    );
+                }
+            }
+        }
+    }
 
-    // Read params file content
-    let params = std::fs::read_to_string(&args.params)
-        .with_context(|| format!("Failed to read params file: {}", args.params.display()))?;
+    println!();
+    println!("Plugins: {} valid, {} invalid", valid, invalid);
+}
+
+/// Processes a single image, or several `--input`/`--output` pairs and whole directories or
+/// globs in batch mode, with one plugin, a list of `--step`s or a pipeline file.
+fn run(args: Args) -> Result<()> {
+    if args.input.len() != args.output.len() {
+        anyhow::bail!(
+            "Got {} --input but {} --output; each --input needs an --output at the same position",
+            args.input.len(),
+            args.output.len()
+        );
+    }
+    let uses_stdio = args
+        .input
+        .iter()
+        .chain(&args.output)
+        .any(|path| image_io::is_stdio(path));
+    if uses_stdio && args.input.len() > 1 {
+        anyhow::bail!("`-` (stdin/stdout) needs a single --input/--output pair");
+    }
+    if args.output.iter().any(|path| image_io::is_stdio(path)) && args.output_format.is_none() {
+        anyhow::bail!("--output-format is required when writing to stdout (--output -)");
+    }
 
-    // Build plugin library path
-    let library_name = plugin_loader::library_filename(&args.plugin);
-    let plugin_library_path = args.plugin_path.join(&library_name);
+    // Read every params or pipeline file before touching any image
+    let steps = match (&args.plugin, &args.pipeline) {
+        (Some(plugin), _) => vec![pipeline::Step::from_plugin_args(
+            plugin,
+            args.params.as_deref(),
+            args.params_json.as_deref(),
+            &args.param_overrides,
+        )?],
+        (_, Some(pipeline_file)) => pipeline::load_file(pipeline_file)?,
+        _ => args
+            .steps
+            .iter()
+            .map(pipeline::Step::from_arg)
+            .collect::<Result<Vec<_>>>()?,
+    };
 
-    // Call plugin to process image
-    plugin_loader::process(&plugin_library_path, width, height, &mut rgba_data, &params)?;
+    // Load plugins and validate params once for all images
+    let isolate = args.isolate.then_some(sandbox::WorkerLimits {
+        memory_limit_mb: args.memory_limit,
+    });
+    let encode_options = image_io::EncodeOptions {
+        format: args.output_format,
+        png_compression: args.png_compression,
+        png_filter: args.png_filter,
+        jpeg_quality: args.jpeg_quality,
+    };
+    let tiling = args.tile_size.map(|size| tiles::TileOptions {
+        size,
+        overlap: args.tile_overlap,
+    });
 
-    // Reconstruct image from raw bytes
-    let output_img = RgbaImage::from_raw(width, height, rgba_data)
-        .expect("Buffer size mismatch - plugin must not change buffer size");
+    let search_path = SearchPath::resolve(&args.plugin_path)?;
+    let timeout = args.timeout.map(Duration::from_secs);
+    let pipeline = pipeline::Pipeline::prepare(steps, &search_path, isolate, timeout)?;
 
-    // Save output image
-    output_img
-        .save(&args.output)
-        .with_context(|| format!("Failed to save image: {}", args.output.display()))?;
+    // From here on Ctrl-C cancels the running plugin calls and fails the remaining images
+    cancel::handle_interrupts()?;
 
-    info!("Saved image to: {}", args.output.display());
+    if let ([input], [output]) = (args.input.as_slice(), args.output.as_slice())
+        && !batch::is_batch_input(input)
+    {
+        return process_file(
+            input,
+            output,
+            &encode_options,
+            &pipeline,
+            args.region,
+            tiling,
+        );
+    }
+
+    // Outputs older than a params or pipeline file, or than a plugin library, are stale as well
+    let dependencies: Vec<PathBuf> = args
+        .params
+        .iter()
+        .chain(&args.pipeline)
+        .cloned()
+        .chain(args.steps.iter().map(|step| step.params.clone()))
+        .chain(pipeline.libraries().iter().cloned())
+        .collect();
+    // Inline params leave no file to compare outputs against, so they always reprocess
+    let force = args.force || args.params_json.is_some() || !args.param_overrides.is_empty();
+    let mut plan = batch::Plan::default();
+    for (input, output) in args.input.iter().zip(&args.output) {
+        if batch::is_batch_input(input) {
+            plan.add_batch(input, output, args.output_format, force, &dependencies)?;
+        } else {
+            plan.add(input, output);
+        }
+    }
+    if args.jobs.get() > 1 {
+        progress::use_lines();
+    }
+    let summary = plan.execute(args.jobs.get(), |input, output| {
+        process_file(
+            input,
+            output,
+            &encode_options,
+            &pipeline,
+            args.region,
+            tiling,
+        )
+    });
+
+    summary.print();
+    if !summary.failed.is_empty() {
+        anyhow::bail!(
+            "{} of {} images failed",
+            summary.failed.len(),
+            summary.total()
+        );
+    }
 
     Ok(())
 }
 
+/// Loads one image, runs the pipeline over it (or over `region` of it, or tile by tile) and
+/// saves the result.
+fn process_file(
+    input: &Path,
+    output: &Path,
+    encode_options: &image_io::EncodeOptions,
+    pipeline: &pipeline::Pipeline,
+    region: Option<pixels::Region>,
+    tiling: Option<tiles::TileOptions>,
+) -> Result<()> {
+    if let Some(tiling) = tiling {
+        return tiles::process_file(input, output, encode_options, pipeline, tiling);
+    }
+    // Fail before any processing if the output cannot be written as asked
+    image_io::output_format(output, encode_options)?;
+
+    // Load the image (format detected from its content), keeping its bit depth
+    info!("Loading image from: {}", input.display());
+    let image = image_io::load(input)?;
+    let source = image.color();
+    let mut pixels = pixels::Pixels::from_image(image);
+    debug!(
+        "Loaded image: {}x{} {} ({} bytes)",
+        pixels.width,
+        pixels.height,
+        pixels.format,
+        pixels.data.len()
+    );
+
+    // Run the plugins; they may change the image size and fall back to a shallower format
+    pipeline.run(&mut pixels, region)?;
+    cancel::check_interrupted()?;
+
+    // Save output image, in the layout of the input where nothing is lost by it
+    let image = pixels::restore_layout(pixels.into_image()?, source);
+    image_io::save(image, output, encode_options)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -96,15 +475,15 @@ mod tests {
         ])
         .expect("should parse all arguments");
 
-        assert_eq!(args.input, PathBuf::from("test_images/sample.png"));
-        assert_eq!(args.output, PathBuf::from("output.png"));
-        assert_eq!(args.plugin, "mirror_plugin");
-        assert_eq!(args.params, PathBuf::from("params.json"));
-        assert_eq!(args.plugin_path, PathBuf::from("/custom/path"));
+        assert_eq!(args.input, vec![PathBuf::from("test_images/sample.png")]);
+        assert_eq!(args.output, vec![PathBuf::from("output.png")]);
+        assert_eq!(args.plugin.as_deref(), Some("mirror_plugin"));
+        assert_eq!(args.params, Some(PathBuf::from("params.json")));
+        assert_eq!(args.plugin_path, vec![PathBuf::from("/custom/path")]);
     }
 
     #[test]
-    fn test_args_plugin_path_default_value() {
+    fn test_args_plugin_path_defaults_to_search_path() {
         let args = Args::try_parse_from([
             "image_processor",
             "--input",
@@ -116,9 +495,9 @@ mod tests {
             "--params",
             "config.json",
         ])
-        .expect("should parse with default plugin-path");
+        .expect("should parse without plugin-path");
 
-        assert_eq!(args.plugin_path, PathBuf::from("target/debug"));
+        assert!(args.plugin_path.is_empty());
     }
 
     #[test]
@@ -167,8 +546,92 @@ mod tests {
     }
 
     #[test]
-    fn test_args_missing_params_fails() {
+    fn test_args_stdio_with_output_format() {
+        let args = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "-",
+            "--output",
+            "-",
+            "--output-format",
+            "jpg",
+            "--plugin",
+            "blur_plugin",
+        ])
+        .expect("should parse stdin/stdout with an output format");
+
+        assert_eq!(args.input, vec![PathBuf::from("-")]);
+        assert_eq!(args.output, vec![PathBuf::from("-")]);
+        assert_eq!(args.output_format, Some(ImageFormat::Jpeg));
+    }
+
+    #[test]
+    fn test_args_encoder_options() {
+        let args = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--plugin",
+            "blur_plugin",
+            "--png-compression",
+            "best",
+            "--png-filter",
+            "paeth",
+            "--jpeg-quality",
+            "90",
+        ])
+        .expect("should parse encoder options");
+
+        assert_eq!(args.png_compression, Some(CompressionType::Best));
+        assert_eq!(args.png_filter, Some(FilterType::Paeth));
+        assert_eq!(args.jpeg_quality, Some(90));
+    }
+
+    #[test]
+    fn test_args_jpeg_quality_out_of_range_fails() {
+        for quality in ["0", "101"] {
+            let result = Args::try_parse_from([
+                "image_processor",
+                "--input",
+                "in.png",
+                "--output",
+                "out.jpg",
+                "--plugin",
+                "blur_plugin",
+                "--jpeg-quality",
+                quality,
+            ]);
+
+            assert!(
+                result.is_err(),
+                "--jpeg-quality {} should be rejected",
+                quality
+            );
+        }
+    }
+
+    #[test]
+    fn test_args_unknown_output_format_fails() {
         let result = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--output-format",
+            "docx",
+            "--plugin",
+            "blur_plugin",
+        ]);
+
+        assert!(result.is_err(), "unknown formats should be rejected");
+    }
+
+    #[test]
+    fn test_args_params_file_is_optional() {
+        let args = Args::try_parse_from([
             "image_processor",
             "--input",
             "input.png",
@@ -176,9 +639,83 @@ mod tests {
             "output.png",
             "--plugin",
             "mirror_plugin",
+        ])
+        .expect("should parse without --params");
+
+        assert_eq!(args.params, None);
+        assert_eq!(args.params_json, None);
+        assert!(args.param_overrides.is_empty());
+    }
+
+    #[test]
+    fn test_args_inline_params() {
+        let args = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "input.png",
+            "--output",
+            "output.png",
+            "--plugin",
+            "blur_plugin",
+            "--params",
+            "blur.json",
+            "--params-json",
+            r#"{"iterations": 2}"#,
+            "--param",
+            "radius=3",
+            "--param",
+            "label=a=b",
+        ])
+        .expect("should parse inline params");
+
+        assert_eq!(args.params_json.as_deref(), Some(r#"{"iterations": 2}"#));
+        assert_eq!(
+            args.param_overrides,
+            vec![
+                pipeline::ParamArg {
+                    key: "radius".to_string(),
+                    value: "3".to_string(),
+                },
+                pipeline::ParamArg {
+                    key: "label".to_string(),
+                    value: "a=b".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_args_param_requires_plugin() {
+        let result = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "input.png",
+            "--output",
+            "output.png",
+            "--step",
+            "blur_plugin=blur.json",
+            "--param",
+            "radius=3",
+        ]);
+
+        assert!(result.is_err(), "--param should not combine with --step");
+    }
+
+    #[test]
+    fn test_args_malformed_param_fails() {
+        let result = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "input.png",
+            "--output",
+            "output.png",
+            "--plugin",
+            "blur_plugin",
+            "--param",
+            "radius",
         ]);
 
-        assert!(result.is_err(), "should fail when --params is missing");
+        assert!(result.is_err(), "--param without '=' should be rejected");
     }
 
     #[test]
@@ -196,9 +733,437 @@ mod tests {
         ])
         .expect("should parse paths with various structures");
 
-        assert_eq!(args.input, PathBuf::from("nested/dir/image.png"));
-        assert_eq!(args.output, PathBuf::from("../relative/output.png"));
-        assert_eq!(args.params, PathBuf::from("./config/params.json"));
+        assert_eq!(args.input, vec![PathBuf::from("nested/dir/image.png")]);
+        assert_eq!(args.output, vec![PathBuf::from("../relative/output.png")]);
+        assert_eq!(args.params, Some(PathBuf::from("./config/params.json")));
+    }
+
+    #[test]
+    fn test_cli_info_subcommand() {
+        let cli = Cli::try_parse_from(["image_processor", "info", "--plugin", "blur_plugin"])
+            .expect("should parse info subcommand");
+
+        assert!(cli.process.is_none());
+        match cli.command {
+            Some(Command::Info {
+                plugin,
+                plugin_path,
+            }) => {
+                assert_eq!(plugin, "blur_plugin");
+                assert!(plugin_path.is_empty());
+            }
+            _ => panic!("expected info subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_cli_without_subcommand_parses_process_args() {
+        let cli = Cli::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--plugin",
+            "mirror_plugin",
+            "--params",
+            "p.json",
+        ])
+        .expect("should parse processing arguments");
+
+        assert!(cli.command.is_none());
+        let args = cli.process.expect("processing arguments should be present");
+        assert_eq!(args.plugin.as_deref(), Some("mirror_plugin"));
+    }
+
+    #[test]
+    fn test_args_isolate_with_limits() {
+        let args = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--plugin",
+            "blur_plugin",
+            "--params",
+            "p.json",
+            "--isolate",
+            "--timeout",
+            "30",
+            "--memory-limit",
+            "512",
+        ])
+        .expect("should parse isolate options");
+
+        assert!(args.isolate);
+        assert_eq!(args.timeout, Some(30));
+        assert_eq!(args.memory_limit, Some(512));
+    }
+
+    #[test]
+    fn test_args_region() {
+        let args = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--plugin",
+            "blur_plugin",
+            "--region",
+            "10,20,30,40",
+        ])
+        .expect("should parse region");
+        assert_eq!(
+            args.region,
+            Some(pixels::Region {
+                x: 10,
+                y: 20,
+                width: 30,
+                height: 40
+            })
+        );
+
+        let result = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--plugin",
+            "blur_plugin",
+            "--region",
+            "10,20",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_args_tiles() {
+        let args = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--plugin",
+            "blur_plugin",
+            "--tile-size",
+            "512",
+            "--tile-overlap",
+            "8",
+        ])
+        .expect("should parse tile options");
+        assert_eq!(args.tile_size, Some(512));
+        assert_eq!(args.tile_overlap, Some(8));
+
+        for invalid in [
+            &["--tile-size", "0"][..],
+            &["--tile-overlap", "8"],
+            &["--tile-size", "512", "--region", "0,0,1,1"],
+        ] {
+            let result = Args::try_parse_from(
+                [
+                    "image_processor",
+                    "--input",
+                    "in.png",
+                    "--output",
+                    "out.png",
+                    "--plugin",
+                    "blur_plugin",
+                ]
+                .iter()
+                .chain(invalid),
+            );
+            assert!(result.is_err(), "{:?} should be rejected", invalid);
+        }
+    }
+
+    #[test]
+    fn test_args_isolate_defaults_off() {
+        let args = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--plugin",
+            "blur_plugin",
+            "--params",
+            "p.json",
+        ])
+        .expect("should parse without isolate options");
+
+        assert!(!args.isolate);
+        assert_eq!(args.timeout, None);
+        assert_eq!(args.memory_limit, None);
+    }
+
+    #[test]
+    fn test_args_limits_require_isolate() {
+        let result = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--plugin",
+            "blur_plugin",
+            "--params",
+            "p.json",
+            "--memory-limit",
+            "512",
+        ]);
+        assert!(result.is_err(), "--memory-limit should require --isolate");
+
+        // Plugins running in-process are cancelled on timeout as well
+        let args = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--plugin",
+            "blur_plugin",
+            "--params",
+            "p.json",
+            "--timeout",
+            "30",
+        ])
+        .expect("--timeout should not require --isolate");
+        assert_eq!(args.timeout, Some(30));
+    }
+
+    #[test]
+    fn test_args_steps_replace_plugin_and_params() {
+        let args = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--step",
+            "mirror_plugin=mirror.json",
+            "--step",
+            "blur_plugin=blur.json",
+        ])
+        .expect("should parse a pipeline without --plugin/--params");
+
+        assert_eq!(args.plugin, None);
+        assert_eq!(args.params, None);
+        assert_eq!(
+            args.steps,
+            vec![
+                pipeline::StepArg {
+                    plugin: "mirror_plugin".to_string(),
+                    params: PathBuf::from("mirror.json"),
+                },
+                pipeline::StepArg {
+                    plugin: "blur_plugin".to_string(),
+                    params: PathBuf::from("blur.json"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_args_steps_conflict_with_plugin() {
+        let result = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--plugin",
+            "mirror_plugin",
+            "--params",
+            "p.json",
+            "--step",
+            "blur_plugin=blur.json",
+        ]);
+
+        assert!(result.is_err(), "--step should conflict with --plugin");
+    }
+
+    #[test]
+    fn test_args_pipeline_file() {
+        let args = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--pipeline",
+            "pipeline.json",
+        ])
+        .expect("should parse a pipeline file without --plugin/--params");
+
+        assert_eq!(args.pipeline, Some(PathBuf::from("pipeline.json")));
+        assert_eq!(args.plugin, None);
+        assert!(args.steps.is_empty());
+    }
+
+    #[test]
+    fn test_args_pipeline_conflicts_with_step() {
+        let result = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--pipeline",
+            "pipeline.json",
+            "--step",
+            "blur_plugin=blur.json",
+        ]);
+
+        assert!(result.is_err(), "--pipeline should conflict with --step");
+    }
+
+    #[test]
+    fn test_args_malformed_step_fails() {
+        let result = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--step",
+            "blur_plugin",
+        ]);
+
+        assert!(result.is_err(), "--step without '=' should be rejected");
+    }
+
+    #[test]
+    fn test_args_force_defaults_off() {
+        let args = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "photos",
+            "--output",
+            "out",
+            "--plugin",
+            "blur_plugin",
+            "--params",
+            "p.json",
+        ])
+        .expect("should parse batch arguments");
+
+        assert!(!args.force);
+    }
+
+    #[test]
+    fn test_args_multiple_pairs_with_jobs() {
+        let args = Args::try_parse_from([
+            "image_processor",
+            "--input",
+            "a.png",
+            "--output",
+            "a_out.png",
+            "--input",
+            "b.png",
+            "--output",
+            "b_out.png",
+            "--plugin",
+            "blur_plugin",
+            "--params",
+            "p.json",
+            "--jobs",
+            "4",
+        ])
+        .expect("should parse repeated input/output pairs");
+
+        assert_eq!(
+            args.input,
+            vec![PathBuf::from("a.png"), PathBuf::from("b.png")]
+        );
+        assert_eq!(
+            args.output,
+            vec![PathBuf::from("a_out.png"), PathBuf::from("b_out.png")]
+        );
+        assert_eq!(args.jobs.get(), 4);
+    }
+
+    #[test]
+    fn test_args_jobs_defaults_to_one_and_rejects_zero() {
+        let base = [
+            "image_processor",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--plugin",
+            "blur_plugin",
+            "--params",
+            "p.json",
+        ];
+        let args = Args::try_parse_from(base).expect("should parse without --jobs");
+        assert_eq!(args.jobs.get(), 1);
+
+        let result = Args::try_parse_from(base.iter().copied().chain(["--jobs", "0"]));
+        assert!(result.is_err(), "--jobs 0 should be rejected");
+    }
+
+    #[test]
+    fn test_cli_list_plugins_repeated_paths() {
+        let cli = Cli::try_parse_from([
+            "image_processor",
+            "list-plugins",
+            "--plugin-path",
+            "plugins",
+            "--plugin-path",
+            "/opt/plugins",
+        ])
+        .expect("should parse list-plugins subcommand");
+
+        match cli.command {
+            Some(Command::ListPlugins { plugin_path }) => assert_eq!(
+                plugin_path,
+                vec![PathBuf::from("plugins"), PathBuf::from("/opt/plugins")]
+            ),
+            _ => panic!("expected list-plugins subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_cli_list_plugins_default_path() {
+        let cli = Cli::try_parse_from(["image_processor", "list-plugins"])
+            .expect("should parse list-plugins without arguments");
+
+        match cli.command {
+            Some(Command::ListPlugins { plugin_path }) => assert!(plugin_path.is_empty()),
+            _ => panic!("expected list-plugins subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_cli_verbose_counts_and_is_global() {
+        let cli = Cli::try_parse_from([
+            "image_processor",
+            "-vv",
+            "--input",
+            "in.png",
+            "--output",
+            "out.png",
+            "--plugin",
+            "blur_plugin",
+            "--params",
+            "p.json",
+        ])
+        .expect("should parse -vv before processing arguments");
+        assert_eq!(cli.verbose, 2);
+
+        let cli = Cli::try_parse_from(["image_processor", "info", "--plugin", "blur_plugin", "-v"])
+            .expect("should parse -v after a subcommand");
+        assert_eq!(cli.verbose, 1);
+    }
+
+    #[test]
+    fn test_cli_info_requires_plugin() {
+        let result = Cli::try_parse_from(["image_processor", "info"]);
+        assert!(result.is_err(), "info should fail when --plugin is missing");
     }
 
     #[test]
@@ -216,6 +1181,6 @@ mod tests {
         ])
         .expect("should accept plugin name with hyphens, underscores, and numbers");
 
-        assert_eq!(args.plugin, "my-custom_plugin123");
+        assert_eq!(args.plugin.as_deref(), Some("my-custom_plugin123"));
     }
 }
@@ -0,0 +1,529 @@
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+
+/// JSON type of a single plugin parameter, as declared in the plugin's schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamType {
+    Boolean,
+    Integer,
+    Number,
+    String,
+    /// Any type the host does not check (arrays, objects, missing `type`).
+    Any,
+}
+
+impl ParamType {
+    fn from_schema(value: Option<&Value>) -> ParamType {
+        match value.and_then(Value::as_str) {
+            Some("boolean") => ParamType::Boolean,
+            Some("integer") => ParamType::Integer,
+            Some("number") => ParamType::Number,
+            Some("string") => ParamType::String,
+            _ => ParamType::Any,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ParamType::Boolean => "boolean",
+            ParamType::Integer => "integer",
+            ParamType::Number => "number",
+            ParamType::String => "string",
+            ParamType::Any => "any",
+        }
+    }
+
+    /// Converts a command-line string to a JSON value of this type.
+    fn coerce(self, name: &str, raw: &str) -> Result<Value> {
+        let value = match self {
+            ParamType::Boolean => raw.parse::<bool>().ok().map(Value::Bool),
+            ParamType::Integer => raw
+                .parse::<i64>()
+                .map(Value::from)
+                .or_else(|_| raw.parse::<u64>().map(Value::from))
+                .ok(),
+            ParamType::Number => raw.parse::<i64>().map(Value::from).ok().or_else(|| {
+                raw.parse::<f64>()
+                    .ok()
+                    .and_then(serde_json::Number::from_f64)
+                    .map(Value::Number)
+            }),
+            ParamType::String => Some(Value::String(raw.to_string())),
+            ParamType::Any => Some(infer_value(raw)),
+        };
+        value.with_context(|| {
+            format!(
+                "parameter '{}' must be of type {}, got '{}'",
+                name,
+                self.name(),
+                raw
+            )
+        })
+    }
+
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            ParamType::Boolean => value.is_boolean(),
+            ParamType::Integer => value.is_i64() || value.is_u64(),
+            ParamType::Number => value.is_number(),
+            ParamType::String => value.is_string(),
+            ParamType::Any => true,
+        }
+    }
+}
+
+/// Declaration of a single parameter taken from a plugin's JSON Schema.
+#[derive(Debug, Clone)]
+pub struct ParamSpec {
+    pub name: String,
+    pub param_type: ParamType,
+    pub description: String,
+    pub default: Option<Value>,
+    pub minimum: Option<f64>,
+    pub maximum: Option<f64>,
+    pub allowed: Option<Vec<Value>>,
+}
+
+/// The subset of JSON Schema the host understands: a flat object of typed properties.
+#[derive(Debug, Clone)]
+pub struct ParamsSchema {
+    pub properties: Vec<ParamSpec>,
+    pub required: Vec<String>,
+    pub additional_properties: bool,
+}
+
+impl ParamsSchema {
+    /// Parses the JSON Schema exported by a plugin.
+    pub fn parse(schema_json: &str) -> Result<ParamsSchema> {
+        let schema: Value = serde_json::from_str(schema_json)
+            .context("Plugin parameter schema is not valid JSON")?;
+
+        let empty = Map::new();
+        let properties = match schema.get("properties") {
+            Some(Value::Object(properties)) => properties,
+            Some(_) => anyhow::bail!("Plugin parameter schema: \"properties\" must be an object"),
+            None => &empty,
+        };
+
+        let properties = properties
+            .iter()
+            .map(|(name, spec)| ParamSpec {
+                name: name.clone(),
+                param_type: ParamType::from_schema(spec.get("type")),
+                description: spec
+                    .get("description")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+                default: spec.get("default").cloned(),
+                minimum: spec.get("minimum").and_then(Value::as_f64),
+                maximum: spec.get("maximum").and_then(Value::as_f64),
+                allowed: spec.get("enum").and_then(Value::as_array).cloned(),
+            })
+            .collect();
+
+        let required = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let additional_properties = schema
+            .get("additionalProperties")
+            .and_then(Value::as_bool)
+            .unwrap_or(true);
+
+        Ok(ParamsSchema {
+            properties,
+            required,
+            additional_properties,
+        })
+    }
+
+    /// Looks up a declared parameter by name.
+    pub fn property(&self, name: &str) -> Option<&ParamSpec> {
+        self.properties.iter().find(|spec| spec.name == name)
+    }
+
+    /// Converts a `--param` value to the type declared for `name`; undeclared parameters
+    /// fall back to [`infer_value`] and are left for [`ParamsSchema::validate`] to reject.
+    pub fn coerce(&self, name: &str, raw: &str) -> Result<Value> {
+        match self.property(name) {
+            Some(spec) => spec.param_type.coerce(name, raw),
+            None => Ok(infer_value(raw)),
+        }
+    }
+
+    /// Validates a JSON parameters string against the schema.
+    ///
+    /// All problems are collected so the user can fix them in one go.
+    pub fn validate(&self, params_json: &str) -> Result<()> {
+        let params: Value =
+            serde_json::from_str(params_json).context("Parameters are not valid JSON")?;
+        let Value::Object(params) = params else {
+            anyhow::bail!(
+                "Parameters must be a JSON object, got: {}",
+                params_json.trim()
+            );
+        };
+
+        let mut problems = Vec::new();
+
+        for (key, value) in &params {
+            match self.property(key) {
+                Some(spec) => problems.extend(spec.check(value)),
+                None if !self.additional_properties => problems.push(self.unknown_key(key)),
+                None => {}
+            }
+        }
+
+        for name in &self.required {
+            if !params.contains_key(name) {
+                problems.push(format!("missing required parameter '{}'", name));
+            }
+        }
+
+        if !problems.is_empty() {
+            anyhow::bail!("{}", problems.join("; "));
+        }
+
+        Ok(())
+    }
+
+    fn unknown_key(&self, key: &str) -> String {
+        let accepted: Vec<&str> = self.properties.iter().map(|s| s.name.as_str()).collect();
+        let suggestion = accepted
+            .iter()
+            .map(|name| (edit_distance(key, name), name))
+            .filter(|(distance, _)| *distance <= 2)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, name)| format!(" (did you mean '{}'?)", name))
+            .unwrap_or_default();
+
+        format!(
+            "unknown parameter '{}'{}; accepted parameters: {}",
+            key,
+            suggestion,
+            accepted.join(", ")
+        )
+    }
+
+    /// Renders the declared parameters on one line, e.g. `radius: integer = 1, mode: string`.
+    pub fn summary(&self) -> String {
+        if self.properties.is_empty() {
+            return "(none)".to_string();
+        }
+        let params: Vec<String> = self
+            .properties
+            .iter()
+            .map(|spec| match &spec.default {
+                Some(default) => format!("{}: {} = {}", spec.name, spec.param_type.name(), default),
+                None => format!("{}: {}", spec.name, spec.param_type.name()),
+            })
+            .collect();
+        params.join(", ")
+    }
+
+    /// Renders the declared parameters as a plain-text table.
+    pub fn help_table(&self) -> String {
+        let header = ["NAME", "TYPE", "DEFAULT", "RANGE", "DESCRIPTION"];
+        let rows: Vec<[String; 5]> = self
+            .properties
+            .iter()
+            .map(|spec| {
+                [
+                    spec.name.clone(),
+                    spec.param_type.name().to_string(),
+                    spec.default
+                        .as_ref()
+                        .map(Value::to_string)
+                        .unwrap_or_else(|| "-".to_string()),
+                    spec.range_text(),
+                    spec.description.clone(),
+                ]
+            })
+            .collect();
+
+        let mut widths = header.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let format_row = |cells: [&str; 5]| {
+            let line: Vec<String> = cells
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            line.join("  ").trim_end().to_string()
+        };
+
+        let mut table = vec![format_row(header)];
+        for row in &rows {
+            table.push(format_row(
+                [&row[0], &row[1], &row[2], &row[3], &row[4]].map(String::as_str),
+            ));
+        }
+        table.join("\n")
+    }
+}
+
+impl ParamSpec {
+    /// Returns a description of every way `value` violates this declaration.
+    fn check(&self, value: &Value) -> Vec<String> {
+        if !self.param_type.matches(value) {
+            return vec![format!(
+                "parameter '{}' must be of type {}, got {}",
+                self.name,
+                self.param_type.name(),
+                value
+            )];
+        }
+
+        let mut problems = Vec::new();
+        if let Some(number) = value.as_f64() {
+            if let Some(minimum) = self.minimum
+                && number < minimum
+            {
+                problems.push(format!(
+                    "parameter '{}' must be >= {}, got {}",
+                    self.name, minimum, value
+                ));
+            }
+            if let Some(maximum) = self.maximum
+                && number > maximum
+            {
+                problems.push(format!(
+                    "parameter '{}' must be <= {}, got {}",
+                    self.name, maximum, value
+                ));
+            }
+        }
+        if let Some(allowed) = &self.allowed
+            && !allowed.contains(value)
+        {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            problems.push(format!(
+                "parameter '{}' must be one of {}, got {}",
+                self.name,
+                allowed.join(", "),
+                value
+            ));
+        }
+        problems
+    }
+
+    fn range_text(&self) -> String {
+        if let Some(allowed) = &self.allowed {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            return allowed.join("|");
+        }
+        match (self.minimum, self.maximum) {
+            (Some(min), Some(max)) => format!("{}..={}", min, max),
+            (Some(min), None) => format!(">={}", min),
+            (None, Some(max)) => format!("<={}", max),
+            (None, None) => "-".to_string(),
+        }
+    }
+}
+
+/// Reads a `--param` value without a declared type: JSON literals (`3`, `true`, `[1, 2]`)
+/// keep their type, anything else becomes a string.
+pub fn infer_value(raw: &str) -> Value {
+    serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+/// Levenshtein distance, used to suggest the intended name for a misspelled parameter.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b_chars.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b_chars.len() + 1];
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b_chars.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLUR_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "radius": {"type": "integer", "minimum": 0, "maximum": 100, "default": 1, "description": "Blur radius"},
+            "iterations": {"type": "integer", "minimum": 0, "default": 1}
+        },
+        "additionalProperties": false
+    }"#;
+
+    fn blur_schema() -> ParamsSchema {
+        ParamsSchema::parse(BLUR_SCHEMA).expect("valid schema")
+    }
+
+    #[test]
+    fn test_parse_schema_properties() {
+        let schema = blur_schema();
+
+        assert_eq!(schema.properties.len(), 2);
+        let radius = schema.property("radius").expect("radius is declared");
+        assert_eq!(radius.param_type, ParamType::Integer);
+        assert_eq!(radius.maximum, Some(100.0));
+        assert_eq!(radius.default, Some(Value::from(1)));
+        assert!(!schema.additional_properties);
+    }
+
+    #[test]
+    fn test_parse_invalid_schema_fails() {
+        assert!(ParamsSchema::parse("not json").is_err());
+        assert!(ParamsSchema::parse(r#"{"properties": 5}"#).is_err());
+    }
+
+    #[test]
+    fn test_validate_accepts_valid_params() {
+        let schema = blur_schema();
+        assert!(schema.validate(r#"{"radius": 5, "iterations": 2}"#).is_ok());
+        assert!(schema.validate("{}").is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_key_with_suggestion() {
+        let err = blur_schema()
+            .validate(r#"{"radus": 5}"#)
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("unknown parameter 'radus'"), "got: {}", err);
+        assert!(err.contains("did you mean 'radius'?"), "got: {}", err);
+        assert!(err.contains("accepted parameters: radius, iterations"));
+    }
+
+    #[test]
+    fn test_validate_rejects_wrong_type() {
+        let err = blur_schema()
+            .validate(r#"{"radius": "big"}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("'radius' must be of type integer"),
+            "got: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_out_of_range() {
+        let err = blur_schema()
+            .validate(r#"{"radius": 500, "iterations": -1}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("'radius' must be <= 100"), "got: {}", err);
+        assert!(err.contains("'iterations' must be >= 0"), "got: {}", err);
+    }
+
+    #[test]
+    fn test_validate_rejects_non_object() {
+        assert!(blur_schema().validate("[1, 2]").is_err());
+        assert!(blur_schema().validate("not json").is_err());
+    }
+
+    #[test]
+    fn test_validate_missing_required() {
+        let schema = ParamsSchema::parse(
+            r#"{"properties": {"angle": {"type": "integer", "enum": [90, 180, 270]}}, "required": ["angle"]}"#,
+        )
+        .expect("valid schema");
+
+        assert!(schema.validate(r#"{"angle": 90}"#).is_ok());
+        let err = schema.validate("{}").unwrap_err().to_string();
+        assert!(err.contains("missing required parameter 'angle'"));
+        let err = schema.validate(r#"{"angle": 45}"#).unwrap_err().to_string();
+        assert!(err.contains("must be one of 90, 180, 270"), "got: {}", err);
+    }
+
+    #[test]
+    fn test_help_table_lists_parameters() {
+        let table = blur_schema().help_table();
+
+        assert!(table.starts_with("NAME"));
+        assert!(table.contains("radius"));
+        assert!(table.contains("0..=100"));
+        assert!(table.contains("Blur radius"));
+    }
+
+    #[test]
+    fn test_summary_lists_names_types_and_defaults() {
+        assert_eq!(
+            blur_schema().summary(),
+            "radius: integer = 1, iterations: integer = 1"
+        );
+
+        let empty = ParamsSchema::parse(r#"{"type": "object", "properties": {}}"#)
+            .expect("empty schema should parse");
+        assert_eq!(empty.summary(), "(none)");
+    }
+
+    #[test]
+    fn test_coerce_uses_declared_types() {
+        let schema = ParamsSchema::parse(
+            r#"{"properties": {
+                "flag": {"type": "boolean"},
+                "count": {"type": "integer"},
+                "amount": {"type": "number"},
+                "label": {"type": "string"},
+                "extra": {}
+            }}"#,
+        )
+        .expect("schema should parse");
+
+        let coerce = |name, raw| schema.coerce(name, raw).expect("value should coerce");
+        assert_eq!(coerce("flag", "true"), Value::Bool(true));
+        assert_eq!(coerce("count", "-3"), Value::from(-3));
+        assert_eq!(coerce("amount", "2"), Value::from(2));
+        assert_eq!(coerce("amount", "0.5"), Value::from(0.5));
+        assert_eq!(coerce("label", "42"), Value::from("42"));
+        assert_eq!(coerce("extra", "[1, 2]"), serde_json::json!([1, 2]));
+        assert_eq!(coerce("undeclared", "7"), Value::from(7));
+    }
+
+    #[test]
+    fn test_coerce_rejects_values_of_the_wrong_type() {
+        let schema = blur_schema();
+
+        let err = schema.coerce("radius", "big").expect_err("not an integer");
+        assert_eq!(
+            err.to_string(),
+            "parameter 'radius' must be of type integer, got 'big'"
+        );
+        assert!(schema.coerce("radius", "1.5").is_err());
+    }
+
+    #[test]
+    fn test_infer_value_falls_back_to_string() {
+        assert_eq!(infer_value("true"), Value::Bool(true));
+        assert_eq!(infer_value("1.5"), Value::from(1.5));
+        assert_eq!(infer_value("hello"), Value::from("hello"));
+        assert_eq!(infer_value(""), Value::from(""));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("radius", "radius"), 0);
+        assert_eq!(edit_distance("radus", "radius"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -0,0 +1,815 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+use serde_json::{Map, Value};
+
+use crate::cancel::Cancel;
+use crate::params_schema::{self, ParamsSchema};
+use crate::pixels::{Pixels, Region};
+use crate::plugin_loader::{self, Plugin, PluginInfo, PluginRegistry};
+use crate::progress::Progress;
+use crate::sandbox::{self, WorkerLimits};
+use crate::search_path::SearchPath;
+
+/// One `--step plugin=params.json` command-line argument.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepArg {
+    pub plugin: String,
+    pub params: PathBuf,
+}
+
+impl FromStr for StepArg {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let (plugin, params) = value
+            .split_once('=')
+            .ok_or_else(|| format!("expected PLUGIN=PARAMS_FILE, got '{}'", value))?;
+        if plugin.is_empty() {
+            return Err(format!("missing plugin name in '{}'", value));
+        }
+        if params.is_empty() {
+            return Err(format!("missing params file in '{}'", value));
+        }
+        Ok(Self {
+            plugin: plugin.to_string(),
+            params: PathBuf::from(params),
+        })
+    }
+}
+
+/// One `--param key=value` command-line argument.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamArg {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for ParamArg {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let (key, param_value) = value
+            .split_once('=')
+            .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", value))?;
+        if key.is_empty() {
+            return Err(format!("missing parameter name in '{}'", value));
+        }
+        Ok(Self {
+            key: key.to_string(),
+            value: param_value.to_string(),
+        })
+    }
+}
+
+/// Keys accepted at the top level of a pipeline file.
+const PIPELINE_KEYS: &[&str] = &["description", "steps"];
+
+/// Keys accepted in each step of a pipeline file.
+const STEP_KEYS: &[&str] = &["name", "plugin", "params", "enabled"];
+
+/// A pipeline step ready to run: the plugin to call and its JSON parameters.
+#[derive(Debug, Clone)]
+pub struct Step {
+    /// Optional label from a pipeline file, used in logs and errors.
+    pub name: Option<String>,
+    pub plugin: String,
+    pub params: String,
+    /// `--param` values merged over `params` by [`Pipeline::prepare`], once the plugin's
+    /// declared types are known.
+    pub overrides: Vec<ParamArg>,
+}
+
+impl Step {
+    /// Reads the params file of a `--step` argument.
+    pub fn from_arg(arg: &StepArg) -> Result<Self> {
+        let params = std::fs::read_to_string(&arg.params)
+            .with_context(|| format!("Failed to read params file: {}", arg.params.display()))?;
+        Ok(Self {
+            name: None,
+            plugin: arg.plugin.clone(),
+            params,
+            overrides: Vec::new(),
+        })
+    }
+
+    /// Builds the step of a `--plugin` run: the params file (`{}` without one) with
+    /// `--params-json` merged over it, and the `--param` overrides.
+    pub fn from_plugin_args(
+        plugin: &str,
+        params_file: Option<&Path>,
+        params_json: Option<&str>,
+        overrides: &[ParamArg],
+    ) -> Result<Self> {
+        let params = match params_file {
+            Some(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read params file: {}", path.display()))?,
+            None => "{}".to_string(),
+        };
+
+        let params = match params_json {
+            Some(params_json) => {
+                let mut merged = params_object(&params).context("Invalid params file")?;
+                merged.extend(params_object(params_json).context("Invalid --params-json")?);
+                Value::Object(merged).to_string()
+            }
+            None => params,
+        };
+
+        Ok(Self {
+            name: None,
+            plugin: plugin.to_string(),
+            params,
+            overrides: overrides.to_vec(),
+        })
+    }
+
+    /// Human-readable label for logs and errors, e.g. `'soften' (blur_plugin)`.
+    fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("'{}' ({})", name, self.plugin),
+            None => format!("({})", self.plugin),
+        }
+    }
+}
+
+/// Reads a pipeline file and returns its enabled steps.
+pub fn load_file(path: &Path) -> Result<Vec<Step>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read pipeline file: {}", path.display()))?;
+    parse(&contents).with_context(|| format!("Invalid pipeline file: {}", path.display()))
+}
+
+/// Parses a pipeline definition and returns its enabled steps in order.
+///
+/// ```json
+/// {
+///     "description": "optional free text",
+///     "steps": [
+///         {"name": "flip", "plugin": "mirror_plugin", "params": {"horizontal": true}},
+///         {"name": "soften", "plugin": "blur_plugin", "params": {"radius": 2}, "enabled": false}
+///     ]
+/// }
+/// ```
+///
+/// `name` and `enabled` (default `true`) are optional; `params` defaults to `{}`.
+pub fn parse(pipeline_json: &str) -> Result<Vec<Step>> {
+    let pipeline: Value =
+        serde_json::from_str(pipeline_json).context("Pipeline is not valid JSON")?;
+    let pipeline = pipeline
+        .as_object()
+        .ok_or_else(|| anyhow::anyhow!("Pipeline must be a JSON object"))?;
+    check_keys(pipeline, PIPELINE_KEYS, "pipeline")?;
+
+    let steps = match pipeline.get("steps") {
+        Some(Value::Array(steps)) => steps,
+        Some(_) => anyhow::bail!("\"steps\" must be an array"),
+        None => anyhow::bail!("Pipeline has no \"steps\""),
+    };
+
+    let mut names = HashSet::new();
+    let mut enabled_steps = Vec::new();
+    for (index, step) in steps.iter().enumerate() {
+        let (step, enabled) = parse_step(step).with_context(|| format!("Step {}", index + 1))?;
+
+        if let Some(name) = &step.name
+            && !names.insert(name.clone())
+        {
+            anyhow::bail!("Step {}: duplicate step name '{}'", index + 1, name);
+        }
+
+        if enabled {
+            enabled_steps.push(step);
+        } else {
+            debug!(
+                "Skipping disabled pipeline step {} {}",
+                index + 1,
+                step.label()
+            );
+        }
+    }
+
+    if enabled_steps.is_empty() {
+        anyhow::bail!("Pipeline has no enabled steps");
+    }
+    Ok(enabled_steps)
+}
+
+/// Parses one entry of the `steps` array. Returns the step and its `enabled` flag.
+fn parse_step(step: &Value) -> Result<(Step, bool)> {
+    let step = step
+        .as_object()
+        .ok_or_else(|| anyhow::anyhow!("step must be a JSON object"))?;
+    check_keys(step, STEP_KEYS, "step")?;
+
+    let name = match step.get("name") {
+        Some(Value::String(name)) => Some(name.clone()),
+        Some(_) => anyhow::bail!("\"name\" must be a string"),
+        None => None,
+    };
+
+    let plugin = match step.get("plugin") {
+        Some(Value::String(plugin)) if !plugin.is_empty() => plugin.clone(),
+        Some(_) => anyhow::bail!("\"plugin\" must be a non-empty string"),
+        None => anyhow::bail!("missing \"plugin\""),
+    };
+
+    let params = match step.get("params") {
+        Some(params @ Value::Object(_)) => params.to_string(),
+        Some(_) => anyhow::bail!("\"params\" must be a JSON object"),
+        None => "{}".to_string(),
+    };
+
+    let enabled = match step.get("enabled") {
+        Some(Value::Bool(enabled)) => *enabled,
+        Some(_) => anyhow::bail!("\"enabled\" must be a boolean"),
+        None => true,
+    };
+
+    Ok((
+        Step {
+            name,
+            plugin,
+            params,
+            overrides: Vec::new(),
+        },
+        enabled,
+    ))
+}
+
+/// Parses a JSON parameters string that must be an object.
+fn params_object(params_json: &str) -> Result<Map<String, Value>> {
+    match serde_json::from_str(params_json).context("Parameters are not valid JSON")? {
+        Value::Object(params) => Ok(params),
+        _ => anyhow::bail!(
+            "Parameters must be a JSON object, got: {}",
+            params_json.trim()
+        ),
+    }
+}
+
+/// Merges `--param` overrides into `params`, converting each value to the type the plugin
+/// declares. Without a schema, values are read as JSON literals or strings.
+fn apply_overrides(
+    params: &str,
+    overrides: &[ParamArg],
+    schema: Option<&ParamsSchema>,
+) -> Result<String> {
+    if overrides.is_empty() {
+        return Ok(params.to_string());
+    }
+
+    let mut merged = params_object(params)?;
+    for ParamArg { key, value } in overrides {
+        let value = match schema {
+            Some(schema) => schema.coerce(key, value)?,
+            None => params_schema::infer_value(value),
+        };
+        merged.insert(key.clone(), value);
+    }
+    Ok(Value::Object(merged).to_string())
+}
+
+/// Rejects keys outside `allowed`, so typos such as `"enabeld"` are not silently ignored.
+fn check_keys(object: &Map<String, Value>, allowed: &[&str], what: &str) -> Result<()> {
+    if let Some(key) = object.keys().find(|key| !allowed.contains(&key.as_str())) {
+        anyhow::bail!(
+            "unknown {} key '{}' (accepted keys: {})",
+            what,
+            key,
+            allowed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Steps ready to run over any number of images.
+///
+/// Preparing a pipeline finds every plugin library on the search path and validates all params
+/// before the first image is touched. In-process, every distinct plugin is loaded once. With
+/// `isolate` set, only the plugins' metadata is read in this process and each step of each
+/// image runs in its own worker process. A `timeout` cancels any single plugin call that runs
+/// longer.
+pub struct Pipeline {
+    steps: Vec<Step>,
+    /// Library file of each step, found on the search path.
+    libraries: Vec<PathBuf>,
+    isolate: Option<WorkerLimits>,
+    timeout: Option<Duration>,
+    /// Plugins of all steps; empty when isolated.
+    plugins: PluginRegistry,
+}
+
+impl Pipeline {
+    /// Checks, loads and validates `steps` without touching any image.
+    pub fn prepare(
+        steps: Vec<Step>,
+        search_path: &SearchPath,
+        isolate: Option<WorkerLimits>,
+        timeout: Option<Duration>,
+    ) -> Result<Self> {
+        let mut libraries = Vec::with_capacity(steps.len());
+        for index in 0..steps.len() {
+            let library = in_step(search_path.find(&steps[index].plugin), &steps, index)?;
+            libraries.push(library);
+        }
+
+        let mut pipeline = Self {
+            steps,
+            libraries,
+            isolate,
+            timeout,
+            plugins: PluginRegistry::default(),
+        };
+
+        if pipeline.isolate.is_some() {
+            // Reading the metadata calls no processing code; that is left to the workers
+            let mut infos: HashMap<String, Option<PluginInfo>> = HashMap::new();
+            for index in 0..pipeline.steps.len() {
+                let step = &pipeline.steps[index];
+                if !infos.contains_key(&step.plugin) {
+                    let info = pipeline
+                        .in_step(plugin_loader::read_info(&pipeline.libraries[index]), index)?;
+                    infos.insert(step.plugin.clone(), info);
+                }
+                let info = infos[&step.plugin].as_ref();
+                let schema = info.and_then(|info| info.params_schema.as_ref());
+                let params =
+                    apply_overrides(&step.params, &step.overrides, schema).and_then(|params| {
+                        match info {
+                            Some(info) => info.validate_params(&params).map(|()| params),
+                            None => Ok(params),
+                        }
+                    });
+                pipeline.steps[index].params = pipeline.in_step(params, index)?;
+            }
+            return Ok(pipeline);
+        }
+
+        for index in 0..pipeline.steps.len() {
+            let step = &pipeline.steps[index];
+            let plugin = pipeline
+                .plugins
+                .load(&step.plugin, &pipeline.libraries[index]);
+            let plugin = in_step(plugin, &pipeline.steps, index)?;
+            let params = apply_overrides(&step.params, &step.overrides, plugin.params_schema())
+                .and_then(|params| plugin.validate_params(&params).map(|()| params));
+            pipeline.steps[index].params = pipeline.in_step(params, index)?;
+        }
+
+        Ok(pipeline)
+    }
+
+    /// Returns the library file of each step, in step order.
+    pub fn libraries(&self) -> &[PathBuf] {
+        &self.libraries
+    }
+
+    /// Returns how many pixels of context around a tile all steps together need, or `None`
+    /// when isolated, since the plugins are not loaded in this process to ask them.
+    /// Fails if a plugin cannot work on tiles.
+    pub fn halo(&self) -> Result<Option<u32>> {
+        if self.isolate.is_some() {
+            return Ok(None);
+        }
+
+        let mut total = 0u32;
+        for (index, step) in self.steps.iter().enumerate() {
+            let plugin = self.plugin(step);
+            let halo = plugin.halo(&step.params).and_then(|halo| {
+                halo.with_context(|| {
+                    format!(
+                        "Plugin {} cannot process tiles (it does not export plugin_halo)",
+                        step.plugin
+                    )
+                })
+            });
+            let halo = self.in_step(halo, index)?;
+            // Each step reads `halo` pixels around what the previous step produced
+            total = total.saturating_add(halo);
+        }
+        Ok(Some(total))
+    }
+
+    /// Runs the steps in order over the same pixels, or only over the pixels inside `region`.
+    ///
+    /// Each step gets the richest pixel format its plugin accepts; once a step needs a
+    /// conversion to a shallower format, the following steps continue from that format.
+    ///
+    /// A plugin call cancelled by Ctrl-C or the timeout fails its step, even when the plugin
+    /// finished without noticing; the pixels it was given are then not to be used.
+    pub fn run(&self, pixels: &mut Pixels, region: Option<Region>) -> Result<()> {
+        for (index, step) in self.steps.iter().enumerate() {
+            info!("Step {}/{}: {}", index + 1, self.steps.len(), step.label());
+            let library = &self.libraries[index];
+            let progress = Progress::new(match self.steps.len() {
+                1 => step.plugin.clone(),
+                count => format!("Step {}/{}: {}", index + 1, count, step.plugin),
+            });
+            let report = |fraction| progress.report(fraction);
+            let cancel = Cancel::new(self.timeout);
+            if let Some(reason) = cancel.reason() {
+                return self.in_step(
+                    Err(anyhow::anyhow!("Plugin {} {}", step.plugin, reason)),
+                    index,
+                );
+            }
+            let result = match (self.isolate, region) {
+                (Some(limits), None) => {
+                    sandbox::process(library, pixels, &step.params, limits, &cancel, &report)
+                }
+                (Some(limits), Some(region)) => process_region_isolated(
+                    library,
+                    pixels,
+                    region,
+                    &step.params,
+                    limits,
+                    &cancel,
+                    &report,
+                ),
+                (None, region) => {
+                    let plugin = self.plugin(step);
+                    plugin.with_cancel(&|| cancel.is_cancelled(), || {
+                        plugin.with_progress(&report, || match region {
+                            None => plugin.process_pixels(pixels, &step.params),
+                            Some(region) => plugin.process_region(pixels, region, &step.params),
+                        })
+                    })
+                }
+            };
+            let result = match (result, cancel.reason()) {
+                (Err(err), Some(reason)) => {
+                    Err(err.context(format!("Plugin {} {}", step.plugin, reason)))
+                }
+                // A plugin that never polls for cancellation runs to the end; its result is
+                // dropped all the same so a cancelled run writes no output
+                (Ok(()), Some(reason)) => Err(anyhow::anyhow!("Plugin {} {}", step.plugin, reason)),
+                (result, None) => result,
+            };
+            self.in_step(result, index)?;
+        }
+        Ok(())
+    }
+
+    fn plugin(&self, step: &Step) -> &Plugin {
+        self.plugins
+            .get(&step.plugin)
+            .expect("prepare loads the plugin of every step")
+    }
+
+    fn in_step<T>(&self, result: Result<T>, index: usize) -> Result<T> {
+        in_step(result, &self.steps, index)
+    }
+}
+
+/// Sends a copy of the pixels inside `region` to a worker and writes the result back.
+fn process_region_isolated(
+    library: &Path,
+    pixels: &mut Pixels,
+    region: Region,
+    params: &str,
+    limits: WorkerLimits,
+    cancel: &Cancel,
+    report: &(dyn Fn(f32) + Sync),
+) -> Result<()> {
+    let mut part = pixels.crop(region)?;
+    sandbox::process(library, &mut part, params, limits, cancel, report)?;
+
+    if (part.width, part.height) != (region.width, region.height) {
+        anyhow::bail!(
+            "Plugin {} changes the image size and cannot process part of an image",
+            library.display()
+        );
+    }
+    // The worker may have converted the pixels for its plugin; the rest of the image follows
+    pixels.convert(part.format)?;
+    pixels.region_mut(region)?.copy_from_packed(&part.data);
+    Ok(())
+}
+
+/// Adds the failing step to an error. Single-plugin runs keep the plugin's own message.
+fn in_step<T>(result: Result<T>, steps: &[Step], index: usize) -> Result<T> {
+    if steps.len() == 1 {
+        return result;
+    }
+    result.with_context(|| {
+        format!(
+            "Pipeline step {} {} failed",
+            index + 1,
+            steps[index].label()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixels::PixelFormat;
+
+    #[test]
+    fn test_step_arg_parses_plugin_and_params() {
+        let step: StepArg = "blur_plugin=params/blur.json".parse().expect("valid step");
+
+        assert_eq!(step.plugin, "blur_plugin");
+        assert_eq!(step.params, PathBuf::from("params/blur.json"));
+    }
+
+    #[test]
+    fn test_step_arg_splits_on_first_equals() {
+        let step: StepArg = "mirror_plugin=a=b.json".parse().expect("valid step");
+
+        assert_eq!(step.plugin, "mirror_plugin");
+        assert_eq!(step.params, PathBuf::from("a=b.json"));
+    }
+
+    #[test]
+    fn test_step_arg_rejects_malformed_values() {
+        assert!("blur_plugin".parse::<StepArg>().is_err());
+        assert!("=params.json".parse::<StepArg>().is_err());
+        assert!("blur_plugin=".parse::<StepArg>().is_err());
+    }
+
+    #[test]
+    fn test_param_arg_parses_key_and_value() {
+        let param: ParamArg = "label=a=b".parse().expect("valid param");
+        assert_eq!(param.key, "label");
+        assert_eq!(param.value, "a=b");
+
+        let empty: ParamArg = "label=".parse().expect("empty value is allowed");
+        assert_eq!(empty.value, "");
+
+        assert!("radius".parse::<ParamArg>().is_err());
+        assert!("=3".parse::<ParamArg>().is_err());
+    }
+
+    #[test]
+    fn test_from_plugin_args_merges_inline_json_over_file() {
+        let dir = tempfile::TempDir::new().expect("failed to create temp directory");
+        let params_file = dir.path().join("blur.json");
+        std::fs::write(&params_file, r#"{"radius": 1, "iterations": 4}"#)
+            .expect("failed to write params");
+
+        let step = Step::from_plugin_args(
+            "blur_plugin",
+            Some(&params_file),
+            Some(r#"{"radius": 5}"#),
+            &[],
+        )
+        .expect("params should merge");
+
+        let params: Value = serde_json::from_str(&step.params).expect("valid JSON");
+        assert_eq!(params, serde_json::json!({"radius": 5, "iterations": 4}));
+    }
+
+    #[test]
+    fn test_from_plugin_args_without_file_uses_empty_object() {
+        let step =
+            Step::from_plugin_args("mirror_plugin", None, None, &[]).expect("no params is valid");
+        assert_eq!(step.params, "{}");
+
+        let err = Step::from_plugin_args("mirror_plugin", None, Some("[1]"), &[])
+            .expect_err("inline params must be an object");
+        assert!(format!("{:#}", err).contains("Invalid --params-json"));
+    }
+
+    #[test]
+    fn test_apply_overrides_without_schema_infers_types() {
+        let overrides: Vec<ParamArg> = ["horizontal=true", "label=left"]
+            .iter()
+            .map(|arg| arg.parse().expect("valid param"))
+            .collect();
+
+        let params = apply_overrides(r#"{"horizontal": false}"#, &overrides, None)
+            .expect("overrides should apply");
+
+        let params: Value = serde_json::from_str(&params).expect("valid JSON");
+        assert_eq!(
+            params,
+            serde_json::json!({"horizontal": true, "label": "left"})
+        );
+    }
+
+    #[test]
+    fn test_prepare_reports_missing_plugin() {
+        let step = Step {
+            name: None,
+            plugin: "nonexistent_plugin".to_string(),
+            params: "{}".to_string(),
+            overrides: Vec::new(),
+        };
+        let steps = vec![step.clone(), step];
+        let err = Pipeline::prepare(
+            steps,
+            &SearchPath::from_dirs(&[PathBuf::from("/nonexistent")]),
+            None,
+            None,
+        )
+        .err()
+        .expect("missing plugin must fail");
+
+        let message = format!("{:#}", err);
+        assert!(
+            message.contains("Pipeline step 1 (nonexistent_plugin) failed"),
+            "got: {}",
+            message
+        );
+        assert!(message.contains("Plugin library not found"));
+    }
+
+    #[test]
+    fn test_parse_pipeline_with_named_steps() {
+        let steps = parse(
+            r#"{
+                "description": "flip and soften",
+                "steps": [
+                    {"name": "flip", "plugin": "mirror_plugin", "params": {"horizontal": true}},
+                    {"plugin": "blur_plugin"}
+                ]
+            }"#,
+        )
+        .expect("valid pipeline");
+
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].name.as_deref(), Some("flip"));
+        assert_eq!(steps[0].plugin, "mirror_plugin");
+        assert_eq!(steps[0].params, r#"{"horizontal":true}"#);
+        assert_eq!(steps[1].name, None);
+        assert_eq!(steps[1].params, "{}");
+    }
+
+    #[test]
+    fn test_parse_pipeline_skips_disabled_steps() {
+        let steps = parse(
+            r#"{"steps": [
+                {"name": "flip", "plugin": "mirror_plugin", "enabled": false},
+                {"name": "soften", "plugin": "blur_plugin", "enabled": true}
+            ]}"#,
+        )
+        .expect("valid pipeline");
+
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].name.as_deref(), Some("soften"));
+    }
+
+    #[test]
+    fn test_parse_pipeline_rejects_unknown_step_key() {
+        let err = parse(r#"{"steps": [{"plugin": "blur_plugin", "enabeld": false}]}"#).unwrap_err();
+
+        let message = format!("{:#}", err);
+        assert!(
+            message.contains("Step 1: unknown step key 'enabeld'"),
+            "got: {}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_parse_pipeline_rejects_invalid_steps() {
+        let cases = [
+            (r#"[]"#, "must be a JSON object"),
+            (r#"{}"#, "no \"steps\""),
+            (r#"{"steps": [{}]}"#, "missing \"plugin\""),
+            (
+                r#"{"steps": [{"plugin": "x", "params": "radius=2"}]}"#,
+                "\"params\" must be a JSON object",
+            ),
+            (
+                r#"{"steps": [{"plugin": "x", "enabled": "no"}]}"#,
+                "\"enabled\" must be a boolean",
+            ),
+            (
+                r#"{"steps": [{"name": "a", "plugin": "x"}, {"name": "a", "plugin": "y"}]}"#,
+                "duplicate step name 'a'",
+            ),
+            (
+                r#"{"steps": [{"plugin": "x", "enabled": false}]}"#,
+                "no enabled steps",
+            ),
+        ];
+
+        for (pipeline, expected) in cases {
+            let message = format!("{:#}", parse(pipeline).unwrap_err());
+            assert!(
+                message.contains(expected),
+                "{} should fail with '{}', got: {}",
+                pipeline,
+                expected,
+                message
+            );
+        }
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_run_chains_steps_and_reuses_plugins() {
+        let steps = vec![
+            Step {
+                name: None,
+                plugin: "mirror_plugin".to_string(),
+                params: r#"{"horizontal": true}"#.to_string(),
+                overrides: Vec::new(),
+            },
+            Step {
+                name: None,
+                plugin: "rotate_plugin".to_string(),
+                params: r#"{"angle": 90}"#.to_string(),
+                overrides: Vec::new(),
+            },
+            Step {
+                name: None,
+                plugin: "mirror_plugin".to_string(),
+                params: r#"{"horizontal": true}"#.to_string(),
+                overrides: Vec::new(),
+            },
+        ];
+        // 2x1 image: red, green
+        let mut pixels = Pixels::new(
+            2,
+            1,
+            PixelFormat::Rgba8,
+            vec![255, 0, 0, 255, 0, 255, 0, 255],
+        )
+        .expect("valid image");
+
+        let pipeline = Pipeline::prepare(
+            steps,
+            &SearchPath::from_dirs(&[PathBuf::from("../target/debug")]),
+            None,
+            None,
+        )
+        .expect("pipeline should load");
+        pipeline
+            .run(&mut pixels, None)
+            .expect("pipeline should succeed");
+
+        // mirror -> [green, red]; rotate 90 -> 1x2 column green over red; mirror is a no-op
+        // on a single column.
+        assert_eq!((pixels.width, pixels.height), (1, 2));
+        assert_eq!(pixels.data, vec![0, 255, 0, 255, 255, 0, 0, 255]);
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_prepare_coerces_overrides_with_plugin_schema() {
+        let search_path = SearchPath::from_dirs(&[PathBuf::from("../target/debug")]);
+        let overrides = vec!["radius=3".parse().expect("valid param")];
+        let step = Step::from_plugin_args("blur_plugin", None, None, &overrides)
+            .expect("step should build");
+
+        let pipeline =
+            Pipeline::prepare(vec![step], &search_path, None, None).expect("pipeline should load");
+        let params: Value = serde_json::from_str(&pipeline.steps[0].params).expect("valid JSON");
+        assert_eq!(params, serde_json::json!({"radius": 3}));
+
+        let overrides = vec!["radius=wide".parse().expect("valid param")];
+        let step = Step::from_plugin_args("blur_plugin", None, None, &overrides)
+            .expect("step should build");
+        let err = Pipeline::prepare(vec![step], &search_path, None, None)
+            .err()
+            .expect("a non-integer radius must fail");
+        assert!(
+            format!("{:#}", err).contains("parameter 'radius' must be of type integer"),
+            "got: {:#}",
+            err
+        );
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_prepare_validates_all_steps() {
+        let steps = vec![
+            Step {
+                name: None,
+                plugin: "mirror_plugin".to_string(),
+                params: r#"{"horizontal": true}"#.to_string(),
+                overrides: Vec::new(),
+            },
+            Step {
+                name: None,
+                plugin: "blur_plugin".to_string(),
+                params: r#"{"radus": 2}"#.to_string(),
+                overrides: Vec::new(),
+            },
+        ];
+
+        let err = Pipeline::prepare(
+            steps,
+            &SearchPath::from_dirs(&[PathBuf::from("../target/debug")]),
+            None,
+            None,
+        )
+        .err()
+        .expect("invalid params must fail before any image is processed");
+
+        let message = format!("{:#}", err);
+        assert!(
+            message.contains("Pipeline step 2 (blur_plugin) failed"),
+            "got: {}",
+            message
+        );
+    }
+}
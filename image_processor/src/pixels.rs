@@ -0,0 +1,711 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use image::{ColorType, DynamicImage, ImageBuffer, Luma, LumaA, Rgb, Rgba};
+
+/// Pixel layout of a buffer passed to a plugin: gray (`L`), gray with alpha (`LA`), RGB or
+/// RGBA channels, interleaved, with samples in native byte order. `Rgba32F` samples are
+/// nominally in `0.0..=1.0`.
+///
+/// The discriminants are the format codes of the plugin ABI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgba8 = 0,
+    Rgba16 = 1,
+    Rgba32F = 2,
+    L8 = 3,
+    La8 = 4,
+    Rgb8 = 5,
+}
+
+impl PixelFormat {
+    pub const ALL: [PixelFormat; 6] = [
+        Self::Rgba8,
+        Self::Rgba16,
+        Self::Rgba32F,
+        Self::L8,
+        Self::La8,
+        Self::Rgb8,
+    ];
+
+    /// Format code passed to `process_pixels` / `transform_pixels`.
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.code() == code)
+    }
+
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::L8 => 1,
+            Self::La8 => 2,
+            Self::Rgb8 => 3,
+            Self::Rgba8 => 4,
+            Self::Rgba16 => 8,
+            Self::Rgba32F => 16,
+        }
+    }
+
+    /// Returns the length of a `width` x `height` buffer in this format, or `None` on overflow.
+    pub fn buffer_len(self, width: u32, height: u32) -> Option<usize> {
+        (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|n| n.checked_mul(self.bytes_per_pixel()))
+    }
+
+    /// Returns the most compact format that keeps all the channels and precision of `image`.
+    pub fn of_image(image: &DynamicImage) -> Self {
+        match image.color() {
+            ColorType::L8 => Self::L8,
+            ColorType::La8 => Self::La8,
+            ColorType::Rgb8 => Self::Rgb8,
+            ColorType::Rgb32F | ColorType::Rgba32F => Self::Rgba32F,
+            ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => Self::Rgba16,
+            _ => Self::Rgba8,
+        }
+    }
+
+    /// Picks the format to hand a plugin that accepts `supported`: this one if possible,
+    /// otherwise the most compact alternative that loses nothing, then the richest lossy
+    /// one, ending with RGBA8 which every plugin accepts.
+    pub fn negotiate(self, supported: FormatSet) -> Self {
+        let candidates: &[PixelFormat] = match self {
+            Self::L8 => &[Self::L8, Self::La8, Self::Rgb8, Self::Rgba8],
+            Self::La8 => &[Self::La8, Self::Rgba8],
+            Self::Rgb8 => &[Self::Rgb8, Self::Rgba8],
+            Self::Rgba8 => &[Self::Rgba8],
+            // RGBA32F holds every 16-bit value exactly, so it is preferred over 8-bit
+            Self::Rgba16 => &[Self::Rgba16, Self::Rgba32F, Self::Rgba8],
+            Self::Rgba32F => &[Self::Rgba32F, Self::Rgba16, Self::Rgba8],
+        };
+        candidates
+            .iter()
+            .copied()
+            .find(|format| supported.contains(*format))
+            .unwrap_or(Self::Rgba8)
+    }
+}
+
+impl fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::L8 => "L8",
+            Self::La8 => "LA8",
+            Self::Rgb8 => "RGB8",
+            Self::Rgba8 => "RGBA8",
+            Self::Rgba16 => "RGBA16",
+            Self::Rgba32F => "RGBA32F",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Pixel formats a plugin accepts, as returned by its `plugin_pixel_formats` bitmask
+/// (bit `1 << code` per format). RGBA8 is always included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatSet(u32);
+
+impl FormatSet {
+    /// The set of plugins that predate `plugin_pixel_formats` or do not export it.
+    pub const RGBA8_ONLY: FormatSet = FormatSet(1 << PixelFormat::Rgba8 as u32);
+
+    /// Builds a set from a plugin's bitmask. Bits of unknown formats are ignored.
+    pub fn from_mask(mask: u32) -> Self {
+        Self(mask | Self::RGBA8_ONLY.0)
+    }
+
+    pub fn contains(self, format: PixelFormat) -> bool {
+        self.0 & (1 << format.code()) != 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = PixelFormat> {
+        PixelFormat::ALL
+            .into_iter()
+            .filter(move |format| self.contains(*format))
+    }
+}
+
+impl fmt::Display for FormatSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self.iter().map(|format| format.to_string()).collect();
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+/// An image as raw bytes in one of the plugin pixel formats, handed from step to step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pixels {
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+    pub data: Vec<u8>,
+}
+
+impl Pixels {
+    /// Wraps `data`, checking that it holds exactly `width` x `height` pixels of `format`.
+    pub fn new(width: u32, height: u32, format: PixelFormat, data: Vec<u8>) -> Result<Self> {
+        let expected_len = format
+            .buffer_len(width, height)
+            .context("Image dimensions overflow")?;
+        if data.len() != expected_len {
+            anyhow::bail!(
+                "Buffer size mismatch: expected {} bytes for a {}x{} {} image, got {}",
+                expected_len,
+                width,
+                height,
+                format,
+                data.len()
+            );
+        }
+        Ok(Self {
+            width,
+            height,
+            format,
+            data,
+        })
+    }
+
+    /// Converts a decoded image to the format that keeps all of its precision.
+    pub fn from_image(image: DynamicImage) -> Self {
+        let format = PixelFormat::of_image(&image);
+        Self::from_image_as(image, format)
+    }
+
+    fn from_image_as(image: DynamicImage, format: PixelFormat) -> Self {
+        let (width, height) = (image.width(), image.height());
+        let data = match format {
+            PixelFormat::L8 => image.into_luma8().into_raw(),
+            PixelFormat::La8 => image.into_luma_alpha8().into_raw(),
+            PixelFormat::Rgb8 => image.into_rgb8().into_raw(),
+            PixelFormat::Rgba8 => image.into_rgba8().into_raw(),
+            PixelFormat::Rgba16 => image
+                .into_rgba16()
+                .into_raw()
+                .into_iter()
+                .flat_map(u16::to_ne_bytes)
+                .collect(),
+            PixelFormat::Rgba32F => image
+                .into_rgba32f()
+                .into_raw()
+                .into_iter()
+                .flat_map(f32::to_ne_bytes)
+                .collect(),
+        };
+        Self {
+            width,
+            height,
+            format,
+            data,
+        }
+    }
+
+    pub fn into_image(self) -> Result<DynamicImage> {
+        let (width, height) = (self.width, self.height);
+        let image = match self.format {
+            PixelFormat::L8 => {
+                ImageBuffer::<Luma<u8>, _>::from_raw(width, height, self.data).map(Into::into)
+            }
+            PixelFormat::La8 => {
+                ImageBuffer::<LumaA<u8>, _>::from_raw(width, height, self.data).map(Into::into)
+            }
+            PixelFormat::Rgb8 => {
+                ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, self.data).map(Into::into)
+            }
+            PixelFormat::Rgba8 => {
+                ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, self.data).map(Into::into)
+            }
+            PixelFormat::Rgba16 => {
+                let samples = self
+                    .data
+                    .chunks_exact(2)
+                    .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+                    .collect();
+                ImageBuffer::<Rgba<u16>, _>::from_raw(width, height, samples).map(Into::into)
+            }
+            PixelFormat::Rgba32F => {
+                let samples = self
+                    .data
+                    .chunks_exact(4)
+                    .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                    .collect();
+                ImageBuffer::<Rgba<f32>, _>::from_raw(width, height, samples).map(Into::into)
+            }
+        };
+        image.with_context(|| {
+            format!(
+                "Buffer size mismatch for a {}x{} {} image",
+                width, height, self.format
+            )
+        })
+    }
+
+    /// Returns a view of all the pixels, with tightly packed rows.
+    pub fn view_mut(&mut self) -> PixelsViewMut<'_> {
+        let stride = self.width as usize * self.format.bytes_per_pixel();
+        PixelsViewMut {
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            stride,
+            data: &mut self.data,
+        }
+    }
+
+    /// Returns a view of the pixels inside `region`, which must lie within the image.
+    pub fn region_mut(&mut self, region: Region) -> Result<PixelsViewMut<'_>> {
+        let (start, stride) = self.region_start(region)?;
+        PixelsViewMut::new(
+            region.width,
+            region.height,
+            self.format,
+            stride,
+            &mut self.data[start..],
+        )
+    }
+
+    /// Copies the pixels inside `region`, which must lie within the image.
+    pub fn crop(&self, region: Region) -> Result<Pixels> {
+        let (start, stride) = self.region_start(region)?;
+        let row_bytes = region.width as usize * self.format.bytes_per_pixel();
+        let data = (0..region.height as usize)
+            .flat_map(|y| &self.data[start + y * stride..][..row_bytes])
+            .copied()
+            .collect();
+        Pixels::new(region.width, region.height, self.format, data)
+    }
+
+    /// Returns the offset of the first pixel of `region` and the row stride.
+    fn region_start(&self, region: Region) -> Result<(usize, usize)> {
+        let fits =
+            |start: u32, len: u32, size: u32| start.checked_add(len).is_some_and(|end| end <= size);
+        if !fits(region.x, region.width, self.width) || !fits(region.y, region.height, self.height)
+        {
+            anyhow::bail!(
+                "Region {} does not fit in the {}x{} image",
+                region,
+                self.width,
+                self.height
+            );
+        }
+
+        let bytes_per_pixel = self.format.bytes_per_pixel();
+        let stride = self.width as usize * bytes_per_pixel;
+        let start = region.y as usize * stride + region.x as usize * bytes_per_pixel;
+        Ok((start, stride))
+    }
+
+    /// Converts the pixels to `format` in place.
+    pub fn convert(&mut self, format: PixelFormat) -> Result<()> {
+        if format == self.format {
+            return Ok(());
+        }
+        let data = std::mem::take(&mut self.data);
+        let image = Self { data, ..*self }.into_image()?;
+        *self = Self::from_image_as(image, format);
+        Ok(())
+    }
+}
+
+/// Converts `image` back to the `source` layout it was decoded from when it was widened for
+/// the plugins (16-bit gray and RGB, float RGB) and still fits that layout exactly, so gray
+/// stays gray and opaque stays opaque unless a plugin added color or transparency.
+pub fn restore_layout(image: DynamicImage, source: ColorType) -> DynamicImage {
+    if fits_layout(&image, source) {
+        into_color(image, source)
+    } else {
+        image
+    }
+}
+
+/// Returns true when `source` is a layout the plugins' formats are wider than and `image`
+/// can be narrowed back to it without loss.
+pub fn fits_layout(image: &DynamicImage, source: ColorType) -> bool {
+    match (image, source) {
+        (
+            DynamicImage::ImageRgba16(buffer),
+            ColorType::L16 | ColorType::La16 | ColorType::Rgb16,
+        ) => buffer.pixels().all(|&Rgba([r, g, b, a])| {
+            (source == ColorType::Rgb16 || (r == g && g == b))
+                && (source == ColorType::La16 || a == u16::MAX)
+        }),
+        (DynamicImage::ImageRgba32F(buffer), ColorType::Rgb32F) => {
+            buffer.pixels().all(|pixel| pixel[3] == 1.0)
+        }
+        _ => false,
+    }
+}
+
+/// Returns true for the layouts the plugins get in a wider format, which
+/// [`restore_layout`] may narrow the result back to.
+pub fn is_widened(source: ColorType) -> bool {
+    matches!(
+        source,
+        ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgb32F
+    )
+}
+
+/// Converts `image` to the `color` layout; layouts without 8-bit, 16-bit or float samples
+/// become RGBA8.
+pub fn into_color(image: DynamicImage, color: ColorType) -> DynamicImage {
+    match color {
+        ColorType::L8 => image.into_luma8().into(),
+        ColorType::La8 => image.into_luma_alpha8().into(),
+        ColorType::Rgb8 => image.into_rgb8().into(),
+        ColorType::L16 => image.into_luma16().into(),
+        ColorType::La16 => image.into_luma_alpha16().into(),
+        ColorType::Rgb16 => image.into_rgb16().into(),
+        ColorType::Rgba16 => image.into_rgba16().into(),
+        ColorType::Rgb32F => image.into_rgb32f().into(),
+        ColorType::Rgba32F => image.into_rgba32f().into(),
+        _ => image.into_rgba8().into(),
+    }
+}
+
+/// A rectangle of an image, as given to `--region x,y,w,h`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let numbers = value
+            .split(',')
+            .map(|number| number.trim().parse::<u32>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| format!("expected X,Y,WIDTH,HEIGHT in pixels, got '{}'", value))?;
+        let [x, y, width, height] = numbers[..] else {
+            return Err(format!(
+                "expected X,Y,WIDTH,HEIGHT in pixels, got '{}'",
+                value
+            ));
+        };
+        if width == 0 || height == 0 {
+            return Err(format!("region '{}' is empty", value));
+        }
+        Ok(Self {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{} at {},{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// `height` rows of `width` pixels inside a larger buffer, each row starting `stride` bytes
+/// after the previous one. The bytes between rows belong to someone else and are never touched.
+#[derive(Debug)]
+pub struct PixelsViewMut<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+    pub stride: usize,
+    /// From the first pixel of the view to the last, padding between rows included.
+    data: &'a mut [u8],
+}
+
+impl<'a> PixelsViewMut<'a> {
+    /// Wraps `data`, which starts at the first pixel of the view and must reach its last one.
+    pub fn new(
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+        stride: usize,
+        data: &'a mut [u8],
+    ) -> Result<Self> {
+        let row_bytes = format
+            .buffer_len(width, 1)
+            .context("Image dimensions overflow")?;
+        if stride < row_bytes {
+            anyhow::bail!(
+                "Row stride {} is shorter than a row of {} {} pixels ({} bytes)",
+                stride,
+                width,
+                format,
+                row_bytes
+            );
+        }
+        let expected_len = match height {
+            0 => 0,
+            _ => stride
+                .checked_mul(height as usize - 1)
+                .and_then(|n| n.checked_add(row_bytes))
+                .context("Image dimensions overflow")?,
+        };
+        if data.len() < expected_len {
+            anyhow::bail!(
+                "Buffer too short: expected at least {} bytes for a {}x{} {} view with stride {}, got {}",
+                expected_len,
+                width,
+                height,
+                format,
+                stride,
+                data.len()
+            );
+        }
+        Ok(Self {
+            width,
+            height,
+            format,
+            stride,
+            data: &mut data[..expected_len],
+        })
+    }
+
+    fn row_bytes(&self) -> usize {
+        self.width as usize * self.format.bytes_per_pixel()
+    }
+
+    /// Returns `true` if there is no padding between rows.
+    pub fn is_packed(&self) -> bool {
+        self.stride == self.row_bytes()
+    }
+
+    /// The bytes of the view, from its first pixel to its last.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        self.data
+    }
+
+    fn rows(&self) -> impl Iterator<Item = std::ops::Range<usize>> + use<> {
+        let (stride, row_bytes) = (self.stride, self.row_bytes());
+        (0..self.height as usize).map(move |y| y * stride..y * stride + row_bytes)
+    }
+
+    /// Copies the pixels into a buffer with tightly packed rows.
+    pub fn to_packed(&self) -> Vec<u8> {
+        self.rows()
+            .flat_map(|row| self.data[row].iter().copied())
+            .collect()
+    }
+
+    /// Overwrites the pixels from a buffer with tightly packed rows.
+    pub fn copy_from_packed(&mut self, packed: &[u8]) {
+        for (row, packed_row) in self.rows().zip(packed.chunks_exact(self.row_bytes())) {
+            self.data[row].copy_from_slice(packed_row);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_of_image_keeps_source_precision() {
+        assert_eq!(
+            PixelFormat::of_image(&DynamicImage::new_luma8(1, 1)),
+            PixelFormat::L8
+        );
+        assert_eq!(
+            PixelFormat::of_image(&DynamicImage::new_rgb8(1, 1)),
+            PixelFormat::Rgb8
+        );
+        assert_eq!(
+            PixelFormat::of_image(&DynamicImage::new_rgba8(1, 1)),
+            PixelFormat::Rgba8
+        );
+        assert_eq!(
+            PixelFormat::of_image(&DynamicImage::new_luma16(1, 1)),
+            PixelFormat::Rgba16
+        );
+        assert_eq!(
+            PixelFormat::of_image(&DynamicImage::new_rgb32f(1, 1)),
+            PixelFormat::Rgba32F
+        );
+    }
+
+    #[test]
+    fn test_restore_layout_narrows_only_without_loss() {
+        let gray = DynamicImage::ImageLuma16(ImageBuffer::from_fn(3, 2, |x, y| {
+            Luma([x as u16 * 20000 + y as u16])
+        }));
+        let widened = Pixels::from_image(gray.clone())
+            .into_image()
+            .expect("valid buffer");
+        assert_eq!(widened.color(), ColorType::Rgba16);
+        assert_eq!(restore_layout(widened.clone(), ColorType::L16), gray);
+        assert_eq!(
+            restore_layout(widened.clone(), ColorType::Rgb16),
+            DynamicImage::ImageRgb16(gray.to_rgb16())
+        );
+
+        // A plugin that added color keeps the widened layout
+        let mut tinted = widened.into_rgba16();
+        tinted.put_pixel(0, 0, Rgba([1, 2, 3, u16::MAX]));
+        let tinted = DynamicImage::ImageRgba16(tinted);
+        assert_eq!(restore_layout(tinted.clone(), ColorType::L16), tinted);
+
+        let float = DynamicImage::new_rgb32f(2, 2);
+        let widened = Pixels::from_image(float.clone())
+            .into_image()
+            .expect("valid buffer");
+        assert_eq!(restore_layout(widened, ColorType::Rgb32F), float);
+    }
+
+    #[test]
+    fn test_negotiate_prefers_richest_supported_format() {
+        let all = FormatSet::from_mask(0b111);
+        let deep = FormatSet::from_mask(1 << PixelFormat::Rgba32F.code());
+
+        assert_eq!(PixelFormat::Rgba16.negotiate(all), PixelFormat::Rgba16);
+        assert_eq!(PixelFormat::Rgba16.negotiate(deep), PixelFormat::Rgba32F);
+        assert_eq!(
+            PixelFormat::Rgba32F.negotiate(FormatSet::RGBA8_ONLY),
+            PixelFormat::Rgba8
+        );
+        // An 8-bit image gains nothing from a deeper format
+        assert_eq!(PixelFormat::Rgba8.negotiate(all), PixelFormat::Rgba8);
+    }
+
+    #[test]
+    fn test_negotiate_expands_gray_as_little_as_possible() {
+        let gray_alpha = FormatSet::from_mask(1 << PixelFormat::La8.code());
+
+        assert_eq!(PixelFormat::L8.negotiate(gray_alpha), PixelFormat::La8);
+        assert_eq!(
+            PixelFormat::L8.negotiate(FormatSet::RGBA8_ONLY),
+            PixelFormat::Rgba8
+        );
+        assert_eq!(PixelFormat::Rgb8.negotiate(gray_alpha), PixelFormat::Rgba8);
+    }
+
+    #[test]
+    fn test_gray_pixels_take_one_byte_and_expand_on_convert() {
+        let image =
+            DynamicImage::ImageLuma8(ImageBuffer::from_fn(3, 1, |x, _| Luma([x as u8 * 100])));
+
+        let mut pixels = Pixels::from_image(image);
+        assert_eq!(pixels.format, PixelFormat::L8);
+        assert_eq!(pixels.data, vec![0, 100, 200]);
+
+        pixels
+            .convert(PixelFormat::Rgba8)
+            .expect("conversion should succeed");
+        assert_eq!(
+            pixels.data,
+            vec![0, 0, 0, 255, 100, 100, 100, 255, 200, 200, 200, 255]
+        );
+    }
+
+    #[test]
+    fn test_format_set_always_contains_rgba8() {
+        let set = FormatSet::from_mask(1 << PixelFormat::Rgba16.code() | 1 << 31);
+
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            vec![PixelFormat::Rgba8, PixelFormat::Rgba16]
+        );
+        assert_eq!(set.to_string(), "RGBA8, RGBA16");
+    }
+
+    #[test]
+    fn test_pixels_roundtrip_and_convert() {
+        let image = DynamicImage::ImageRgba16(ImageBuffer::from_fn(2, 1, |x, _| {
+            Rgba([x as u16 * 1000, 65535, 0, 65535])
+        }));
+
+        let mut pixels = Pixels::from_image(image.clone());
+        assert_eq!(pixels.format, PixelFormat::Rgba16);
+        assert_eq!(pixels.data.len(), 2 * 8);
+        assert_eq!(pixels.clone().into_image().expect("valid buffer"), image);
+
+        pixels
+            .convert(PixelFormat::Rgba32F)
+            .expect("conversion should succeed");
+        assert_eq!(pixels.data.len(), 2 * 16);
+        pixels
+            .convert(PixelFormat::Rgba16)
+            .expect("conversion should succeed");
+        assert_eq!(pixels.into_image().expect("valid buffer"), image);
+    }
+
+    #[test]
+    fn test_view_packs_and_unpacks_rows_around_padding() {
+        // 2x2 LA8 view with 2 bytes of padding after each row
+        let mut buffer: Vec<u8> = (0..10).collect();
+        let mut view =
+            PixelsViewMut::new(2, 2, PixelFormat::La8, 6, &mut buffer).expect("valid view");
+        assert!(!view.is_packed());
+        assert_eq!(view.to_packed(), vec![0, 1, 2, 3, 6, 7, 8, 9]);
+
+        view.copy_from_packed(&[10, 11, 12, 13, 16, 17, 18, 19]);
+        assert_eq!(buffer, vec![10, 11, 12, 13, 4, 5, 16, 17, 18, 19]);
+    }
+
+    #[test]
+    fn test_view_checks_stride_and_length() {
+        let mut buffer = vec![0u8; 9];
+
+        let err = PixelsViewMut::new(2, 2, PixelFormat::Rgb8, 5, &mut buffer).expect_err("short");
+        assert!(
+            err.to_string().contains("shorter than a row"),
+            "got: {}",
+            err
+        );
+        let err = PixelsViewMut::new(2, 2, PixelFormat::Rgb8, 6, &mut buffer).expect_err("short");
+        assert!(err.to_string().contains("Buffer too short"), "got: {}", err);
+
+        let mut pixels = Pixels::new(3, 1, PixelFormat::Rgb8, buffer).expect("valid");
+        assert!(pixels.view_mut().is_packed());
+    }
+
+    #[test]
+    fn test_region_parses_and_rejects_malformed_values() {
+        assert_eq!(
+            "10, 20,30,40".parse::<Region>(),
+            Ok(Region {
+                x: 10,
+                y: 20,
+                width: 30,
+                height: 40
+            })
+        );
+        assert!("10,20,30".parse::<Region>().is_err());
+        assert!("10,20,30,40,50".parse::<Region>().is_err());
+        assert!("-1,0,5,5".parse::<Region>().is_err());
+        assert!("0,0,0,5".parse::<Region>().is_err());
+    }
+
+    #[test]
+    fn test_region_mut_views_the_rectangle() {
+        // 3x2 L8 image; region covers the right two pixels of the bottom row
+        let mut pixels = Pixels::new(3, 2, PixelFormat::L8, vec![0, 1, 2, 3, 4, 5]).expect("valid");
+        let region: Region = "1,1,2,1".parse().expect("valid region");
+
+        let view = pixels.region_mut(region).expect("region fits");
+        assert_eq!(view.to_packed(), vec![4, 5]);
+        assert_eq!(pixels.crop(region).expect("region fits").data, vec![4, 5]);
+
+        let err = pixels
+            .region_mut("2,0,2,1".parse().expect("valid region"))
+            .expect_err("region sticks out");
+        assert!(
+            err.to_string()
+                .contains("Region 2x1 at 2,0 does not fit in the 3x2 image"),
+            "got: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_pixels_new_checks_length() {
+        assert!(Pixels::new(2, 1, PixelFormat::Rgba16, vec![0; 16]).is_ok());
+
+        let err = Pixels::new(2, 1, PixelFormat::Rgba16, vec![0; 8]).expect_err("too short");
+        assert!(err.to_string().contains("Buffer size mismatch"));
+    }
+}
@@ -1,100 +1,1174 @@
-use std::ffi::{CString, c_char};
-use std::path::Path;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
+use image::{DynamicImage, RgbaImage};
 use libloading::Library;
 use log::{debug, info};
 
+use crate::params_schema::ParamsSchema;
+use crate::pixels::{FormatSet, PixelFormat, Pixels, PixelsViewMut, Region};
+
 type ProcessImageFn = unsafe extern "C" fn(u32, u32, *mut u8, *const c_char) -> i32;
+type PluginAbiVersionFn = unsafe extern "C" fn() -> u32;
+type PluginInfoFn = unsafe extern "C" fn() -> *const RawPluginInfo;
+type PluginParamsSchemaFn = unsafe extern "C" fn() -> *const c_char;
+type PluginLastErrorFn = unsafe extern "C" fn() -> *const c_char;
+type PluginOutputDimensionsFn =
+    unsafe extern "C" fn(u32, u32, *const c_char, *mut u32, *mut u32) -> i32;
+type TransformImageFn =
+    unsafe extern "C" fn(u32, u32, *const u8, u32, u32, *mut u8, *const c_char) -> i32;
+type PluginPixelFormatsFn = unsafe extern "C" fn() -> u32;
+type ProcessPixelsFn = unsafe extern "C" fn(u32, u32, u32, *mut u8, *const c_char) -> i32;
+type TransformPixelsFn =
+    unsafe extern "C" fn(u32, u32, u32, *const u8, u32, u32, *mut u8, *const c_char) -> i32;
+type ProcessStridedFn = unsafe extern "C" fn(u32, u32, u32, usize, *mut u8, *const c_char) -> i32;
+type PluginHaloFn = unsafe extern "C" fn(*const c_char, *mut u32) -> i32;
+type ProgressCallbackFn = unsafe extern "C" fn(*mut c_void, f32);
+type CancelCallbackFn = unsafe extern "C" fn(*mut c_void) -> bool;
+type LogCallbackFn = unsafe extern "C" fn(u32, *const c_char, *const c_char);
+type PluginSetLogCallbackFn = unsafe extern "C" fn(Option<LogCallbackFn>, u32);
+/// Signature of the `plugin_set_*_callback` exports; a null callback removes the current one.
+// `C` is always one of the callback function pointer types above, for which `Option<C>` is
+// FFI-safe; the lint cannot see that through the type parameter.
+#[allow(improper_ctypes_definitions)]
+type SetCallbackFn<C> = unsafe extern "C" fn(Option<C>, *mut c_void);
 
-/// Returns the platform-specific library filename for a plugin.
-pub(crate) fn library_filename(plugin_name: &str) -> String {
+/// Plugin ABI version implemented by this host.
+///
+/// Compatibility policy: the host accepts any plugin whose ABI version lies in
+/// `MIN_SUPPORTED_ABI_VERSION..=HOST_ABI_VERSION`. Additive changes (new optional
+/// exports) bump `HOST_ABI_VERSION` only; breaking changes to existing exports also
+/// raise `MIN_SUPPORTED_ABI_VERSION`.
+pub(crate) const HOST_ABI_VERSION: u32 = 12;
+
+/// Oldest plugin ABI version this host can still call safely.
+pub(crate) const MIN_SUPPORTED_ABI_VERSION: u32 = 1;
+
+/// First ABI version in which plugins must export `plugin_info`.
+const PLUGIN_INFO_ABI_VERSION: u32 = 2;
+
+/// First ABI version in which plugins must export `plugin_params_schema`.
+const PARAMS_SCHEMA_ABI_VERSION: u32 = 3;
+
+/// First ABI version in which plugins must export `plugin_last_error`.
+const LAST_ERROR_ABI_VERSION: u32 = 4;
+
+/// First ABI version in which plugins may export `plugin_output_dimensions` and
+/// `transform_image` to produce an image of a different size.
+const TRANSFORM_ABI_VERSION: u32 = 5;
+
+/// First ABI version in which plugins may export `plugin_pixel_formats` to accept pixel formats
+/// other than RGBA8 through `process_pixels` and `transform_pixels`.
+const PIXEL_FORMATS_ABI_VERSION: u32 = 6;
+
+/// First ABI version in which the pixel formats include the L8, LA8 and RGB8 layouts.
+const CHANNEL_LAYOUTS_ABI_VERSION: u32 = 7;
+
+/// First ABI version in which plugins may export `process_strided` to process rows that are
+/// not tightly packed.
+const STRIDE_ABI_VERSION: u32 = 8;
+
+/// First ABI version in which plugins may export `plugin_halo` to work on tiles.
+const HALO_ABI_VERSION: u32 = 9;
+
+/// First ABI version in which plugins may export `plugin_set_progress_callback` to report how
+/// far a call has got.
+const PROGRESS_ABI_VERSION: u32 = 10;
+
+/// First ABI version in which plugins may export `plugin_set_cancel_callback` to stop a call
+/// early when the host asks.
+const CANCEL_ABI_VERSION: u32 = 11;
+
+/// First ABI version in which plugins may export `plugin_set_log_callback` to send their `log`
+/// records to the host's logger.
+const LOG_ABI_VERSION: u32 = 12;
+
+/// Well-known error codes shared by all plugins. Codes between -1 and -999 are reserved
+/// for this list; plugins may define their own codes at or below
+/// `ERROR_PLUGIN_SPECIFIC_BASE`.
+pub(crate) const ERROR_INVALID_PARAMS: i32 = -1;
+pub(crate) const ERROR_SIZE_OVERFLOW: i32 = -2;
+pub(crate) const ERROR_PARAM_OUT_OF_RANGE: i32 = -3;
+pub(crate) const ERROR_PANIC: i32 = -4;
+pub(crate) const ERROR_UNSUPPORTED_FORMAT: i32 = -5;
+pub(crate) const ERROR_INVALID_STRIDE: i32 = -6;
+pub(crate) const ERROR_CANCELLED: i32 = -7;
+pub(crate) const ERROR_PLUGIN_SPECIFIC_BASE: i32 = -1000;
+
+/// C layout of the static descriptor returned by a plugin's `plugin_info` export.
+/// Must match the `PluginInfo` struct declared in every plugin.
+#[repr(C)]
+struct RawPluginInfo {
+    name: *const c_char,
+    version: *const c_char,
+    description: *const c_char,
+    author: *const c_char,
+    /// Comma-separated list of supported operations.
+    operations: *const c_char,
+}
+
+/// Plugin metadata copied out of the library's static descriptor.
+#[derive(Debug, Clone)]
+pub struct PluginInfo {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub author: String,
+    pub operations: Vec<String>,
+    pub abi_version: u32,
+    /// Declared parameters, `None` for plugins built before schemas were part of the ABI.
+    pub params_schema: Option<ParamsSchema>,
+    pub pixel_formats: FormatSet,
+}
+
+impl PluginInfo {
+    /// Checks `params` against the parameter schema, if the plugin declares one.
+    pub fn validate_params(&self, params: &str) -> Result<()> {
+        let Some(schema) = &self.params_schema else {
+            return Ok(());
+        };
+
+        schema.validate(params).with_context(|| {
+            format!(
+                "Invalid parameters for plugin {} (run `image_processor info --plugin {}` for the parameter list)",
+                self.name, self.name
+            )
+        })?;
+        debug!("Parameters validated against {} schema", self.name);
+        Ok(())
+    }
+}
+
+/// Returns the platform-specific prefix and extension of plugin library filenames.
+fn library_affixes() -> (&'static str, &'static str) {
     if cfg!(target_os = "macos") {
-        format!("lib{}.dylib", plugin_name)
+        ("lib", ".dylib")
     } else if cfg!(target_os = "linux") {
-        format!("lib{}.so", plugin_name)
+        ("lib", ".so")
     } else if cfg!(target_os = "windows") {
-        format!("{}.dll", plugin_name)
+        ("", ".dll")
     } else {
         // Fallback to Linux-style naming for unknown platforms
-        format!("lib{}.so", plugin_name)
+        ("lib", ".so")
     }
 }
 
-/// Loads a plugin from the given path and processes the image data.
+/// Returns the platform-specific library filename for a plugin.
+pub(crate) fn library_filename(plugin_name: &str) -> String {
+    let (prefix, extension) = library_affixes();
+    format!("{}{}{}", prefix, plugin_name, extension)
+}
+
+/// Returns the plugin name for a library filename, or `None` if the file does not follow
+/// the [`library_filename`] convention.
+pub(crate) fn plugin_name_from_filename(file_name: &str) -> Option<&str> {
+    let (prefix, extension) = library_affixes();
+    file_name
+        .strip_prefix(prefix)?
+        .strip_suffix(extension)
+        .filter(|name| !name.is_empty())
+}
+
+/// Returns a short description of a plugin error code.
+pub(crate) fn describe_error_code(code: i32) -> &'static str {
+    match code {
+        ERROR_INVALID_PARAMS => "invalid parameters",
+        ERROR_SIZE_OVERFLOW => "image size overflow",
+        ERROR_PARAM_OUT_OF_RANGE => "parameter value out of range",
+        ERROR_PANIC => "plugin panicked",
+        ERROR_UNSUPPORTED_FORMAT => "unsupported pixel format",
+        ERROR_INVALID_STRIDE => "invalid row stride",
+        ERROR_CANCELLED => "cancelled",
+        code if code <= ERROR_PLUGIN_SPECIFIC_BASE => "plugin-specific error",
+        _ => "unknown error",
+    }
+}
+
+/// Checks a plugin's reported ABI version against the range supported by the host.
+pub(crate) fn check_abi_version(plugin_version: u32) -> Result<()> {
+    if (MIN_SUPPORTED_ABI_VERSION..=HOST_ABI_VERSION).contains(&plugin_version) {
+        return Ok(());
+    }
+
+    let supported = if MIN_SUPPORTED_ABI_VERSION == HOST_ABI_VERSION {
+        format!("v{}", HOST_ABI_VERSION)
+    } else {
+        format!("v{}..=v{}", MIN_SUPPORTED_ABI_VERSION, HOST_ABI_VERSION)
+    };
+    anyhow::bail!(
+        "Incompatible plugin: plugin built for ABI v{}, host supports {}",
+        plugin_version,
+        supported
+    );
+}
+
+/// Reads the `plugin_abi_version` export and verifies it is supported by the host.
 ///
-/// # Arguments
-/// * `plugin_path` - Full path to the plugin library file
-/// * `width` - Image width in pixels
-/// * `height` - Image height in pixels
-/// * `rgba_data` - Mutable slice of RGBA pixel data (must be width * height * 4 bytes)
-/// * `params` - JSON parameters string to pass to the plugin
-pub fn process(
-    plugin_path: &Path,
-    width: u32,
-    height: u32,
-    rgba_data: &mut [u8],
-    params: &str,
-) -> Result<()> {
+/// Must be called before resolving any other symbol: until the version is known,
+/// the signatures of the remaining exports cannot be trusted.
+fn verify_abi_version(lib: &Library, plugin_path: &Path) -> Result<u32> {
+    // SAFETY: plugin_abi_version is the one export whose signature is fixed across all
+    // ABI versions (no arguments, returns u32), so resolving it with this type is sound
+    // for any plugin that exports the symbol at all.
+    let abi_version_fn: libloading::Symbol<PluginAbiVersionFn> =
+        unsafe { lib.get(b"plugin_abi_version\0") }.with_context(|| {
+            format!(
+                "Plugin {} does not export plugin_abi_version (built for an unversioned ABI?)",
+                plugin_path.display()
+            )
+        })?;
+
+    // SAFETY: The symbol was resolved above with its fixed signature; it takes no
+    // arguments and only returns a constant.
+    let plugin_version = unsafe { abi_version_fn() };
     debug!(
-        "plugin_loader::process called with path={}, dimensions={}x{}, params={}",
+        "Plugin {} reports ABI v{}",
         plugin_path.display(),
-        width,
-        height,
-        params
+        plugin_version
     );
 
-    info!("Loading plugin from: {}", plugin_path.display());
-
-    // Validate buffer size before FFI call with checked arithmetic
-    let expected_len = (width as usize)
-        .checked_mul(height as usize)
-        .and_then(|n| n.checked_mul(4))
-        .ok_or_else(|| anyhow::anyhow!("Image dimensions overflow"))?;
-    debug_assert_eq!(
-        rgba_data.len(),
-        expected_len,
-        "Buffer size mismatch: expected {} bytes for {}x{} RGBA image, got {}",
-        expected_len,
-        width,
-        height,
-        rgba_data.len()
+    check_abi_version(plugin_version)
+        .with_context(|| format!("Refusing to load plugin: {}", plugin_path.display()))?;
+
+    Ok(plugin_version)
+}
+
+/// Copies a plugin-owned C string into an owned `String`. Null pointers become empty strings.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a valid null-terminated string that outlives this call.
+unsafe fn string_from_plugin(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    // SAFETY: ptr is non-null and the caller guarantees it is a valid C string.
+    unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Reads the plugin's metadata descriptor. Returns `None` for plugins built before
+/// `plugin_info` was part of the ABI.
+fn read_plugin_info(
+    lib: &Library,
+    plugin_path: &Path,
+    abi_version: u32,
+) -> Result<Option<PluginInfo>> {
+    if abi_version < PLUGIN_INFO_ABI_VERSION {
+        return Ok(None);
+    }
+
+    // SAFETY: The plugin's ABI version was verified to include plugin_info, so the library
+    // promises to export it with this signature.
+    let plugin_info_fn: libloading::Symbol<PluginInfoFn> = unsafe { lib.get(b"plugin_info\0") }
+        .with_context(|| {
+            format!(
+                "Plugin {} reports ABI v{} but does not export plugin_info",
+                plugin_path.display(),
+                abi_version
+            )
+        })?;
+
+    // SAFETY: plugin_info takes no arguments and returns a pointer to a static descriptor.
+    let raw = unsafe { plugin_info_fn() };
+    if raw.is_null() {
+        anyhow::bail!(
+            "Plugin {} returned a null plugin_info descriptor",
+            plugin_path.display()
+        );
+    }
+
+    // SAFETY: raw is non-null and points to a 'static PluginInfo owned by the library, whose
+    // fields are null or valid null-terminated strings. The library stays loaded while we copy.
+    let [name, version, description, author, operations] = unsafe {
+        let raw = &*raw;
+        [
+            raw.name,
+            raw.version,
+            raw.description,
+            raw.author,
+            raw.operations,
+        ]
+        .map(|ptr| string_from_plugin(ptr))
+    };
+
+    Ok(Some(PluginInfo {
+        name,
+        version,
+        description,
+        author,
+        operations: operations
+            .split(',')
+            .map(str::trim)
+            .filter(|op| !op.is_empty())
+            .map(String::from)
+            .collect(),
+        abi_version,
+        params_schema: read_params_schema(lib, plugin_path, abi_version)?,
+        pixel_formats: read_pixel_formats(lib, abi_version),
+    }))
+}
+
+/// Reads and parses the JSON Schema of the plugin's parameters. Returns `None` for plugins
+/// built before `plugin_params_schema` was part of the ABI.
+fn read_params_schema(
+    lib: &Library,
+    plugin_path: &Path,
+    abi_version: u32,
+) -> Result<Option<ParamsSchema>> {
+    if abi_version < PARAMS_SCHEMA_ABI_VERSION {
+        return Ok(None);
+    }
+
+    // SAFETY: The plugin's ABI version was verified to include plugin_params_schema, so the
+    // library promises to export it with this signature.
+    let schema_fn: libloading::Symbol<PluginParamsSchemaFn> =
+        unsafe { lib.get(b"plugin_params_schema\0") }.with_context(|| {
+            format!(
+                "Plugin {} reports ABI v{} but does not export plugin_params_schema",
+                plugin_path.display(),
+                abi_version
+            )
+        })?;
+
+    // SAFETY: plugin_params_schema takes no arguments and returns a pointer to a static
+    // null-terminated string that lives as long as the library, which is still loaded.
+    let schema_json = unsafe { string_from_plugin(schema_fn()) };
+
+    let schema = ParamsSchema::parse(&schema_json)
+        .with_context(|| format!("Invalid parameter schema in {}", plugin_path.display()))?;
+    Ok(Some(schema))
+}
+
+/// Reads the pixel formats the plugin accepts. Plugins that predate `plugin_pixel_formats` or
+/// do not export it accept RGBA8 only.
+fn read_pixel_formats(lib: &Library, abi_version: u32) -> FormatSet {
+    if abi_version < PIXEL_FORMATS_ABI_VERSION {
+        return FormatSet::RGBA8_ONLY;
+    }
+
+    // SAFETY: The plugin's ABI version was verified to define plugin_pixel_formats, so if the
+    // library exports the symbol it has this signature.
+    let Ok(pixel_formats_fn) =
+        (unsafe { lib.get::<PluginPixelFormatsFn>(b"plugin_pixel_formats\0") })
+    else {
+        return FormatSet::RGBA8_ONLY;
+    };
+
+    // SAFETY: plugin_pixel_formats takes no arguments and only returns a constant bitmask.
+    let mut mask = unsafe { pixel_formats_fn() };
+    if abi_version < CHANNEL_LAYOUTS_ABI_VERSION {
+        // Codes above RGBA32F were undefined before, so their bits cannot be trusted
+        mask &= (1 << (PixelFormat::Rgba32F.code() + 1)) - 1;
+    }
+    FormatSet::from_mask(mask)
+}
+
+/// Routes the plugin's `log` records to this process's logger, if the plugin supports it.
+///
+/// The callback stays installed for as long as the library is loaded; it is the same host
+/// function for every load, so loading a library again only refreshes the level.
+fn install_logger(lib: &Library, abi_version: u32) {
+    if abi_version < LOG_ABI_VERSION {
+        return;
+    }
+
+    // SAFETY: The plugin's ABI version was verified to define plugin_set_log_callback, so if
+    // the library exports the symbol it has this signature.
+    let Ok(set_log_fn) =
+        (unsafe { lib.get::<PluginSetLogCallbackFn>(b"plugin_set_log_callback\0") })
+    else {
+        return;
+    };
+
+    // SAFETY: forward_log is a plain function of the host and never dangles; it accepts any
+    // level and null pointers.
+    unsafe { set_log_fn(Some(forward_log), log::max_level() as u32) };
+}
+
+/// Log callback handed to plugins: logs `message` at `level` (1 = error to 5 = trace) under
+/// the plugin's `target`, which starts with its crate name, so `RUST_LOG` can filter on it.
+unsafe extern "C" fn forward_log(level: u32, target: *const c_char, message: *const c_char) {
+    let level = match level {
+        1 => log::Level::Error,
+        2 => log::Level::Warn,
+        3 => log::Level::Info,
+        4 => log::Level::Debug,
+        5 => log::Level::Trace,
+        _ => return,
+    };
+    if target.is_null() || message.is_null() {
+        return;
+    }
+    // SAFETY: The plugin passes null-terminated strings that stay valid during the call.
+    let (target, message) = unsafe { (CStr::from_ptr(target), CStr::from_ptr(message)) };
+    let target = target.to_string_lossy();
+    log::logger().log(
+        &log::Record::builder()
+            .level(level)
+            .target(&target)
+            .args(format_args!("{}", message.to_string_lossy()))
+            .build(),
     );
+}
+
+/// Exports of a plugin, resolved and checked once when it is loaded.
+///
+/// The function pointers come from the plugin's `Library` and are only valid while it stays
+/// loaded, which [`Plugin`] guarantees by owning both.
+struct Exports {
+    process_image: Option<ProcessImageFn>,
+    process_pixels: Option<ProcessPixelsFn>,
+    process_strided: Option<ProcessStridedFn>,
+    transform: Option<TransformExports>,
+    halo: Option<PluginHaloFn>,
+    last_error: Option<PluginLastErrorFn>,
+    set_progress_callback: Option<SetCallbackFn<ProgressCallbackFn>>,
+    set_cancel_callback: Option<SetCallbackFn<CancelCallbackFn>>,
+}
+
+/// Exports of a plugin that changes the image size.
+struct TransformExports {
+    output_dimensions: PluginOutputDimensionsFn,
+    transform_image: TransformImageFn,
+    transform_pixels: Option<TransformPixelsFn>,
+}
 
+impl Exports {
+    /// Resolves every export the plugin's ABI version allows and checks that the plugin can
+    /// process each pixel format it accepts, so that a broken plugin fails when it is loaded
+    /// rather than on the first image.
+    fn resolve(
+        lib: &Library,
+        plugin_name: &str,
+        abi_version: u32,
+        pixel_formats: FormatSet,
+    ) -> Result<Self> {
+        let lookup = ExportLookup { lib, abi_version };
+
+        // SAFETY: Each symbol is read with the signature the ABI defines for it, from the ABI
+        // version that defines it on.
+        let (output_dimensions, transform_image, transform_pixels) = unsafe {
+            (
+                lookup.get::<PluginOutputDimensionsFn>(
+                    TRANSFORM_ABI_VERSION,
+                    b"plugin_output_dimensions\0",
+                ),
+                lookup.get::<TransformImageFn>(TRANSFORM_ABI_VERSION, b"transform_image\0"),
+                lookup.get::<TransformPixelsFn>(PIXEL_FORMATS_ABI_VERSION, b"transform_pixels\0"),
+            )
+        };
+        // transform_image is mandatory once plugin_output_dimensions is exported
+        let transform = output_dimensions
+            .map(|output_dimensions| {
+                let transform_image = transform_image.with_context(|| {
+                    format!(
+                        "Plugin {} exports plugin_output_dimensions but not transform_image",
+                        plugin_name
+                    )
+                })?;
+                anyhow::Ok(TransformExports {
+                    output_dimensions,
+                    transform_image,
+                    transform_pixels,
+                })
+            })
+            .transpose()?;
+
+        // SAFETY: As above.
+        let exports = unsafe {
+            Self {
+                process_image: lookup.get(MIN_SUPPORTED_ABI_VERSION, b"process_image\0"),
+                process_pixels: lookup.get(PIXEL_FORMATS_ABI_VERSION, b"process_pixels\0"),
+                process_strided: lookup.get(STRIDE_ABI_VERSION, b"process_strided\0"),
+                transform,
+                halo: lookup.get(HALO_ABI_VERSION, b"plugin_halo\0"),
+                last_error: lookup.get(LAST_ERROR_ABI_VERSION, b"plugin_last_error\0"),
+                set_progress_callback: lookup
+                    .get(PROGRESS_ABI_VERSION, b"plugin_set_progress_callback\0"),
+                set_cancel_callback: lookup
+                    .get(CANCEL_ABI_VERSION, b"plugin_set_cancel_callback\0"),
+            }
+        };
+
+        // Every format the plugin accepts needs an export that takes it
+        let (rgba8_export, other_export, has_rgba8, has_other) = match &exports.transform {
+            Some(transform) => (
+                "transform_image",
+                "transform_pixels",
+                true,
+                transform.transform_pixels.is_some(),
+            ),
+            None => (
+                "process_image",
+                "process_pixels",
+                exports.process_image.is_some(),
+                exports.process_pixels.is_some(),
+            ),
+        };
+        if !has_rgba8 {
+            anyhow::bail!("Plugin {} does not export {}", plugin_name, rgba8_export);
+        }
+        if let Some(format) = pixel_formats
+            .iter()
+            .find(|&format| format != PixelFormat::Rgba8)
+            .filter(|_| !has_other)
+        {
+            anyhow::bail!(
+                "Plugin {} accepts {} pixels but does not export {}",
+                plugin_name,
+                format,
+                other_export
+            );
+        }
+
+        Ok(exports)
+    }
+}
+
+/// Looks up the exports of a library built for `abi_version`.
+struct ExportLookup<'lib> {
+    lib: &'lib Library,
+    abi_version: u32,
+}
+
+impl ExportLookup<'_> {
+    /// Resolves the export `symbol` (null-terminated), or returns `None` if the plugin does
+    /// not export it or predates `since`, the first ABI version that defines it.
+    ///
+    /// # Safety
+    ///
+    /// From `since` on, the ABI must define `symbol` with the signature `T`, and the function
+    /// pointer must not be called after the library is unloaded.
+    unsafe fn get<T: Copy>(&self, since: u32, symbol: &[u8]) -> Option<T> {
+        if self.abi_version < since {
+            return None;
+        }
+        // SAFETY: The caller guarantees that `symbol` has the signature `T`.
+        unsafe { self.lib.get::<T>(symbol) }
+            .ok()
+            .map(|symbol| *symbol)
+    }
+}
+
+/// Opens a plugin library and verifies its ABI version. Returns the library and its version.
+fn open_library(plugin_path: &Path) -> Result<(Library, u32)> {
     // SAFETY: The library path is provided by the user and we trust the library to be a valid plugin.
     // If the library is malformed or incompatible, this could cause undefined behavior or crash.
     let lib = unsafe { Library::new(plugin_path) }
         .with_context(|| format!("Failed to load plugin library: {}", plugin_path.display()))?;
 
-    // SAFETY: The symbol name is null-terminated and we trust the library exports this symbol
-    // with the correct signature. If the symbol has a different signature, calling it would
-    // cause undefined behavior due to ABI mismatch.
-    let process_image_fn: libloading::Symbol<ProcessImageFn> =
-        unsafe { lib.get(b"process_image\0") }
-            .with_context(|| "Failed to find process_image symbol")?;
+    let abi_version = verify_abi_version(&lib, plugin_path)?;
+
+    Ok((lib, abi_version))
+}
+
+/// Loaded plugins keyed by plugin name, so each library is opened once however many steps or
+/// images use it.
+#[derive(Default)]
+pub struct PluginRegistry {
+    plugins: HashMap<String, Plugin>,
+}
+
+impl PluginRegistry {
+    /// Returns the plugin registered as `name`, loading it from `plugin_path` first if it is
+    /// not registered yet.
+    pub fn load(&mut self, name: &str, plugin_path: &Path) -> Result<&Plugin> {
+        match self.plugins.entry(name.to_string()) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(Plugin::load(plugin_path)?)),
+        }
+    }
+
+    /// Returns the plugin registered as `name`, if it has been loaded.
+    pub fn get(&self, name: &str) -> Option<&Plugin> {
+        self.plugins.get(name)
+    }
+}
+
+/// Loads a plugin and returns its metadata descriptor, or `None` for plugins built before
+/// metadata was part of the ABI. Nothing else is resolved or called.
+pub fn read_info(plugin_path: &Path) -> Result<Option<PluginInfo>> {
+    let (lib, abi_version) = open_library(plugin_path)?;
+    read_plugin_info(&lib, plugin_path, abi_version)
+}
+
+/// Loads a plugin and returns its metadata descriptor.
+pub fn info(plugin_path: &Path) -> Result<PluginInfo> {
+    let (lib, abi_version) = open_library(plugin_path)?;
+
+    read_plugin_info(&lib, plugin_path, abi_version)?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "Plugin {} was built for ABI v{} and does not provide metadata (plugin_info requires ABI v{})",
+            plugin_path.display(),
+            abi_version,
+            PLUGIN_INFO_ABI_VERSION
+        )
+    })
+}
+
+/// A plugin library that has been loaded once and can process any number of images.
+///
+/// The ABI version is verified, the metadata read and every export resolved and checked when
+/// the plugin is loaded; calls go straight to the resolved functions. The library stays
+/// loaded until the handle is dropped.
+pub struct Plugin {
+    /// Keeps the library, and with it the resolved exports, loaded.
+    _lib: Library,
+    exports: Exports,
+    path: PathBuf,
+    name: String,
+    info: Option<PluginInfo>,
+    pixel_formats: FormatSet,
+}
+
+impl Plugin {
+    /// Loads the plugin library at `plugin_path`, verifies its ABI version and resolves its
+    /// exports.
+    pub fn load(plugin_path: &Path) -> Result<Self> {
+        info!("Loading plugin from: {}", plugin_path.display());
+
+        let (lib, abi_version) = open_library(plugin_path)?;
+        let info = read_plugin_info(&lib, plugin_path, abi_version)?;
+
+        let name = match &info {
+            Some(plugin_info) => {
+                info!(
+                    "Loaded plugin {} v{} (ABI v{}): {}",
+                    plugin_info.name, plugin_info.version, abi_version, plugin_info.description
+                );
+                plugin_info.name.clone()
+            }
+            None => {
+                info!(
+                    "Loaded plugin {} (ABI v{}, no metadata)",
+                    plugin_path.display(),
+                    abi_version
+                );
+                plugin_path.display().to_string()
+            }
+        };
+
+        let pixel_formats = read_pixel_formats(&lib, abi_version);
+        debug!("Plugin {} accepts pixel formats: {}", name, pixel_formats);
+        let exports = Exports::resolve(&lib, &name, abi_version, pixel_formats)?;
+        install_logger(&lib, abi_version);
+
+        Ok(Self {
+            _lib: lib,
+            exports,
+            path: plugin_path.to_path_buf(),
+            name,
+            info,
+            pixel_formats,
+        })
+    }
+
+    /// Returns the plugin's parameter schema, if it exports one.
+    pub fn params_schema(&self) -> Option<&ParamsSchema> {
+        self.info
+            .as_ref()
+            .and_then(|plugin_info| plugin_info.params_schema.as_ref())
+    }
+
+    /// Checks `params` against the plugin's parameter schema, if it exports one.
+    pub fn validate_params(&self, params: &str) -> Result<()> {
+        match &self.info {
+            Some(plugin_info) => plugin_info.validate_params(params),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns how many pixels of context around a tile the plugin reads with `params`, or
+    /// `None` if it cannot work on tiles (it does not export `plugin_halo`).
+    pub fn halo(&self, params: &str) -> Result<Option<u32>> {
+        let Some(halo_fn) = self.exports.halo else {
+            return Ok(None);
+        };
+
+        let c_params = self.prepare_call(PixelFormat::Rgba8, params)?;
+        let mut halo = 0u32;
+        // SAFETY: c_params is a valid null-terminated CString and halo is a local u32 that
+        // outlives the call.
+        let result = unsafe { halo_fn(c_params.as_ptr(), &mut halo) };
+        if result != 0 {
+            return Err(self.failure(result));
+        }
+        debug!("Plugin {} needs a halo of {} pixels", self.name, halo);
+        Ok(Some(halo))
+    }
+
+    /// Runs `call` with the fractions the plugin reports (0.0 to 1.0) passed to `report`.
+    ///
+    /// Only plugin calls made on this thread inside `call` report progress; plugins that do
+    /// not export `plugin_set_progress_callback` never do.
+    pub fn with_progress<T>(&self, report: &dyn Fn(f32), call: impl FnOnce() -> T) -> T {
+        let context: *const &dyn Fn(f32) = &report;
+        // SAFETY: forward_progress has the callback type of plugin_set_progress_callback and
+        // expects a pointer to a `&dyn Fn(f32)`; `report` outlives the guard.
+        let _guard = unsafe {
+            install_callback(
+                self.exports.set_progress_callback,
+                forward_progress,
+                context.cast_mut().cast(),
+            )
+        };
+        call()
+    }
+
+    /// Runs `call` with the plugin stopping early, with error code `-7`, once `cancelled`
+    /// returns true. A cancelled plugin leaves the image buffer as it was.
+    ///
+    /// Only plugin calls made on this thread inside `call` can be cancelled; plugins that do
+    /// not export `plugin_set_cancel_callback` always run to completion.
+    pub fn with_cancel<T>(&self, cancelled: &dyn Fn() -> bool, call: impl FnOnce() -> T) -> T {
+        let context: *const &dyn Fn() -> bool = &cancelled;
+        // SAFETY: forward_cancel has the callback type of plugin_set_cancel_callback and
+        // expects a pointer to a `&dyn Fn() -> bool`; `cancelled` outlives the guard.
+        let _guard = unsafe {
+            install_callback(
+                self.exports.set_cancel_callback,
+                forward_cancel,
+                context.cast_mut().cast(),
+            )
+        };
+        call()
+    }
+
+    /// Processes an RGBA8 image in place with `params` (a JSON object), for callers that hold
+    /// `image` buffers rather than [`Pixels`]. The plugin may change the image size; on error
+    /// the image is left as it was.
+    #[cfg_attr(
+        not(test),
+        expect(dead_code, reason = "the host itself works on Pixels in every format")
+    )]
+    pub fn apply(&self, image: &mut RgbaImage, params: &str) -> Result<()> {
+        let mut pixels = Pixels::from_image(DynamicImage::ImageRgba8(std::mem::take(image)));
+        // Every plugin accepts RGBA8, so the pixels come back in that format
+        let result = self.process_pixels(&mut pixels, params);
+        *image = pixels.into_image()?.into_rgba8();
+        result
+    }
+
+    /// Processes `pixels` with this plugin in the richest format it accepts.
+    ///
+    /// Pixels in a format the plugin does not accept are converted first (see
+    /// [`PixelFormat::negotiate`]) and stay in the new format afterwards.
+    pub fn process_pixels(&self, pixels: &mut Pixels, params: &str) -> Result<()> {
+        let format = self.negotiate(pixels)?;
+
+        if self.exports.transform.is_some() {
+            (pixels.width, pixels.height) = self.process(
+                pixels.width,
+                pixels.height,
+                format,
+                &mut pixels.data,
+                params,
+            )?;
+        } else {
+            self.process_view(&mut pixels.view_mut(), params)?;
+        }
+        Ok(())
+    }
+
+    /// Processes only the pixels inside `region`, leaving the rest of the image untouched.
+    /// Like [`Plugin::process_pixels`], the whole image is converted first if the plugin
+    /// does not accept its format.
+    pub fn process_region(&self, pixels: &mut Pixels, region: Region, params: &str) -> Result<()> {
+        self.negotiate(pixels)?;
+        self.process_view(&mut pixels.region_mut(region)?, params)
+    }
+
+    /// Converts `pixels` to the richest format the plugin accepts and returns it.
+    fn negotiate(&self, pixels: &mut Pixels) -> Result<PixelFormat> {
+        let format = pixels.format.negotiate(self.pixel_formats);
+        if format != pixels.format {
+            info!(
+                "Plugin {} does not accept {} pixels; converting to {}",
+                self.name, pixels.format, format
+            );
+            pixels.convert(format)?;
+        }
+        Ok(format)
+    }
+
+    /// Processes the pixels of `view` in place, leaving the bytes between its rows untouched.
+    /// The view's format must be one the plugin accepts.
+    ///
+    /// Plugins that export `process_strided` work on the view directly; for the others the
+    /// rows are copied to a packed buffer and back unless they are packed already. Plugins
+    /// that change the image size cannot process a view.
+    pub fn process_view(&self, view: &mut PixelsViewMut<'_>, params: &str) -> Result<()> {
+        debug!(
+            "Plugin::process_view called for {} with dimensions={}x{}, format={}, stride={}, params={}",
+            self.path.display(),
+            view.width,
+            view.height,
+            view.format,
+            view.stride,
+            params
+        );
+
+        let c_params = self.prepare_call(view.format, params)?;
+        if self.exports.transform.is_some() {
+            anyhow::bail!(
+                "Plugin {} changes the image size and cannot process part of an image",
+                self.name
+            );
+        }
+
+        self.call_in_place(view, &c_params)?;
+        info!("Plugin execution complete");
+        Ok(())
+    }
+
+    /// Checks that the plugin accepts `format` and `params`, and converts `params` for the call.
+    fn prepare_call(&self, format: PixelFormat, params: &str) -> Result<CString> {
+        if !self.pixel_formats.contains(format) {
+            anyhow::bail!(
+                "Plugin {} does not accept {} pixels (accepts: {})",
+                self.name,
+                format,
+                self.pixel_formats
+            );
+        }
+
+        let c_params = CString::new(params).with_context(|| "Invalid params string")?;
+        self.validate_params(params)?;
+        Ok(c_params)
+    }
+
+    /// Processes the image data with this plugin. `format` must be one the plugin accepts:
+    /// RGBA8 goes through `process_image` / `transform_image`, other formats through
+    /// `process_pixels` / `transform_pixels`.
+    ///
+    /// Plugins that export `transform_image` may change the image size: `data` is then
+    /// replaced by a new buffer. Returns the dimensions of the resulting image.
+    ///
+    /// # Arguments
+    /// * `width` - Image width in pixels
+    /// * `height` - Image height in pixels
+    /// * `format` - Pixel format of `data`
+    /// * `data` - Pixel data (must be width * height * format.bytes_per_pixel() bytes)
+    /// * `params` - JSON parameters string to pass to the plugin
+    pub fn process(
+        &self,
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+        data: &mut Vec<u8>,
+        params: &str,
+    ) -> Result<(u32, u32)> {
+        debug!(
+            "Plugin::process called for {} with dimensions={}x{}, format={}, params={}",
+            self.path.display(),
+            width,
+            height,
+            format,
+            params
+        );
+
+        // Validate buffer size before FFI call with checked arithmetic
+        let expected_len = format
+            .buffer_len(width, height)
+            .ok_or_else(|| anyhow::anyhow!("Image dimensions overflow"))?;
+        debug_assert_eq!(
+            data.len(),
+            expected_len,
+            "Buffer size mismatch: expected {} bytes for {}x{} {} image, got {}",
+            expected_len,
+            width,
+            height,
+            format,
+            data.len()
+        );
+
+        let c_params = self.prepare_call(format, params)?;
+        let plugin_name = self.name.as_str();
+
+        if let Some(transform) = &self.exports.transform {
+            let (mut out_width, mut out_height) = (0u32, 0u32);
+            // SAFETY: c_params is a valid null-terminated CString and both out pointers refer
+            // to local u32s that outlive the call.
+            let result = unsafe {
+                (transform.output_dimensions)(
+                    width,
+                    height,
+                    c_params.as_ptr(),
+                    &mut out_width,
+                    &mut out_height,
+                )
+            };
+            if result != 0 {
+                return Err(self.failure(result));
+            }
+
+            if out_width == 0 || out_height == 0 {
+                anyhow::bail!(
+                    "Plugin {} reported invalid output dimensions {}x{}",
+                    plugin_name,
+                    out_width,
+                    out_height
+                );
+            }
+            let out_len = format.buffer_len(out_width, out_height).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Plugin {} reported output dimensions {}x{} that overflow",
+                    plugin_name,
+                    out_width,
+                    out_height
+                )
+            })?;
+            debug!(
+                "Plugin {} produces a {}x{} image from {}x{}",
+                plugin_name, out_width, out_height, width, height
+            );
+
+            let mut output = vec![0u8; out_len];
+            let result = if format == PixelFormat::Rgba8 {
+                // SAFETY: data holds width*height*4 bytes, output is a separate buffer of
+                // out_width*out_height*4 bytes matching the size the plugin reported, c_params
+                // is a valid null-terminated CString, and the library remains loaded for the
+                // call.
+                unsafe {
+                    (transform.transform_image)(
+                        width,
+                        height,
+                        data.as_ptr(),
+                        out_width,
+                        out_height,
+                        output.as_mut_ptr(),
+                        c_params.as_ptr(),
+                    )
+                }
+            } else {
+                let transform_pixels_fn = transform
+                    .transform_pixels
+                    .expect("checked on load for plugins that accept other formats");
+                // SAFETY: As above, with both buffers sized for `format`, which the plugin
+                // accepts.
+                unsafe {
+                    transform_pixels_fn(
+                        width,
+                        height,
+                        format.code(),
+                        data.as_ptr(),
+                        out_width,
+                        out_height,
+                        output.as_mut_ptr(),
+                        c_params.as_ptr(),
+                    )
+                }
+            };
+            if result != 0 {
+                return Err(self.failure(result));
+            }
+
+            *data = output;
+            info!("Plugin execution complete");
+            return Ok((out_width, out_height));
+        }
+
+        let stride = width as usize * format.bytes_per_pixel();
+        self.call_in_place(
+            &mut PixelsViewMut::new(width, height, format, stride, data)?,
+            &c_params,
+        )?;
+
+        info!("Plugin execution complete");
+
+        Ok((width, height))
+    }
+
+    /// Runs an in-place plugin on `view`, through `process_strided` if the plugin exports it.
+    fn call_in_place(&self, view: &mut PixelsViewMut<'_>, c_params: &CString) -> Result<()> {
+        if let Some(process_strided_fn) = self.exports.process_strided {
+            let (width, height, format, stride) =
+                (view.width, view.height, view.format, view.stride);
+            // SAFETY: PixelsViewMut guarantees that its data holds height rows of width
+            // pixels of `format`, stride bytes apart; `format` was checked against the
+            // plugin's formats, c_params is a valid null-terminated CString, and the
+            // library remains loaded for the duration of this call.
+            let result = unsafe {
+                process_strided_fn(
+                    width,
+                    height,
+                    format.code(),
+                    stride,
+                    view.data_mut().as_mut_ptr(),
+                    c_params.as_ptr(),
+                )
+            };
+            if result != 0 {
+                return Err(self.failure(result));
+            }
+            return Ok(());
+        }
 
-    let c_params = CString::new(params).with_context(|| "Invalid params string")?;
+        if view.is_packed() {
+            let (width, height, format) = (view.width, view.height, view.format);
+            return self.call_packed(width, height, format, view.data_mut(), c_params);
+        }
 
-    // SAFETY: The rgba_data buffer is validated above to be width*height*4 bytes, c_params is
-    // a valid null-terminated CString, and the library remains loaded for the duration of
-    // this call. If the plugin writes beyond the buffer bounds or panics, this would cause
-    // undefined behavior.
-    let result =
-        unsafe { process_image_fn(width, height, rgba_data.as_mut_ptr(), c_params.as_ptr()) };
+        debug!(
+            "Plugin {} does not export process_strided; copying the {}x{} view",
+            self.name, view.width, view.height
+        );
+        let mut packed = view.to_packed();
+        self.call_packed(view.width, view.height, view.format, &mut packed, c_params)?;
+        view.copy_from_packed(&packed);
+        Ok(())
+    }
+
+    /// Runs an in-place plugin on a tightly packed buffer of `width * height` pixels of `format`.
+    /// `data` comes from a packed [`PixelsViewMut`] or its copy, so it has exactly that length.
+    fn call_packed(
+        &self,
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+        data: &mut [u8],
+        c_params: &CString,
+    ) -> Result<()> {
+        let result = if format == PixelFormat::Rgba8 {
+            let process_image_fn = self
+                .exports
+                .process_image
+                .expect("checked on load for in-place plugins");
+
+            // SAFETY: The data buffer holds exactly width*height*4 bytes, c_params
+            // is a valid null-terminated CString, and the library remains loaded for the
+            // duration of this call. If the plugin writes beyond the buffer bounds or panics,
+            // this would cause undefined behavior.
+            unsafe { process_image_fn(width, height, data.as_mut_ptr(), c_params.as_ptr()) }
+        } else {
+            let process_pixels_fn = self
+                .exports
+                .process_pixels
+                .expect("checked on load for plugins that accept other formats");
+
+            // SAFETY: The data buffer holds exactly width*height pixels of `format`; the rest
+            // is as for process_image.
+            unsafe {
+                process_pixels_fn(
+                    width,
+                    height,
+                    format.code(),
+                    data.as_mut_ptr(),
+                    c_params.as_ptr(),
+                )
+            }
+        };
 
-    if result != 0 {
-        anyhow::bail!("Plugin returned error code: {}", result);
+        if result != 0 {
+            return Err(self.failure(result));
+        }
+        Ok(())
     }
 
-    info!("Plugin execution complete");
+    /// Builds the error reported when a plugin call returns a non-zero code.
+    fn failure(&self, code: i32) -> anyhow::Error {
+        let description = describe_error_code(code);
+        match self.last_error() {
+            Some(reason) => anyhow::anyhow!(
+                "Plugin {} failed: {} (error code {}: {})",
+                self.name,
+                reason,
+                code,
+                description
+            ),
+            None => anyhow::anyhow!(
+                "Plugin {} returned error code: {} ({})",
+                self.name,
+                code,
+                description
+            ),
+        }
+    }
 
-    Ok(())
+    /// Reads the plugin's description of its last failure on this thread. Returns `None` when
+    /// the plugin does not export `plugin_last_error` or did not record a message.
+    fn last_error(&self) -> Option<String> {
+        let last_error_fn = self.exports.last_error?;
+        // SAFETY: plugin_last_error returns null or a pointer to a thread-local C string that
+        // stays valid until the next plugin call on this thread; we copy it right away.
+        let message = unsafe { string_from_plugin(last_error_fn()) };
+        (!message.is_empty()).then_some(message)
+    }
+}
+
+/// Installs `callback` through a plugin's `plugin_set_*_callback` export until the returned
+/// guard is dropped. Returns `None` for plugins without the export.
+///
+/// # Safety
+///
+/// The plugin's library must stay loaded, and `context` valid for `callback`, for as long as
+/// the guard lives.
+unsafe fn install_callback<C>(
+    set_callback_fn: Option<SetCallbackFn<C>>,
+    callback: C,
+    context: *mut c_void,
+) -> Option<CallbackGuard<C>> {
+    let set_callback_fn = set_callback_fn?;
+    // SAFETY: The caller keeps context valid while the guard lives; the guard removes the
+    // callback again when dropped, even on panic.
+    unsafe { set_callback_fn(Some(callback), context) };
+    Some(CallbackGuard(set_callback_fn))
+}
+
+/// Removes a callback installed by [`install_callback`] when dropped.
+struct CallbackGuard<C>(SetCallbackFn<C>);
+
+impl<C> Drop for CallbackGuard<C> {
+    fn drop(&mut self) {
+        // SAFETY: A null callback takes no pointers; it only clears the plugin's thread-local.
+        unsafe { (self.0)(None, std::ptr::null_mut()) };
+    }
+}
+
+/// Progress callback handed to plugins. `context` points to the `report` closure of
+/// [`Plugin::with_progress`]; fractions outside 0.0 to 1.0 are clamped and NaN is ignored.
+unsafe extern "C" fn forward_progress(context: *mut c_void, fraction: f32) {
+    if context.is_null() || fraction.is_nan() {
+        return;
+    }
+    // SAFETY: with_progress installs this callback with a pointer to its `report` argument
+    // and removes it before `report` goes out of scope.
+    let report = unsafe { &*context.cast::<&dyn Fn(f32)>() };
+    report(fraction.clamp(0.0, 1.0));
+}
+
+/// Cancellation callback handed to plugins. `context` points to the `cancelled` closure of
+/// [`Plugin::with_cancel`].
+unsafe extern "C" fn forward_cancel(context: *mut c_void) -> bool {
+    if context.is_null() {
+        return false;
+    }
+    // SAFETY: with_cancel installs this callback with a pointer to its `cancelled` argument
+    // and removes it before `cancelled` goes out of scope.
+    let cancelled = unsafe { &*context.cast::<&dyn Fn() -> bool>() };
+    cancelled()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Loads a plugin and processes RGBA8 data with it, for one-off calls.
+    fn process(
+        plugin_path: &Path,
+        width: u32,
+        height: u32,
+        rgba_data: &mut Vec<u8>,
+        params: &str,
+    ) -> Result<(u32, u32)> {
+        Plugin::load(plugin_path)?.process(width, height, PixelFormat::Rgba8, rgba_data, params)
+    }
+
     #[test]
     fn test_library_filename_current_platform() {
         let name = library_filename("mirror_plugin");
@@ -109,6 +1183,66 @@ mod tests {
         assert_eq!(name, "mirror_plugin.dll");
     }
 
+    #[test]
+    fn test_plugin_name_from_filename_inverts_library_filename() {
+        let file_name = library_filename("blur_plugin");
+        assert_eq!(plugin_name_from_filename(&file_name), Some("blur_plugin"));
+
+        assert_eq!(plugin_name_from_filename(&library_filename("")), None);
+        assert_eq!(plugin_name_from_filename("blur_plugin.d"), None);
+        assert_eq!(plugin_name_from_filename("README.md"), None);
+    }
+
+    #[test]
+    fn test_describe_error_code_well_known_codes() {
+        assert_eq!(describe_error_code(-1), "invalid parameters");
+        assert_eq!(describe_error_code(-2), "image size overflow");
+        assert_eq!(describe_error_code(-3), "parameter value out of range");
+        assert_eq!(describe_error_code(-4), "plugin panicked");
+        assert_eq!(describe_error_code(-5), "unsupported pixel format");
+        assert_eq!(describe_error_code(-6), "invalid row stride");
+        assert_eq!(describe_error_code(-7), "cancelled");
+    }
+
+    #[test]
+    fn test_describe_error_code_unknown_and_plugin_specific() {
+        assert_eq!(describe_error_code(-42), "unknown error");
+        assert_eq!(describe_error_code(7), "unknown error");
+        assert_eq!(describe_error_code(-1000), "plugin-specific error");
+        assert_eq!(describe_error_code(-5000), "plugin-specific error");
+    }
+
+    #[test]
+    fn test_check_abi_version_accepts_supported_range() {
+        for version in MIN_SUPPORTED_ABI_VERSION..=HOST_ABI_VERSION {
+            assert!(check_abi_version(version).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_check_abi_version_rejects_newer_plugin() {
+        let err = check_abi_version(HOST_ABI_VERSION + 1)
+            .unwrap_err()
+            .to_string();
+
+        assert!(
+            err.contains(&format!("plugin built for ABI v{}", HOST_ABI_VERSION + 1)),
+            "unexpected error: {}",
+            err
+        );
+        assert!(err.contains(&format!("host supports v{}", MIN_SUPPORTED_ABI_VERSION)));
+    }
+
+    #[test]
+    fn test_check_abi_version_rejects_version_zero() {
+        let err = check_abi_version(0).unwrap_err().to_string();
+        assert!(
+            err.contains("plugin built for ABI v0"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
     #[test]
     fn test_process_missing_library_returns_error() {
         let lib_name = library_filename("nonexistent_plugin");
@@ -121,6 +1255,282 @@ mod tests {
         assert!(err.contains("Failed to load plugin library"));
     }
 
+    #[test]
+    fn test_info_missing_library_returns_error() {
+        let path = std::path::PathBuf::from("/nonexistent/path").join(library_filename("x"));
+        let err = info(&path).unwrap_err().to_string();
+        assert!(err.contains("Failed to load plugin library"));
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_info_with_real_plugin() {
+        let plugin_path =
+            std::path::PathBuf::from("../target/debug").join(library_filename("blur_plugin"));
+
+        let plugin_info = info(&plugin_path).expect("blur_plugin should export plugin_info");
+
+        assert_eq!(plugin_info.name, "blur_plugin");
+        assert!(!plugin_info.version.is_empty());
+        assert_eq!(plugin_info.operations, vec!["blur".to_string()]);
+        assert_eq!(plugin_info.abi_version, HOST_ABI_VERSION);
+
+        let schema = plugin_info
+            .params_schema
+            .expect("blur_plugin should export a parameter schema");
+        assert!(schema.property("radius").is_some());
+        assert!(schema.property("iterations").is_some());
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_apply_processes_rgba_image() {
+        let plugin_path =
+            std::path::PathBuf::from("../target/debug").join(library_filename("mirror_plugin"));
+        let mirror = Plugin::load(&plugin_path).expect("mirror_plugin should load");
+        let mut image = RgbaImage::from_fn(3, 2, |x, y| image::Rgba([x as u8, y as u8, 7, 255]));
+        let expected = RgbaImage::from_fn(3, 2, |x, y| image::Rgba([2 - x as u8, y as u8, 7, 255]));
+
+        mirror
+            .apply(&mut image, r#"{"horizontal": true}"#)
+            .expect("mirror should succeed");
+        assert_eq!(image, expected);
+
+        mirror
+            .apply(&mut image, "not json")
+            .expect_err("invalid params");
+        assert_eq!(image, expected, "a failed call leaves the image as it was");
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_registry_loads_each_plugin_once() {
+        let plugin_path =
+            std::path::PathBuf::from("../target/debug").join(library_filename("mirror_plugin"));
+        let mut registry = PluginRegistry::default();
+        assert!(registry.get("mirror_plugin").is_none());
+
+        let first: *const Plugin = registry
+            .load("mirror_plugin", &plugin_path)
+            .expect("mirror_plugin should load");
+        // A registered plugin is returned without opening the library again
+        let again: *const Plugin = registry
+            .load("mirror_plugin", Path::new("/nonexistent/library"))
+            .expect("mirror_plugin is registered");
+        assert_eq!(first, again);
+        assert!(registry.get("mirror_plugin").is_some());
+        assert!(
+            registry
+                .load("other_plugin", Path::new("/nonexistent/library"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_process_pixels_falls_back_to_rgba8() {
+        let plugin_dir = std::path::PathBuf::from("../target/debug");
+        let mirror = Plugin::load(&plugin_dir.join(library_filename("mirror_plugin")))
+            .expect("mirror_plugin should load");
+        let panic_plugin = Plugin::load(&plugin_dir.join(library_filename("panic_plugin")))
+            .expect("panic_plugin should load");
+        assert_eq!(mirror.pixel_formats, FormatSet::from_mask(0b11_1111));
+        assert_eq!(panic_plugin.pixel_formats, FormatSet::RGBA8_ONLY);
+
+        // 2x1 RGBA16 image: black, white
+        let mut pixels = Pixels::new(2, 1, PixelFormat::Rgba16, vec![0; 16]).expect("valid");
+        pixels.data[8..].fill(0xFF);
+        let original = pixels.clone();
+
+        mirror
+            .process_pixels(&mut pixels, r#"{"horizontal": true}"#)
+            .expect("mirror should accept RGBA16");
+        assert_eq!(pixels.format, PixelFormat::Rgba16);
+        assert_eq!(pixels.data[..8], original.data[8..]);
+
+        // panic_plugin only takes RGBA8, so the pixels are converted before it panics
+        let err = panic_plugin
+            .process_pixels(&mut pixels, r#"{"mode": "panic"}"#)
+            .expect_err("panic_plugin always fails");
+        assert!(err.to_string().contains("panicked"), "got: {}", err);
+        assert_eq!(pixels.format, PixelFormat::Rgba8);
+        assert_eq!(pixels.data, vec![255, 255, 255, 255, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_process_view_keeps_bytes_between_rows() {
+        let plugin_dir = std::path::PathBuf::from("../target/debug");
+        // 2x2 L8 view in rows of 3 bytes; the third byte of each row is not part of it
+        let expected = vec![4, 3, 99, 2, 1];
+
+        // mirror_plugin exports process_strided and works on the view directly
+        let mirror = Plugin::load(&plugin_dir.join(library_filename("mirror_plugin")))
+            .expect("mirror_plugin should load");
+        let mut buffer = vec![1, 2, 99, 3, 4];
+        let mut view =
+            PixelsViewMut::new(2, 2, PixelFormat::L8, 3, &mut buffer).expect("valid view");
+        mirror
+            .process_view(&mut view, r#"{"horizontal": true, "vertical": true}"#)
+            .expect("mirror should succeed");
+        assert_eq!(buffer, expected);
+
+        // panic_plugin lacks process_strided, so the host copies the rows; on failure nothing
+        // is written back
+        let panic_plugin = Plugin::load(&plugin_dir.join(library_filename("panic_plugin")))
+            .expect("panic_plugin should load");
+        let mut buffer = vec![7u8; 10];
+        let mut view =
+            PixelsViewMut::new(1, 2, PixelFormat::Rgba8, 6, &mut buffer).expect("valid view");
+        let err = panic_plugin
+            .process_view(&mut view, r#"{"mode": "panic"}"#)
+            .expect_err("panic_plugin always fails");
+        assert!(err.to_string().contains("panicked"), "got: {}", err);
+        assert_eq!(buffer, vec![7u8; 10]);
+
+        // Size-changing plugins have no place to put a different-sized result
+        let rotate = Plugin::load(&plugin_dir.join(library_filename("rotate_plugin")))
+            .expect("rotate_plugin should load");
+        let mut buffer = vec![1, 2, 99, 3, 4];
+        let mut view =
+            PixelsViewMut::new(2, 2, PixelFormat::L8, 3, &mut buffer).expect("valid view");
+        let err = rotate
+            .process_view(&mut view, r#"{"angle": 90}"#)
+            .expect_err("rotate changes the size");
+        assert!(
+            err.to_string().contains("cannot process part"),
+            "got: {}",
+            err
+        );
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_halo_only_for_tiling_plugins() {
+        let plugin_dir = std::path::PathBuf::from("../target/debug");
+        let blur = Plugin::load(&plugin_dir.join(library_filename("blur_plugin")))
+            .expect("blur_plugin should load");
+        let mirror = Plugin::load(&plugin_dir.join(library_filename("mirror_plugin")))
+            .expect("mirror_plugin should load");
+
+        assert_eq!(
+            blur.halo(r#"{"radius": 2, "iterations": 3}"#)
+                .expect("valid params"),
+            Some(6)
+        );
+        assert!(blur.halo(r#"{"radus": 2}"#).is_err());
+        // Flipping moves pixels across the whole image, so mirror cannot work on tiles
+        assert_eq!(mirror.halo("{}").expect("valid params"), None);
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_load_resolves_exports_once() {
+        let plugin_dir = std::path::PathBuf::from("../target/debug");
+        let load = |name| {
+            Plugin::load(&plugin_dir.join(library_filename(name))).expect("plugin should load")
+        };
+
+        let blur = load("blur_plugin").exports;
+        assert!(blur.process_image.is_some() && blur.process_pixels.is_some());
+        assert!(blur.process_strided.is_some() && blur.halo.is_some());
+        assert!(blur.set_progress_callback.is_some() && blur.set_cancel_callback.is_some());
+        assert!(blur.transform.is_none());
+
+        let rotate = load("rotate_plugin").exports;
+        let transform = rotate.transform.expect("rotate changes the image size");
+        assert!(transform.transform_pixels.is_some());
+        assert!(rotate.halo.is_none() && rotate.set_progress_callback.is_none());
+
+        let panic = load("panic_plugin").exports;
+        assert!(panic.process_pixels.is_none() && panic.last_error.is_some());
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_with_progress_forwards_plugin_reports() {
+        let plugin_dir = std::path::PathBuf::from("../target/debug");
+        let blur = Plugin::load(&plugin_dir.join(library_filename("blur_plugin")))
+            .expect("blur_plugin should load");
+        let mut pixels = Pixels::new(4, 3, PixelFormat::L8, vec![0; 12]).expect("valid buffer");
+
+        let reports = std::cell::RefCell::new(Vec::new());
+        let report = |fraction| reports.borrow_mut().push(fraction);
+        blur.with_progress(&report, || {
+            blur.process_pixels(&mut pixels, r#"{"radius": 1, "iterations": 2}"#)
+        })
+        .expect("blur should succeed");
+
+        let reports = reports.into_inner();
+        assert!(!reports.is_empty(), "blur should report progress");
+        assert!(reports.is_sorted(), "got: {:?}", reports);
+        assert_eq!(reports.last(), Some(&1.0));
+
+        // The callback was removed, so a later call must not reach the dropped closure
+        blur.process_pixels(&mut pixels, "{}")
+            .expect("blur should succeed");
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_with_cancel_stops_plugin_and_keeps_pixels() {
+        let plugin_dir = std::path::PathBuf::from("../target/debug");
+        let blur = Plugin::load(&plugin_dir.join(library_filename("blur_plugin")))
+            .expect("blur_plugin should load");
+        let data: Vec<u8> = (0..12).map(|value| value * 20).collect();
+        let mut pixels = Pixels::new(4, 3, PixelFormat::L8, data.clone()).expect("valid buffer");
+
+        let err = blur
+            .with_cancel(&|| true, || blur.process_pixels(&mut pixels, "{}"))
+            .expect_err("a cancelled blur must fail");
+        assert!(format!("{:#}", err).contains("cancelled"), "got: {:#}", err);
+        assert_eq!(pixels.data, data);
+
+        // Without the callback the blur runs to completion
+        blur.process_pixels(&mut pixels, "{}")
+            .expect("blur should succeed");
+        assert_ne!(pixels.data, data);
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_process_rejects_unknown_param_before_calling_plugin() {
+        let plugin_path =
+            std::path::PathBuf::from("../target/debug").join(library_filename("blur_plugin"));
+        let mut data = vec![0u8, 0, 0, 255, 255, 255, 255, 255];
+        let original = data.clone();
+
+        let result = process(&plugin_path, 2, 1, &mut data, r#"{"radus": 2}"#);
+
+        let err = format!("{:#}", result.unwrap_err());
+        assert!(err.contains("unknown parameter 'radus'"), "got: {}", err);
+        assert_eq!(
+            data, original,
+            "image must not be touched on invalid params"
+        );
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_process_survives_panicking_plugin() {
+        let plugin_path =
+            std::path::PathBuf::from("../target/debug").join(library_filename("panic_plugin"));
+        let mut data = vec![10u8, 20, 30, 255];
+
+        let result = process(&plugin_path, 1, 1, &mut data, "{}");
+
+        let err = result
+            .expect_err("a panicking plugin must be reported as an error")
+            .to_string();
+        assert!(err.contains("panicked: deliberate panic"), "got: {}", err);
+        assert!(
+            err.contains("error code -4: plugin panicked"),
+            "got: {}",
+            err
+        );
+        assert_eq!(data, vec![10u8, 20, 30, 255]);
+    }
+
     #[test]
     #[ignore] // Run with: cargo test -p image_processor -- --ignored
     fn test_process_invalid_params_with_null_byte() {
@@ -160,4 +1570,34 @@ mod tests {
         // Check the plugin executed without error
         assert!(result.is_ok(), "Expected success, got: {:?}", result);
     }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_process_with_dimension_changing_plugin() {
+        let plugin_path =
+            std::path::PathBuf::from("../target/debug").join(library_filename("rotate_plugin"));
+        // 2x1 image: red, green
+        let mut data = vec![255, 0, 0, 255, 0, 255, 0, 255];
+
+        let dimensions = process(&plugin_path, 2, 1, &mut data, r#"{"angle": 270}"#)
+            .expect("rotate_plugin should succeed");
+
+        // Counter-clockwise quarter turn: green ends up on top.
+        assert_eq!(dimensions, (1, 2));
+        assert_eq!(data, vec![0, 255, 0, 255, 255, 0, 0, 255]);
+    }
+
+    #[test]
+    #[ignore] // Run with: cargo test -p image_processor -- --ignored
+    fn test_process_in_place_plugin_keeps_dimensions() {
+        let plugin_path =
+            std::path::PathBuf::from("../target/debug").join(library_filename("mirror_plugin"));
+        let mut data = vec![255, 0, 0, 255, 0, 255, 0, 255];
+
+        let dimensions = process(&plugin_path, 2, 1, &mut data, r#"{"horizontal": true}"#)
+            .expect("mirror_plugin should succeed");
+
+        assert_eq!(dimensions, (2, 1));
+        assert_eq!(data, vec![0, 255, 0, 255, 255, 0, 0, 255]);
+    }
 }
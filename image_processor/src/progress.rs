@@ -0,0 +1,118 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// How long a call runs before its progress bar appears.
+const BAR_DELAY: Duration = Duration::from_millis(500);
+
+/// Minimum time between two redraws of the progress bar.
+const BAR_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time between progress lines when stderr is not a terminal.
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Width of the progress bar between its brackets, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// Cleared by [`use_lines`]; bars of calls on several threads would overwrite each other on
+/// the one terminal line.
+static BARS: AtomicBool = AtomicBool::new(true);
+
+/// Shows progress as lines every few seconds even on a terminal, for parallel jobs.
+pub fn use_lines() {
+    BARS.store(false, Ordering::Relaxed);
+}
+
+/// Shows the progress a plugin reports during one call: a bar on stderr when it is a
+/// terminal (unless [`use_lines`] was called), otherwise a line on stderr every few seconds. Both show at any log level; calls
+/// that finish quickly show nothing, and the bar is erased when the `Progress` is dropped.
+pub struct Progress {
+    label: String,
+    terminal: bool,
+    state: Mutex<State>,
+}
+
+struct State {
+    /// Earliest time the next report may be shown.
+    next_update: Instant,
+    /// Whether a bar is on screen and must be erased.
+    drawn: bool,
+}
+
+impl Progress {
+    pub fn new(label: String) -> Self {
+        let terminal = BARS.load(Ordering::Relaxed) && io::stderr().is_terminal();
+        let delay = if terminal { BAR_DELAY } else { LOG_INTERVAL };
+        Self {
+            label,
+            terminal,
+            state: Mutex::new(State {
+                next_update: Instant::now() + delay,
+                drawn: false,
+            }),
+        }
+    }
+
+    /// Records that the call is `fraction` (0.0 to 1.0) complete.
+    pub fn report(&self, fraction: f32) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if now < state.next_update {
+            return;
+        }
+
+        if self.terminal {
+            // A progress bar that cannot be drawn is not worth failing the run for
+            let _ = write!(io::stderr(), "\r{}", bar(&self.label, fraction));
+            state.drawn = true;
+            state.next_update = now + BAR_INTERVAL;
+        } else {
+            let _ = writeln!(
+                io::stderr(),
+                "{}: {:.0}% done",
+                self.label,
+                fraction * 100.0
+            );
+            state.next_update = now + LOG_INTERVAL;
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        let state = self.state.get_mut().unwrap_or_else(PoisonError::into_inner);
+        if state.drawn {
+            // Erase the bar so that later output starts on a clean line
+            let _ = write!(io::stderr(), "\r\x1b[2K");
+        }
+    }
+}
+
+/// Formats `label [=======       ]  50%` for a fraction from 0.0 to 1.0.
+fn bar(label: &str, fraction: f32) -> String {
+    let fraction = fraction.clamp(0.0, 1.0);
+    let filled = (fraction * BAR_WIDTH as f32).round() as usize;
+    format!(
+        "{} [{}{}] {:>3.0}%",
+        label,
+        "=".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled),
+        fraction * 100.0
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bar_fills_with_fraction() {
+        assert_eq!(
+            bar("blur_plugin", 0.5),
+            format!("blur_plugin [{}{}]  50%", "=".repeat(15), " ".repeat(15))
+        );
+        assert_eq!(bar("x", 0.0), format!("x [{}]   0%", " ".repeat(BAR_WIDTH)));
+        assert_eq!(bar("x", 1.5), format!("x [{}] 100%", "=".repeat(BAR_WIDTH)));
+    }
+}
@@ -0,0 +1,464 @@
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use log::{LevelFilter, debug, info};
+
+use crate::cancel::{self, Cancel};
+use crate::pixels::{PixelFormat, Pixels};
+use crate::plugin_loader::Plugin;
+
+/// Reply tag written by the worker before the payload.
+const RESPONSE_OK: u8 = 0;
+const RESPONSE_ERROR: u8 = 1;
+/// Tag of a progress report (an `f32` fraction) the worker may send before its reply.
+const RESPONSE_PROGRESS: u8 = 2;
+
+/// How often the host checks whether a running worker should be cancelled.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Limits enforced on an isolated plugin run.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WorkerLimits {
+    /// Address-space limit applied by the worker to itself (Unix only).
+    pub memory_limit_mb: Option<u64>,
+}
+
+/// Runs a plugin in a child `image_processor worker` process.
+///
+/// Pixels and params travel over the worker's stdin/stdout pipes; the worker's stderr is
+/// inherited so plugin logs stay visible. A worker still running when `cancel` trips is
+/// killed. Crashes, signals and cancellation are reported as ordinary errors and leave
+/// `pixels` untouched. On success `pixels` holds the resulting image, in the format the
+/// worker negotiated with the plugin, like `Plugin::process_pixels`. The progress the plugin
+/// reports is passed on to `report`.
+pub fn process(
+    plugin_path: &Path,
+    pixels: &mut Pixels,
+    params: &str,
+    limits: WorkerLimits,
+    cancel: &Cancel,
+    report: &(dyn Fn(f32) + Sync),
+) -> Result<()> {
+    info!(
+        "Running plugin {} in an isolated worker process",
+        plugin_path.display()
+    );
+
+    let executable =
+        std::env::current_exe().context("Failed to locate the image_processor executable")?;
+    let mut command = Command::new(executable);
+    command
+        .arg("worker")
+        .arg("--library")
+        .arg(plugin_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit());
+    // The worker logs, and lets the plugin log, at the level -v gave this process
+    match log::max_level() {
+        LevelFilter::Off | LevelFilter::Error | LevelFilter::Warn => {}
+        LevelFilter::Info => {
+            command.arg("-v");
+        }
+        LevelFilter::Debug | LevelFilter::Trace => {
+            command.arg("-vv");
+        }
+    }
+    if let Some(memory_limit_mb) = limits.memory_limit_mb {
+        command
+            .arg("--memory-limit")
+            .arg(memory_limit_mb.to_string());
+    }
+
+    let mut child = command
+        .spawn()
+        .context("Failed to spawn plugin worker process")?;
+    debug!("Spawned plugin worker with pid {}", child.id());
+
+    let mut stdin = child.stdin.take().expect("worker stdin is piped");
+    let stdout = child.stdout.take().expect("worker stdout is piped");
+    let input: &Pixels = pixels;
+
+    let (reply, status) = thread::scope(|scope| {
+        // Write errors are ignored: a worker that dies early closes the pipe, and its exit
+        // status below explains why far better than EPIPE would.
+        scope.spawn(move || {
+            let _ = write_request(&mut stdin, params, input);
+        });
+
+        let (sender, receiver) = mpsc::channel();
+        scope.spawn(move || {
+            let _ = sender.send(read_response(&mut io::BufReader::new(stdout), report));
+        });
+
+        let reply = loop {
+            match receiver.recv_timeout(CANCEL_POLL_INTERVAL) {
+                Ok(reply) => break Some(reply),
+                Err(mpsc::RecvTimeoutError::Timeout) if !cancel.is_cancelled() => {}
+                Err(_) => break None,
+            }
+        };
+        if reply.is_none() {
+            // Killing closes the worker's pipes, which lets both helper threads finish.
+            let _ = child.kill();
+        }
+        (reply, child.wait())
+    });
+
+    let status = status.context("Failed to wait for plugin worker process")?;
+    let Some(reply) = reply else {
+        anyhow::bail!(
+            "Plugin worker {} and was killed",
+            cancel
+                .reason()
+                .unwrap_or_else(|| "stopped replying".to_string())
+        );
+    };
+
+    if !status.success() {
+        let hint = match limits.memory_limit_mb {
+            Some(limit) => format!(
+                "; the plugin crashed or exceeded the memory limit of {} MiB",
+                limit
+            ),
+            None => "; the plugin crashed".to_string(),
+        };
+        anyhow::bail!("Plugin worker {}{}", describe_exit(status), hint);
+    }
+
+    *pixels = match (reply?, cancel.reason()) {
+        (Ok(processed), _) => processed,
+        (Err(message), Some(reason)) => anyhow::bail!("Plugin worker {}: {}", reason, message),
+        (Err(message), None) => anyhow::bail!("{}", message),
+    };
+
+    info!("Isolated plugin execution complete");
+
+    Ok(())
+}
+
+/// Entry point of the hidden `worker` subcommand: reads one request from stdin, runs the
+/// plugin in this process and writes the reply to stdout.
+pub fn run_worker(library: &Path, memory_limit_mb: Option<u64>) -> Result<()> {
+    if let Some(memory_limit_mb) = memory_limit_mb {
+        limit_address_space(memory_limit_mb)?;
+    }
+
+    // Ctrl-C reaches the worker too; let the plugin stop cleanly instead of dying mid-reply
+    cancel::handle_interrupts()?;
+    let (params, mut pixels) = read_request(&mut io::stdin().lock())?;
+    debug!(
+        "Worker received {}x{} {} image ({} bytes)",
+        pixels.width,
+        pixels.height,
+        pixels.format,
+        pixels.data.len()
+    );
+
+    // A host that went away will not read the reply either; the write below reports it
+    let report = |fraction| {
+        let _ = write_progress(&mut io::stdout().lock(), fraction);
+    };
+    // The host enforces the timeout by killing this process
+    let cancel = Cancel::new(None);
+    let response = match Plugin::load(library).and_then(|plugin| {
+        plugin.with_cancel(&|| cancel.is_cancelled(), || {
+            plugin.with_progress(&report, || plugin.process_pixels(&mut pixels, &params))
+        })
+    }) {
+        Ok(()) => Ok(pixels),
+        Err(err) => Err(format!("{:#}", err)),
+    };
+
+    let mut stdout = io::stdout().lock();
+    write_response(&mut stdout, &response).context("Failed to write reply to host")?;
+    stdout.flush().context("Failed to write reply to host")?;
+
+    Ok(())
+}
+
+/// Describes how a worker process ended, including the signal name on Unix.
+fn describe_exit(status: ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("was killed by signal {} ({})", signal, signal_name(signal));
+        }
+    }
+
+    match status.code() {
+        Some(code) => format!("exited with status {}", code),
+        None => "terminated abnormally".to_string(),
+    }
+}
+
+#[cfg(unix)]
+fn signal_name(signal: i32) -> &'static str {
+    match signal {
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGILL => "SIGILL",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGTERM => "SIGTERM",
+        _ => "unknown signal",
+    }
+}
+
+/// Caps the worker's address space so a runaway plugin fails instead of exhausting the host.
+#[cfg(unix)]
+fn limit_address_space(megabytes: u64) -> Result<()> {
+    let bytes = megabytes.saturating_mul(1024 * 1024) as libc::rlim_t;
+    let limit = libc::rlimit {
+        rlim_cur: bytes,
+        rlim_max: bytes,
+    };
+
+    // SAFETY: setrlimit only reads the rlimit struct passed by reference, which lives for the
+    // duration of the call. It affects this worker process alone.
+    let result = unsafe { libc::setrlimit(libc::RLIMIT_AS, &limit) };
+    if result != 0 {
+        return Err(io::Error::last_os_error())
+            .with_context(|| format!("Failed to apply memory limit of {} MiB", megabytes));
+    }
+
+    debug!("Worker address space limited to {} MiB", megabytes);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn limit_address_space(_megabytes: u64) -> Result<()> {
+    log::warn!("Memory limits are not supported on this platform; running without one");
+    Ok(())
+}
+
+fn write_request(writer: &mut impl Write, params: &str, pixels: &Pixels) -> io::Result<()> {
+    write_bytes(writer, params.as_bytes())?;
+    write_pixels(writer, pixels)?;
+    writer.flush()
+}
+
+fn read_request(reader: &mut impl Read) -> Result<(String, Pixels)> {
+    let params = String::from_utf8(read_bytes(reader)?).context("Params are not valid UTF-8")?;
+    Ok((params, read_pixels(reader)?))
+}
+
+fn write_response(
+    writer: &mut impl Write,
+    response: &std::result::Result<Pixels, String>,
+) -> io::Result<()> {
+    match response {
+        Ok(pixels) => {
+            writer.write_all(&[RESPONSE_OK])?;
+            write_pixels(writer, pixels)
+        }
+        Err(message) => {
+            writer.write_all(&[RESPONSE_ERROR])?;
+            write_bytes(writer, message.as_bytes())
+        }
+    }
+}
+
+fn write_progress(writer: &mut impl Write, fraction: f32) -> io::Result<()> {
+    writer.write_all(&[RESPONSE_PROGRESS])?;
+    writer.write_all(&fraction.to_le_bytes())?;
+    writer.flush()
+}
+
+/// Reads the worker's reply, passing the progress reports that precede it to `report`.
+fn read_response(
+    reader: &mut impl Read,
+    report: &dyn Fn(f32),
+) -> Result<std::result::Result<Pixels, String>> {
+    loop {
+        let mut tag = [0u8; 1];
+        reader
+            .read_exact(&mut tag)
+            .context("Plugin worker sent an empty reply")?;
+        match tag[0] {
+            RESPONSE_OK => return Ok(Ok(read_pixels(reader)?)),
+            RESPONSE_ERROR => {
+                return Ok(Err(
+                    String::from_utf8_lossy(&read_bytes(reader)?).into_owned()
+                ));
+            }
+            RESPONSE_PROGRESS => {
+                let mut fraction = [0u8; 4];
+                reader
+                    .read_exact(&mut fraction)
+                    .context("Plugin worker sent a truncated progress report")?;
+                report(f32::from_le_bytes(fraction));
+            }
+            other => anyhow::bail!("Plugin worker sent an unknown reply tag: {}", other),
+        }
+    }
+}
+
+/// Writes width, height and format code (u32 little-endian each) followed by the pixel bytes.
+fn write_pixels(writer: &mut impl Write, pixels: &Pixels) -> io::Result<()> {
+    writer.write_all(&pixels.width.to_le_bytes())?;
+    writer.write_all(&pixels.height.to_le_bytes())?;
+    writer.write_all(&pixels.format.code().to_le_bytes())?;
+    write_bytes(writer, &pixels.data)
+}
+
+fn read_pixels(reader: &mut impl Read) -> Result<Pixels> {
+    let width = read_u32(reader)?;
+    let height = read_u32(reader)?;
+    let code = read_u32(reader)?;
+    let format = PixelFormat::from_code(code)
+        .with_context(|| format!("Unknown pixel format code in worker message: {}", code))?;
+    Pixels::new(width, height, format, read_bytes(reader)?)
+        .context("Invalid image in worker message")
+}
+
+/// Writes a length-prefixed (u64 little-endian) byte string.
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_bytes(reader: &mut impl Read) -> Result<Vec<u8>> {
+    let mut len = [0u8; 8];
+    reader
+        .read_exact(&mut len)
+        .context("Truncated worker message")?;
+    let len = usize::try_from(u64::from_le_bytes(len)).context("Worker message too large")?;
+    let mut bytes = Vec::new();
+    reader
+        .take(len as u64)
+        .read_to_end(&mut bytes)
+        .context("Truncated worker message")?;
+    if bytes.len() != len {
+        anyhow::bail!(
+            "Truncated worker message: expected {} bytes, got {}",
+            len,
+            bytes.len()
+        );
+    }
+    Ok(bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    let mut value = [0u8; 4];
+    reader
+        .read_exact(&mut value)
+        .context("Truncated worker message")?;
+    Ok(u32::from_le_bytes(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba8(width: u32, height: u32, data: Vec<u8>) -> Pixels {
+        Pixels::new(width, height, PixelFormat::Rgba8, data).expect("valid image")
+    }
+
+    #[test]
+    fn test_request_roundtrip() {
+        let mut buffer = Vec::new();
+        let pixels = rgba8(2, 1, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        write_request(&mut buffer, r#"{"radius": 2}"#, &pixels).expect("write to Vec cannot fail");
+
+        let (params, received) = read_request(&mut buffer.as_slice()).expect("valid request");
+
+        assert_eq!(params, r#"{"radius": 2}"#);
+        assert_eq!(received, pixels);
+    }
+
+    #[test]
+    fn test_response_roundtrip_ok_and_error() {
+        let mut buffer = Vec::new();
+        let pixels = Pixels::new(1, 2, PixelFormat::Rgba16, vec![9; 16]).expect("valid image");
+        write_response(&mut buffer, &Ok(pixels.clone())).expect("write to Vec cannot fail");
+        assert_eq!(
+            read_response(&mut buffer.as_slice(), &|_| ()).expect("valid reply"),
+            Ok(pixels)
+        );
+
+        let mut buffer = Vec::new();
+        write_response(&mut buffer, &Err("boom".to_string())).expect("write to Vec cannot fail");
+        assert_eq!(
+            read_response(&mut buffer.as_slice(), &|_| ()).expect("valid reply"),
+            Err("boom".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_response_forwards_progress() {
+        let mut buffer = Vec::new();
+        for fraction in [0.25, 1.0] {
+            write_progress(&mut buffer, fraction).expect("write to Vec cannot fail");
+        }
+        write_response(&mut buffer, &Err("boom".to_string())).expect("write to Vec cannot fail");
+
+        let reports = std::cell::RefCell::new(Vec::new());
+        let response = read_response(&mut buffer.as_slice(), &|fraction| {
+            reports.borrow_mut().push(fraction)
+        })
+        .expect("valid reply");
+
+        assert_eq!(response, Err("boom".to_string()));
+        assert_eq!(reports.into_inner(), vec![0.25, 1.0]);
+    }
+
+    #[test]
+    fn test_read_response_rejects_truncated_reply() {
+        let mut buffer = Vec::new();
+        write_response(&mut buffer, &Ok(rgba8(1, 1, vec![1, 2, 3, 4])))
+            .expect("write to Vec cannot fail");
+        buffer.truncate(buffer.len() - 2);
+
+        let err = read_response(&mut buffer.as_slice(), &|_| ())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Truncated worker message"), "got: {}", err);
+    }
+
+    #[test]
+    fn test_read_response_rejects_wrong_pixel_count() {
+        let mut buffer = Vec::new();
+        buffer.push(RESPONSE_OK);
+        for value in [2u32, 2, PixelFormat::Rgba8.code()] {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        write_bytes(&mut buffer, &[0; 4]).expect("write to Vec cannot fail");
+
+        let err = format!(
+            "{:#}",
+            read_response(&mut buffer.as_slice(), &|_| ()).unwrap_err()
+        );
+        assert!(err.contains("Buffer size mismatch"), "got: {}", err);
+    }
+
+    #[test]
+    fn test_read_response_rejects_empty_reply() {
+        let err = read_response(&mut [].as_slice(), &|_| ())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("empty reply"), "got: {}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_describe_exit_reports_signal() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let status = ExitStatus::from_raw(libc::SIGSEGV);
+        assert_eq!(
+            describe_exit(status),
+            format!("was killed by signal {} (SIGSEGV)", libc::SIGSEGV)
+        );
+
+        let status = ExitStatus::from_raw(3 << 8);
+        assert_eq!(describe_exit(status), "exited with status 3");
+    }
+}
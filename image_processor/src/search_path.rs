@@ -0,0 +1,354 @@
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+use serde_json::Value;
+
+use crate::plugin_loader;
+
+/// Environment variable holding extra plugin directories, separated like `PATH`.
+pub const PLUGIN_PATH_ENV: &str = "IMAGE_PROCESSOR_PLUGIN_PATH";
+
+/// Keys accepted at the top level of the user config file.
+const CONFIG_KEYS: [&str; 1] = ["plugin_path"];
+
+/// Where a plugin directory on the search path came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    CommandLine,
+    Environment,
+    ConfigFile(PathBuf),
+    System,
+    Executable,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::CommandLine => write!(f, "--plugin-path"),
+            Source::Environment => write!(f, "{}", PLUGIN_PATH_ENV),
+            Source::ConfigFile(path) => write!(f, "config file {}", path.display()),
+            Source::System => write!(f, "system directory"),
+            Source::Executable => write!(f, "executable directory"),
+        }
+    }
+}
+
+/// One directory on the plugin search path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchDir {
+    pub dir: PathBuf,
+    pub source: Source,
+}
+
+/// Ordered list of directories searched for plugin libraries; the first match wins.
+///
+/// Precedence, highest first: `--plugin-path` (in the order given), the
+/// `IMAGE_PROCESSOR_PLUGIN_PATH` environment variable, the `plugin_path` list of the user
+/// config file, the system plugin directory and finally the directory of the executable.
+#[derive(Debug, Clone, Default)]
+pub struct SearchPath {
+    dirs: Vec<SearchDir>,
+}
+
+impl SearchPath {
+    /// Builds a search path of command-line directories only.
+    pub fn from_dirs(dirs: &[PathBuf]) -> Self {
+        let mut search_path = Self::default();
+        for dir in dirs {
+            search_path.push(dir.clone(), Source::CommandLine);
+        }
+        search_path
+    }
+
+    /// Assembles the search path for this process from `--plugin-path` values, the
+    /// environment, the user config file and the built-in locations.
+    pub fn resolve(command_line: &[PathBuf]) -> Result<Self> {
+        let executable_dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf));
+        Self::assemble(
+            command_line,
+            std::env::var_os(PLUGIN_PATH_ENV).as_deref(),
+            user_config_file().as_deref(),
+            system_plugin_dir(),
+            executable_dir,
+        )
+    }
+
+    fn assemble(
+        command_line: &[PathBuf],
+        environment: Option<&OsStr>,
+        config_file: Option<&Path>,
+        system_dir: Option<PathBuf>,
+        executable_dir: Option<PathBuf>,
+    ) -> Result<Self> {
+        let mut search_path = Self::from_dirs(command_line);
+
+        if let Some(environment) = environment {
+            for dir in std::env::split_paths(environment) {
+                if !dir.as_os_str().is_empty() {
+                    search_path.push(dir, Source::Environment);
+                }
+            }
+        }
+
+        if let Some(config_file) = config_file.filter(|path| path.is_file()) {
+            for dir in read_config(config_file)? {
+                search_path.push(dir, Source::ConfigFile(config_file.to_path_buf()));
+            }
+        }
+
+        if let Some(system_dir) = system_dir {
+            search_path.push(system_dir, Source::System);
+        }
+        if let Some(executable_dir) = executable_dir {
+            search_path.push(executable_dir, Source::Executable);
+        }
+
+        Ok(search_path)
+    }
+
+    /// Appends `dir` unless it is already on the path.
+    fn push(&mut self, dir: PathBuf, source: Source) {
+        if !self.dirs.iter().any(|existing| existing.dir == dir) {
+            self.dirs.push(SearchDir { dir, source });
+        }
+    }
+
+    pub fn dirs(&self) -> &[SearchDir] {
+        &self.dirs
+    }
+
+    /// Returns the library of `plugin` in the first directory that has one.
+    pub fn find(&self, plugin: &str) -> Result<PathBuf> {
+        let file_name = plugin_loader::library_filename(plugin);
+        for candidate in &self.dirs {
+            let path = candidate.dir.join(&file_name);
+            if path.is_file() {
+                info!(
+                    "Using plugin {} from {} ({})",
+                    plugin,
+                    path.display(),
+                    candidate.source
+                );
+                return Ok(path);
+            }
+            debug!(
+                "Plugin {} not in {} ({})",
+                plugin,
+                candidate.dir.display(),
+                candidate.source
+            );
+        }
+
+        let searched: Vec<String> = self
+            .dirs
+            .iter()
+            .map(|candidate| format!("  {} ({})", candidate.dir.display(), candidate.source))
+            .collect();
+        anyhow::bail!(
+            "Plugin library not found: {}; searched:\n{}",
+            file_name,
+            searched.join("\n")
+        )
+    }
+}
+
+/// Returns the location of the user config file, whether or not it exists:
+/// `$XDG_CONFIG_HOME/image_processor/config.json` (falling back to `~/.config`) or
+/// `%APPDATA%\image_processor\config.json` on Windows.
+fn user_config_file() -> Option<PathBuf> {
+    let config_dir = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    }?;
+    Some(config_dir.join("image_processor").join("config.json"))
+}
+
+/// Returns the system-wide plugin directory.
+fn system_plugin_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        std::env::var_os("ProgramData")
+            .map(|dir| PathBuf::from(dir).join("image_processor").join("plugins"))
+    } else {
+        Some(PathBuf::from("/usr/local/lib/image_processor/plugins"))
+    }
+}
+
+/// Reads the `plugin_path` list of a config file. Relative entries are resolved against the
+/// directory containing the file.
+fn read_config(config_file: &Path) -> Result<Vec<PathBuf>> {
+    let json = std::fs::read_to_string(config_file)
+        .with_context(|| format!("Failed to read config file: {}", config_file.display()))?;
+    let base_dir = config_file.parent().unwrap_or(Path::new(""));
+    parse_config(&json, base_dir)
+        .with_context(|| format!("Invalid config file: {}", config_file.display()))
+}
+
+fn parse_config(json: &str, base_dir: &Path) -> Result<Vec<PathBuf>> {
+    let value: Value = serde_json::from_str(json).context("Config file is not valid JSON")?;
+    let object = value
+        .as_object()
+        .context("Config file must be a JSON object")?;
+    if let Some(key) = object
+        .keys()
+        .find(|key| !CONFIG_KEYS.contains(&key.as_str()))
+    {
+        anyhow::bail!(
+            "unknown key \"{}\" (expected one of: {})",
+            key,
+            CONFIG_KEYS.join(", ")
+        );
+    }
+
+    let Some(plugin_path) = object.get("plugin_path") else {
+        return Ok(Vec::new());
+    };
+    plugin_path
+        .as_array()
+        .context("\"plugin_path\" must be an array of directory paths")?
+        .iter()
+        .map(|entry| {
+            entry
+                .as_str()
+                .map(|dir| base_dir.join(dir))
+                .context("\"plugin_path\" must be an array of directory paths")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn sources(search_path: &SearchPath) -> Vec<(PathBuf, Source)> {
+        search_path
+            .dirs()
+            .iter()
+            .map(|candidate| (candidate.dir.clone(), candidate.source.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_assemble_orders_sources_by_precedence() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let config_file = dir.path().join("config.json");
+        std::fs::write(
+            &config_file,
+            r#"{"plugin_path": ["plugins", "/opt/plugins"]}"#,
+        )
+        .expect("failed to write config");
+        let environment = std::env::join_paths(["/env/a", "/env/b"]).expect("valid paths");
+
+        let search_path = SearchPath::assemble(
+            &[PathBuf::from("cli")],
+            Some(&environment),
+            Some(&config_file),
+            Some(PathBuf::from("/system")),
+            Some(PathBuf::from("/bin")),
+        )
+        .expect("search path should assemble");
+
+        assert_eq!(
+            sources(&search_path),
+            vec![
+                (PathBuf::from("cli"), Source::CommandLine),
+                (PathBuf::from("/env/a"), Source::Environment),
+                (PathBuf::from("/env/b"), Source::Environment),
+                (
+                    dir.path().join("plugins"),
+                    Source::ConfigFile(config_file.clone())
+                ),
+                (
+                    PathBuf::from("/opt/plugins"),
+                    Source::ConfigFile(config_file.clone())
+                ),
+                (PathBuf::from("/system"), Source::System),
+                (PathBuf::from("/bin"), Source::Executable),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assemble_skips_missing_config_and_duplicates() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let missing_config = dir.path().join("missing.json");
+
+        let search_path = SearchPath::assemble(
+            &[PathBuf::from("/plugins")],
+            Some(OsStr::new("")),
+            Some(&missing_config),
+            Some(PathBuf::from("/plugins")),
+            None,
+        )
+        .expect("search path should assemble");
+
+        assert_eq!(
+            sources(&search_path),
+            vec![(PathBuf::from("/plugins"), Source::CommandLine)]
+        );
+    }
+
+    #[test]
+    fn test_parse_config_rejects_unknown_keys_and_bad_entries() {
+        let base = Path::new("/etc");
+
+        assert_eq!(
+            parse_config("{}", base).expect("empty config is valid"),
+            Vec::<PathBuf>::new()
+        );
+        let err = parse_config(r#"{"plugins": []}"#, base).expect_err("unknown key");
+        assert!(err.to_string().contains("unknown key \"plugins\""));
+        assert!(parse_config(r#"{"plugin_path": "/opt"}"#, base).is_err());
+        assert!(parse_config(r#"{"plugin_path": [1]}"#, base).is_err());
+        assert!(parse_config("[]", base).is_err());
+    }
+
+    #[test]
+    fn test_find_returns_first_match() {
+        let first = TempDir::new().expect("failed to create temp directory");
+        let second = TempDir::new().expect("failed to create temp directory");
+        let file_name = plugin_loader::library_filename("blur_plugin");
+        std::fs::write(second.path().join(&file_name), b"").expect("failed to write file");
+        std::fs::write(first.path().join(&file_name), b"").expect("failed to write file");
+
+        let search_path =
+            SearchPath::from_dirs(&[first.path().to_path_buf(), second.path().to_path_buf()]);
+
+        assert_eq!(
+            search_path
+                .find("blur_plugin")
+                .expect("plugin should be found"),
+            first.path().join(&file_name)
+        );
+    }
+
+    #[test]
+    fn test_find_lists_searched_directories() {
+        let search_path = SearchPath::from_dirs(&[PathBuf::from("/nonexistent")]);
+
+        let err = search_path
+            .find("blur_plugin")
+            .expect_err("missing plugin must fail");
+
+        let message = err.to_string();
+        assert!(
+            message.contains("Plugin library not found"),
+            "got: {}",
+            message
+        );
+        assert!(
+            message.contains("/nonexistent (--plugin-path)"),
+            "got: {}",
+            message
+        );
+    }
+}
@@ -0,0 +1,837 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context, Result};
+use image::codecs::png::{CompressionType, FilterType};
+use image::{ColorType, DynamicImage, ImageBuffer, ImageFormat, Luma, LumaA, Rgb, Rgba};
+use log::{debug, info, warn};
+
+use crate::cancel;
+use crate::image_io::{self, EncodeOptions};
+use crate::pipeline::Pipeline;
+use crate::pixels::{self, PixelFormat, Pixels, Region};
+
+/// Signature every PNG file starts with.
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// `--tile-size` / `--tile-overlap` settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileOptions {
+    /// Width and height of the part of each tile that ends up in the output.
+    pub size: u32,
+    /// Pixels of context read around each tile; defaults to the halo the plugins declare.
+    pub overlap: Option<u32>,
+}
+
+/// Runs the pipeline over one image tile by tile and saves the result.
+///
+/// Non-interlaced PNG files are read and written a band of tiles at a time, so only about
+/// `size + 2 * overlap` rows are in memory at once; other formats are decoded or encoded
+/// whole, but the plugins still only ever see one tile.
+pub fn process_file(
+    input: &Path,
+    output: &Path,
+    encode_options: &EncodeOptions,
+    pipeline: &Pipeline,
+    options: TileOptions,
+) -> Result<()> {
+    let overlap = resolve_overlap(pipeline.halo()?, options.overlap)?;
+    let format = image_io::output_format(output, encode_options)?;
+
+    info!("Loading image from: {}", input.display());
+    let (mut source, color): (Box<dyn RowSource>, ColorType) = match PngRows::open(input)? {
+        Some(rows) => {
+            let color = rows.color_type();
+            (Box::new(rows), color)
+        }
+        None => {
+            let image = image_io::load(input)?;
+            let color = image.color();
+            (Box::new(MemoryRows::new(Pixels::from_image(image))), color)
+        }
+    };
+    let (width, height) = source.dimensions();
+    let mut sink: Box<dyn RowSink + '_> = if format == ImageFormat::Png {
+        Box::new(PngRowWriter::new(
+            output,
+            width,
+            height,
+            color,
+            encode_options,
+        ))
+    } else {
+        Box::new(MemoryImage::new(output, color, encode_options))
+    };
+
+    info!(
+        "Processing {}x{} image in {}x{} tiles with {} pixels of overlap",
+        width, height, options.size, options.size, overlap
+    );
+    run(&mut *source, &mut *sink, options.size, overlap, |tile| {
+        pipeline.run(tile, None)
+    })?;
+    cancel::check_interrupted()?;
+    sink.finish()
+}
+
+/// Picks the overlap between tiles: the one asked for, or else the `halo` the plugins
+/// declared. Without either (isolated plugins are not asked) it has to be given.
+fn resolve_overlap(halo: Option<u32>, requested: Option<u32>) -> Result<u32> {
+    match (requested, halo) {
+        (Some(overlap), Some(halo)) if overlap < halo => {
+            warn!(
+                "--tile-overlap {} is smaller than the {} pixels the plugins need; tile edges may show",
+                overlap, halo
+            );
+            Ok(overlap)
+        }
+        (Some(overlap), _) => Ok(overlap),
+        (None, Some(halo)) => Ok(halo),
+        (None, None) => anyhow::bail!(
+            "--tile-overlap is required with --isolate, since the plugins are not loaded in this process to ask them"
+        ),
+    }
+}
+
+/// Rows of an image, read from the top.
+trait RowSource {
+    /// Returns the width and height of the whole image.
+    fn dimensions(&self) -> (u32, u32);
+
+    /// Returns the next `rows` rows.
+    fn read_rows(&mut self, rows: u32) -> Result<Pixels>;
+}
+
+/// Destination for rows of an image, written from the top.
+trait RowSink {
+    /// Appends the next rows.
+    fn write_rows(&mut self, rows: Pixels) -> Result<()>;
+
+    /// Completes the image once every row has been written.
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// Runs `process` over `tile` x `tile` pieces of the image from `source`, each extended by
+/// `overlap` pixels of its neighbours, and writes the results to `sink` one band of tiles at
+/// a time. Only the part of each tile inside its own `tile` x `tile` square is kept.
+fn run(
+    source: &mut dyn RowSource,
+    sink: &mut dyn RowSink,
+    tile: u32,
+    overlap: u32,
+    mut process: impl FnMut(&mut Pixels) -> Result<()>,
+) -> Result<()> {
+    let (width, height) = source.dimensions();
+    // Image rows `window_top..window_top + window.height`
+    let mut window = source.read_rows(0)?;
+    let mut window_top = 0;
+    let bands = height.div_ceil(tile);
+
+    for (band_index, band_top) in (0..height).step_by(tile as usize).enumerate() {
+        let band_height = tile.min(height - band_top);
+        let top = band_top.saturating_sub(overlap);
+        let bottom = (band_top + band_height).saturating_add(overlap).min(height);
+        drop_rows(&mut window, top - window_top);
+        window_top = top;
+        let window_bottom = window_top + window.height;
+        if bottom > window_bottom {
+            append_rows(&mut window, source.read_rows(bottom - window_bottom)?)?;
+        }
+
+        info!(
+            "Tile row {}/{}: image rows {}..{}",
+            band_index + 1,
+            bands,
+            band_top,
+            band_top + band_height
+        );
+        let mut band: Option<Pixels> = None;
+        for tile_left in (0..width).step_by(tile as usize) {
+            let tile_width = tile.min(width - tile_left);
+            let left = tile_left.saturating_sub(overlap);
+            let right = (tile_left + tile_width).saturating_add(overlap).min(width);
+            let with_overlap = Region {
+                x: left,
+                y: 0,
+                width: right - left,
+                height: bottom - top,
+            };
+            let mut pixels = window.crop(with_overlap)?;
+            process(&mut pixels)?;
+            if (pixels.width, pixels.height) != (with_overlap.width, with_overlap.height) {
+                anyhow::bail!(
+                    "The pipeline changed a {}x{} tile to {}x{}; plugins that change the image size cannot be used with --tile-size",
+                    with_overlap.width,
+                    with_overlap.height,
+                    pixels.width,
+                    pixels.height
+                );
+            }
+
+            let mut core = pixels.crop(Region {
+                x: tile_left - left,
+                y: band_top - top,
+                width: tile_width,
+                height: band_height,
+            })?;
+            let band = match &mut band {
+                Some(band) => band,
+                None => band.insert(blank(width, band_height, core.format)?),
+            };
+            core.convert(band.format)?;
+            band.region_mut(Region {
+                x: tile_left,
+                y: 0,
+                width: tile_width,
+                height: band_height,
+            })?
+            .copy_from_packed(&core.data);
+        }
+        if let Some(band) = band {
+            sink.write_rows(band)?;
+        }
+    }
+    Ok(())
+}
+
+fn blank(width: u32, height: u32, format: PixelFormat) -> Result<Pixels> {
+    let len = format
+        .buffer_len(width, height)
+        .context("Image dimensions overflow")?;
+    Pixels::new(width, height, format, vec![0; len])
+}
+
+/// Removes the first `rows` rows of `pixels`.
+fn drop_rows(pixels: &mut Pixels, rows: u32) {
+    let row_bytes = pixels.width as usize * pixels.format.bytes_per_pixel();
+    pixels.data.drain(..rows as usize * row_bytes);
+    pixels.height -= rows;
+}
+
+/// Adds `rows` below the last row of `pixels`.
+fn append_rows(pixels: &mut Pixels, mut rows: Pixels) -> Result<()> {
+    if rows.width != pixels.width {
+        anyhow::bail!(
+            "Cannot append {} pixel wide rows to a {} pixel wide image",
+            rows.width,
+            pixels.width
+        );
+    }
+    rows.convert(pixels.format)?;
+    pixels.data.append(&mut rows.data);
+    pixels.height += rows.height;
+    Ok(())
+}
+
+/// Rows of an image decoded whole, for inputs that cannot be streamed.
+struct MemoryRows {
+    pixels: Pixels,
+    next_row: u32,
+}
+
+impl MemoryRows {
+    fn new(pixels: Pixels) -> Self {
+        Self {
+            pixels,
+            next_row: 0,
+        }
+    }
+}
+
+impl RowSource for MemoryRows {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.pixels.width, self.pixels.height)
+    }
+
+    fn read_rows(&mut self, rows: u32) -> Result<Pixels> {
+        let rows = self.pixels.crop(Region {
+            x: 0,
+            y: self.next_row,
+            width: self.pixels.width,
+            height: rows,
+        })?;
+        self.next_row += rows.height;
+        Ok(rows)
+    }
+}
+
+/// Collects the rows of an image and saves it whole, for outputs that cannot be streamed.
+/// The image is saved in the `source` layout where nothing is lost by it.
+struct MemoryImage<'a> {
+    output: &'a Path,
+    source: ColorType,
+    options: &'a EncodeOptions,
+    pixels: Option<Pixels>,
+}
+
+impl<'a> MemoryImage<'a> {
+    fn new(output: &'a Path, source: ColorType, options: &'a EncodeOptions) -> Self {
+        Self {
+            output,
+            source,
+            options,
+            pixels: None,
+        }
+    }
+}
+
+impl RowSink for MemoryImage<'_> {
+    fn write_rows(&mut self, rows: Pixels) -> Result<()> {
+        match &mut self.pixels {
+            Some(pixels) => append_rows(pixels, rows),
+            None => {
+                self.pixels = Some(rows);
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        let pixels = self.pixels.context("No rows were written")?;
+        let image = pixels::restore_layout(pixels.into_image()?, self.source);
+        image_io::save(image, self.output, self.options)
+    }
+}
+
+/// Rows of a non-interlaced PNG file, decoded as they are read.
+struct PngRows {
+    reader: png::Reader<BufReader<File>>,
+    color: png::ColorType,
+    depth: png::BitDepth,
+}
+
+impl PngRows {
+    /// Opens `input` for streaming, or returns `None` when it is stdin, not a PNG file, or
+    /// an interlaced one (whose rows arrive out of order).
+    fn open(input: &Path) -> Result<Option<Self>> {
+        if image_io::is_stdio(input) {
+            return Ok(None);
+        }
+        let context = || format!("Failed to load image: {}", input.display());
+        let mut file = File::open(input).with_context(context)?;
+        let mut signature = [0; PNG_SIGNATURE.len()];
+        if file.read_exact(&mut signature).is_err() || &signature != PNG_SIGNATURE {
+            return Ok(None);
+        }
+        let file = File::open(input).with_context(context)?;
+
+        let mut decoder = png::Decoder::new(BufReader::new(file));
+        // Palette and 1, 2 or 4-bit images become 8-bit gray or RGB(A)
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let reader = decoder.read_info().with_context(context)?;
+        if reader.info().interlaced {
+            info!("{} is interlaced; decoding it whole", input.display());
+            return Ok(None);
+        }
+        let (color, depth) = reader.output_color_type();
+        debug!("Streaming {:?} {:?}-bit PNG", color, depth);
+        Ok(Some(Self {
+            reader,
+            color,
+            depth,
+        }))
+    }
+
+    /// Returns the layout of the decoded rows.
+    fn color_type(&self) -> ColorType {
+        let deep = self.depth == png::BitDepth::Sixteen;
+        match (self.color, deep) {
+            (png::ColorType::Grayscale, false) => ColorType::L8,
+            (png::ColorType::Grayscale, true) => ColorType::L16,
+            (png::ColorType::GrayscaleAlpha, false) => ColorType::La8,
+            (png::ColorType::GrayscaleAlpha, true) => ColorType::La16,
+            (png::ColorType::Rgb, false) => ColorType::Rgb8,
+            (png::ColorType::Rgb, true) => ColorType::Rgb16,
+            (png::ColorType::Rgba, true) => ColorType::Rgba16,
+            (png::ColorType::Rgba | png::ColorType::Indexed, _) => ColorType::Rgba8,
+        }
+    }
+}
+
+impl RowSource for PngRows {
+    fn dimensions(&self) -> (u32, u32) {
+        let info = self.reader.info();
+        (info.width, info.height)
+    }
+
+    fn read_rows(&mut self, rows: u32) -> Result<Pixels> {
+        let mut bytes = Vec::new();
+        for _ in 0..rows {
+            let row = self
+                .reader
+                .next_row()
+                .context("Failed to decode PNG row")?
+                .context("PNG image data ends early")?;
+            bytes.extend_from_slice(row.data());
+        }
+        let width = self.dimensions().0;
+        let image = png_rows_image(width, rows, self.color, self.depth, bytes)
+            .context("Unexpected PNG row size")?;
+        Ok(Pixels::from_image(image))
+    }
+}
+
+/// Wraps decoded PNG rows, whose 16-bit samples are big-endian, in a `DynamicImage`.
+fn png_rows_image(
+    width: u32,
+    rows: u32,
+    color: png::ColorType,
+    depth: png::BitDepth,
+    bytes: Vec<u8>,
+) -> Option<DynamicImage> {
+    use png::ColorType::{Grayscale, GrayscaleAlpha, Rgb as RgbColor, Rgba as RgbaColor};
+
+    if depth == png::BitDepth::Sixteen {
+        let samples: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|sample| u16::from_be_bytes([sample[0], sample[1]]))
+            .collect();
+        return match color {
+            Grayscale => {
+                ImageBuffer::<Luma<u16>, _>::from_raw(width, rows, samples).map(Into::into)
+            }
+            GrayscaleAlpha => {
+                ImageBuffer::<LumaA<u16>, _>::from_raw(width, rows, samples).map(Into::into)
+            }
+            RgbColor => ImageBuffer::<Rgb<u16>, _>::from_raw(width, rows, samples).map(Into::into),
+            RgbaColor => {
+                ImageBuffer::<Rgba<u16>, _>::from_raw(width, rows, samples).map(Into::into)
+            }
+            png::ColorType::Indexed => None,
+        };
+    }
+    match color {
+        Grayscale => ImageBuffer::<Luma<u8>, _>::from_raw(width, rows, bytes).map(Into::into),
+        GrayscaleAlpha => ImageBuffer::<LumaA<u8>, _>::from_raw(width, rows, bytes).map(Into::into),
+        RgbColor => ImageBuffer::<Rgb<u8>, _>::from_raw(width, rows, bytes).map(Into::into),
+        RgbaColor => ImageBuffer::<Rgba<u8>, _>::from_raw(width, rows, bytes).map(Into::into),
+        png::ColorType::Indexed => None,
+    }
+}
+
+/// PNG file (or stdout) written a band of rows at a time, in the layout of the first band;
+/// float pixels are stored as 16-bit, as [`image_io::save`] does.
+///
+/// Sources the plugins get in a wider format (16-bit gray or RGB, float RGB) are spooled to a
+/// temporary file instead, and written out once every band is in: in the `source` layout if
+/// no band needs the wider one, like [`pixels::restore_layout`] decides for whole images.
+struct PngRowWriter<'a> {
+    output: &'a Path,
+    width: u32,
+    height: u32,
+    source: ColorType,
+    options: &'a EncodeOptions,
+    writer: Option<(png::StreamWriter<'static, Box<dyn Write>>, ColorType)>,
+    spool: Option<Spool>,
+}
+
+impl<'a> PngRowWriter<'a> {
+    fn new(
+        output: &'a Path,
+        width: u32,
+        height: u32,
+        source: ColorType,
+        options: &'a EncodeOptions,
+    ) -> Self {
+        Self {
+            output,
+            width,
+            height,
+            source,
+            options,
+            writer: None,
+            spool: None,
+        }
+    }
+
+    /// Appends `image`, converted to the layout the file was started with.
+    fn write_image(&mut self, image: DynamicImage) -> Result<()> {
+        let (writer, layout) = match &mut self.writer {
+            Some(writer) => writer,
+            None => {
+                let layout = image_io::storable(&image, ImageFormat::Png)
+                    .map_or(image.color(), |stored| stored.color());
+                let writer = self.start(layout)?;
+                self.writer.insert((writer, layout))
+            }
+        };
+        let image = pixels::into_color(image, *layout);
+
+        let bytes = match layout.bytes_per_pixel() / layout.channel_count() {
+            2 => image
+                .as_bytes()
+                .chunks_exact(2)
+                .flat_map(|sample| u16::from_ne_bytes([sample[0], sample[1]]).to_be_bytes())
+                .collect(),
+            _ => image.into_bytes(),
+        };
+        Ok(writer.write_all(&bytes)?)
+    }
+
+    /// Writes out the spooled bands, narrowed to the source layout if they all fit it.
+    fn write_spooled(&mut self, mut spool: Spool) -> Result<()> {
+        spool.file.flush()?;
+        let mut reader = BufReader::new(File::open(&spool.path)?);
+        let row_bytes = self.width as usize * spool.format.bytes_per_pixel();
+        let mut row = 0;
+        while row < self.height {
+            let rows = SPOOL_ROWS.min(self.height - row);
+            let mut data = vec![0; row_bytes * rows as usize];
+            reader.read_exact(&mut data)?;
+            let image = Pixels::new(self.width, rows, spool.format, data)?.into_image()?;
+            self.write_image(match spool.fits {
+                true => pixels::into_color(image, self.source),
+                false => image,
+            })?;
+            row += rows;
+        }
+        Ok(())
+    }
+
+    fn start(&self, layout: ColorType) -> Result<png::StreamWriter<'static, Box<dyn Write>>> {
+        let out: Box<dyn Write> = if image_io::is_stdio(self.output) {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(self.output)?)
+        };
+        let mut encoder = png::Encoder::new(out, self.width, self.height);
+        let (color, depth) = match layout {
+            ColorType::L8 => (png::ColorType::Grayscale, png::BitDepth::Eight),
+            ColorType::La8 => (png::ColorType::GrayscaleAlpha, png::BitDepth::Eight),
+            ColorType::Rgb8 => (png::ColorType::Rgb, png::BitDepth::Eight),
+            ColorType::Rgba8 => (png::ColorType::Rgba, png::BitDepth::Eight),
+            ColorType::L16 => (png::ColorType::Grayscale, png::BitDepth::Sixteen),
+            ColorType::La16 => (png::ColorType::GrayscaleAlpha, png::BitDepth::Sixteen),
+            ColorType::Rgb16 => (png::ColorType::Rgb, png::BitDepth::Sixteen),
+            _ => (png::ColorType::Rgba, png::BitDepth::Sixteen),
+        };
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+
+        // Same mapping as the `image` PNG encoder uses for --png-compression / --png-filter
+        let compression = self.options.png_compression.unwrap_or_default();
+        encoder.set_compression(match compression {
+            CompressionType::Default => png::Compression::Balanced,
+            CompressionType::Best => png::Compression::High,
+            CompressionType::Uncompressed | CompressionType::Level(0) => {
+                png::Compression::NoCompression
+            }
+            _ => png::Compression::Fast,
+        });
+        if let CompressionType::Level(level @ 1..) = compression {
+            encoder.set_deflate_compression(png::DeflateCompression::Level(level));
+        }
+        encoder.set_filter(match self.options.png_filter.unwrap_or_default() {
+            FilterType::NoFilter => png::Filter::NoFilter,
+            FilterType::Sub => png::Filter::Sub,
+            FilterType::Up => png::Filter::Up,
+            FilterType::Avg => png::Filter::Avg,
+            FilterType::Paeth => png::Filter::Paeth,
+            _ => png::Filter::Adaptive,
+        });
+
+        Ok(encoder.write_header()?.into_stream_writer()?)
+    }
+}
+
+impl RowSink for PngRowWriter<'_> {
+    fn write_rows(&mut self, mut rows: Pixels) -> Result<()> {
+        let context = || format!("Failed to save image: {}", self.output.display());
+        if !pixels::is_widened(self.source) {
+            let image = rows.into_image()?;
+            return self.write_image(image).with_context(context);
+        }
+
+        // Whether the output can return to the source layout depends on every band
+        let spool = match &mut self.spool {
+            Some(spool) => spool,
+            None => self.spool.insert(Spool::create(rows.format)?),
+        };
+        rows.convert(spool.format)?;
+        let image = rows.into_image()?;
+        spool.fits = spool.fits && pixels::fits_layout(&image, self.source);
+        spool
+            .file
+            .write_all(image.as_bytes())
+            .with_context(|| format!("Failed to write {}", spool.path.display()))
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let context = || format!("Failed to save image: {}", self.output.display());
+        if let Some(spool) = self.spool.take() {
+            self.write_spooled(spool).with_context(context)?;
+        }
+        let (writer, _) = self.writer.take().context("No rows were written")?;
+        writer.finish().with_context(context)?;
+        if image_io::is_stdio(self.output) {
+            io::stdout()
+                .flush()
+                .context("Failed to write image to stdout")?;
+            info!("Wrote image to stdout");
+        } else {
+            info!("Saved image to: {}", self.output.display());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PngRowWriter<'_> {
+    fn drop(&mut self) {
+        // A run that failed or was cancelled part way leaves no truncated file behind
+        if self.writer.take().is_some() && !image_io::is_stdio(self.output) {
+            let _ = fs::remove_file(self.output);
+        }
+    }
+}
+
+/// Rows read back from a spool file at a time.
+const SPOOL_ROWS: u32 = 64;
+
+/// Temporary file holding bands in the plugins' pixel format, removed when dropped.
+struct Spool {
+    path: PathBuf,
+    file: BufWriter<File>,
+    format: PixelFormat,
+    /// Whether every band so far fits the source layout.
+    fits: bool,
+}
+
+impl Spool {
+    fn create(format: PixelFormat) -> Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "image_processor-{}-{}.rows",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = File::options()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .with_context(|| format!("Failed to create temporary file: {}", path.display()))?;
+        debug!("Spooling rows to {}", path.display());
+        Ok(Self {
+            path,
+            file: BufWriter::new(file),
+            format,
+            fits: true,
+        })
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, ImageBuffer};
+    use tempfile::TempDir;
+
+    impl RowSink for Vec<Pixels> {
+        fn write_rows(&mut self, rows: Pixels) -> Result<()> {
+            self.push(rows);
+            Ok(())
+        }
+
+        fn finish(self: Box<Self>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Replaces each gray pixel with the brightest one within `radius`, a neighbourhood
+    /// operation that needs a halo of `radius` pixels.
+    fn dilate(pixels: &mut Pixels, radius: u32) -> Result<()> {
+        let source = pixels.clone();
+        let (width, height) = (pixels.width as i64, pixels.height as i64);
+        let radius = radius as i64;
+        for y in 0..height {
+            for x in 0..width {
+                let mut brightest = 0;
+                for ny in (y - radius).max(0)..=(y + radius).min(height - 1) {
+                    for nx in (x - radius).max(0)..=(x + radius).min(width - 1) {
+                        brightest = brightest.max(source.data[(ny * width + nx) as usize]);
+                    }
+                }
+                pixels.data[(y * width + x) as usize] = brightest;
+            }
+        }
+        Ok(())
+    }
+
+    fn speckles(width: u32, height: u32) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| {
+            Luma([((x * 7 + y * 13) % 11 == 0) as u8 * (x * 20 + y) as u8])
+        })
+    }
+
+    #[test]
+    fn test_run_matches_whole_image_processing() {
+        let image = speckles(11, 9);
+        let mut expected = Pixels::from_image(image.clone().into());
+        dilate(&mut expected, 2).expect("dilate");
+
+        let mut source = MemoryRows::new(Pixels::from_image(image.into()));
+        let mut bands = Vec::new();
+        run(&mut source, &mut bands, 4, 2, |tile| dilate(tile, 2)).expect("tiled run");
+
+        assert_eq!(bands.len(), 3, "9 rows in tiles of 4");
+        let mut tiled = bands.remove(0);
+        for band in bands {
+            append_rows(&mut tiled, band).expect("same width");
+        }
+        assert_eq!((tiled.width, tiled.height), (11, 9));
+        assert_eq!(tiled.data, expected.data);
+    }
+
+    #[test]
+    fn test_run_without_enough_overlap_shows_tile_edges() {
+        let image = speckles(11, 9);
+        let mut expected = Pixels::from_image(image.clone().into());
+        dilate(&mut expected, 2).expect("dilate");
+
+        let mut source = MemoryRows::new(Pixels::from_image(image.into()));
+        let mut bands = Vec::new();
+        run(&mut source, &mut bands, 4, 1, |tile| dilate(tile, 2)).expect("tiled run");
+
+        let tiled: Vec<u8> = bands.into_iter().flat_map(|band| band.data).collect();
+        assert_ne!(tiled, expected.data);
+    }
+
+    #[test]
+    fn test_run_rejects_size_changes() {
+        let mut source = MemoryRows::new(Pixels::from_image(speckles(4, 4).into()));
+        let mut bands = Vec::new();
+
+        let err = run(&mut source, &mut bands, 2, 0, |tile| {
+            *tile = tile.crop(Region {
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1,
+            })?;
+            Ok(())
+        })
+        .expect_err("size change should fail");
+
+        assert!(
+            err.to_string().contains("cannot be used with --tile-size"),
+            "got: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_resolve_overlap() {
+        assert_eq!(resolve_overlap(Some(3), None).expect("halo"), 3);
+        assert_eq!(resolve_overlap(Some(3), Some(1)).expect("explicit"), 1);
+        assert_eq!(resolve_overlap(None, Some(5)).expect("explicit"), 5);
+
+        let err = resolve_overlap(None, None).expect_err("unknown halo");
+        assert!(err.to_string().contains("--tile-overlap"), "got: {}", err);
+    }
+
+    #[test]
+    fn test_png_rows_round_trip_16_bit() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let input = dir.path().join("in.png");
+        let output = dir.path().join("out.png");
+        let image =
+            ImageBuffer::<Luma<u16>, _>::from_fn(5, 7, |x, y| Luma([(x * 9000 + y) as u16]));
+        image.save(&input).expect("failed to write image");
+
+        let mut source = PngRows::open(&input)
+            .expect("readable")
+            .expect("non-interlaced PNG streams");
+        assert_eq!(source.dimensions(), (5, 7));
+        let options = EncodeOptions::default();
+        let mut sink = Box::new(PngRowWriter::new(
+            &output,
+            5,
+            7,
+            source.color_type(),
+            &options,
+        ));
+        run(&mut source, &mut *sink, 3, 1, |_| Ok(())).expect("tiled run");
+        sink.finish().expect("finish");
+
+        let saved = image::open(&output).expect("output decodes");
+        assert_eq!(saved, DynamicImage::from(image));
+    }
+
+    #[test]
+    fn test_png_row_writer_keeps_alpha_added_in_later_bands() {
+        // Makes bright pixels translucent, which only the last band has
+        fn fade_bright(pixels: &mut Pixels) -> Result<()> {
+            assert_eq!(pixels.format, PixelFormat::Rgba16);
+            for pixel in pixels.data.chunks_exact_mut(8) {
+                if u16::from_ne_bytes([pixel[0], pixel[1]]) > 35000 {
+                    pixel[6..8].copy_from_slice(&0x8000u16.to_ne_bytes());
+                }
+            }
+            Ok(())
+        }
+
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let input = dir.path().join("in.png");
+        let output = dir.path().join("out.png");
+        let image =
+            ImageBuffer::<Luma<u16>, _>::from_fn(4, 6, |x, y| Luma([(y * 10000 + x) as u16]));
+        image.save(&input).expect("failed to write image");
+
+        let mut whole = Pixels::from_image(DynamicImage::from(image));
+        fade_bright(&mut whole).expect("whole image");
+        let expected = pixels::restore_layout(whole.into_image().expect("image"), ColorType::L16);
+        assert_eq!(expected.color(), ColorType::Rgba16);
+
+        let mut source = PngRows::open(&input)
+            .expect("readable")
+            .expect("non-interlaced PNG streams");
+        let options = EncodeOptions::default();
+        let mut sink = Box::new(PngRowWriter::new(&output, 4, 6, ColorType::L16, &options));
+        run(&mut source, &mut *sink, 2, 0, fade_bright).expect("tiled run");
+        sink.finish().expect("finish");
+
+        assert_eq!(image::open(&output).expect("output decodes"), expected);
+    }
+
+    #[test]
+    fn test_png_row_writer_removes_unfinished_output() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let output = dir.path().join("out.png");
+        let options = EncodeOptions::default();
+        let mut source = MemoryRows::new(Pixels::from_image(speckles(4, 4).into()));
+        let mut sink = Box::new(PngRowWriter::new(&output, 4, 4, ColorType::L8, &options));
+
+        let mut tiles = 0;
+        run(&mut source, &mut *sink, 2, 0, |_| {
+            tiles += 1;
+            match tiles {
+                1 | 2 => Ok(()),
+                _ => anyhow::bail!("cancelled"),
+            }
+        })
+        .expect_err("the second row of tiles fails");
+        assert!(output.exists(), "the first row of tiles was written");
+
+        drop(sink);
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn test_png_rows_skip_other_formats() {
+        let dir = TempDir::new().expect("failed to create temp directory");
+        let path = dir.path().join("image.png");
+        speckles(3, 3)
+            .save_with_format(&path, ImageFormat::Bmp)
+            .expect("failed to write image");
+
+        assert!(PngRows::open(&path).expect("readable").is_none());
+        assert!(PngRows::open(Path::new("-")).expect("stdin").is_none());
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use tempfile::TempDir;
 
@@ -29,6 +29,36 @@ fn get_test_images_dir() -> PathBuf {
         .join("test_images")
 }
 
+/// Runs image_processor on `sample.png` with inline params written to a temp file.
+/// Returns the process output, the temp directory (kept alive) and the output path.
+fn run_sample(
+    plugin: &str,
+    params_json: &str,
+    extra_args: &[&str],
+) -> (std::process::Output, TempDir, PathBuf) {
+    let temp_dir = TempDir::new().expect("failed to create temp directory");
+    let params_path = temp_dir.path().join("params.json");
+    std::fs::write(&params_path, params_json).expect("failed to write params file");
+    let output_path = temp_dir.path().join("output.png");
+
+    let output = Command::new(get_binary_path())
+        .arg("--input")
+        .arg(get_test_images_dir().join("sample.png"))
+        .arg("--output")
+        .arg(&output_path)
+        .arg("--plugin")
+        .arg(plugin)
+        .arg("--plugin-path")
+        .arg(get_plugin_dir())
+        .arg("--params")
+        .arg(&params_path)
+        .args(extra_args)
+        .output()
+        .expect("failed to execute image_processor binary");
+
+    (output, temp_dir, output_path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -280,4 +310,1150 @@ mod tests {
             stderr
         );
     }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_info_subcommand_prints_metadata() {
+        let binary_path = get_binary_path();
+        let plugin_dir = get_plugin_dir();
+
+        let output = Command::new(&binary_path)
+            .arg("info")
+            .arg("--plugin")
+            .arg("mirror_plugin")
+            .arg("--plugin-path")
+            .arg(&plugin_dir)
+            .output()
+            .expect("failed to execute image_processor binary");
+
+        assert!(
+            output.status.success(),
+            "info failed with stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.contains("mirror_plugin"),
+            "stdout should contain the plugin name: {}",
+            stdout
+        );
+        assert!(
+            stdout.contains("Version:"),
+            "stdout should contain the plugin version: {}",
+            stdout
+        );
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_error_panicking_plugin() {
+        let binary_path = get_binary_path();
+        let plugin_dir = get_plugin_dir();
+        let test_images_dir = get_test_images_dir();
+
+        let input_path = test_images_dir.join("sample.png");
+
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let params_path = temp_dir.path().join("empty.json");
+        std::fs::write(&params_path, "{}").expect("failed to write params file");
+        let output_path = temp_dir.path().join("output.png");
+
+        let output = Command::new(&binary_path)
+            .arg("--input")
+            .arg(&input_path)
+            .arg("--output")
+            .arg(&output_path)
+            .arg("--plugin")
+            .arg("panic_plugin")
+            .arg("--plugin-path")
+            .arg(&plugin_dir)
+            .arg("--params")
+            .arg(&params_path)
+            .output()
+            .expect("failed to execute image_processor binary");
+
+        // Verify: the host exits with an ordinary error instead of aborting
+        assert_eq!(
+            output.status.code(),
+            Some(1),
+            "image_processor should exit cleanly with status 1, stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("panicked"),
+            "stderr should mention the plugin panic: {}",
+            stderr
+        );
+        assert!(
+            !output_path.exists(),
+            "no output should be written on failure"
+        );
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_isolate_matches_in_process_output() {
+        let params = r#"{"radius": 2, "iterations": 1}"#;
+        let (in_process, _dir_a, in_process_path) = run_sample("blur_plugin", params, &[]);
+        let (isolated, _dir_b, isolated_path) = run_sample("blur_plugin", params, &["--isolate"]);
+
+        assert!(in_process.status.success());
+        assert!(
+            isolated.status.success(),
+            "isolated run failed with stderr: {}",
+            String::from_utf8_lossy(&isolated.stderr)
+        );
+
+        let in_process_image = image::open(&in_process_path).expect("failed to open output");
+        let isolated_image = image::open(&isolated_path).expect("failed to open output");
+        assert_eq!(in_process_image.to_rgba8(), isolated_image.to_rgba8());
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_isolate_reports_crash_as_error() {
+        let (output, _dir, output_path) =
+            run_sample("panic_plugin", r#"{"mode": "abort"}"#, &["--isolate"]);
+
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("Plugin worker"),
+            "stderr should describe the worker crash: {}",
+            stderr
+        );
+        assert!(!output_path.exists());
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_isolate_timeout_kills_worker() {
+        let started = std::time::Instant::now();
+        let (output, _dir, _output_path) = run_sample(
+            "panic_plugin",
+            r#"{"mode": "hang"}"#,
+            &["--isolate", "--timeout", "1"],
+        );
+
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("timed out"), "stderr: {}", stderr);
+        assert!(started.elapsed() < std::time::Duration::from_secs(30));
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_plugin_logs_reach_host_logger() {
+        for extra_args in [&[][..], &["--isolate"][..]] {
+            let temp_dir = TempDir::new().expect("failed to create temp directory");
+            let output = Command::new(get_binary_path())
+                .arg("--input")
+                .arg(get_test_images_dir().join("sample.png"))
+                .arg("--output")
+                .arg(temp_dir.path().join("output.png"))
+                .args(["--plugin", "blur_plugin"])
+                .args(["--params-json", r#"{"radius": 1}"#])
+                .arg("--plugin-path")
+                .arg(get_plugin_dir())
+                .args(extra_args)
+                .env("RUST_LOG", "blur_plugin=debug")
+                .output()
+                .expect("failed to execute image_processor binary");
+
+            assert!(output.status.success());
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(
+                stderr.contains("DEBUG blur_plugin] Blurring 100x100 image with radius 1"),
+                "stderr: {}",
+                stderr
+            );
+        }
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_isolated_plugin_logs_follow_verbose() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let output = Command::new(get_binary_path())
+            .arg("-vv")
+            .arg("--input")
+            .arg(get_test_images_dir().join("sample.png"))
+            .arg("--output")
+            .arg(temp_dir.path().join("output.png"))
+            .args(["--plugin", "blur_plugin"])
+            .args(["--params-json", r#"{"radius": 1}"#])
+            .arg("--plugin-path")
+            .arg(get_plugin_dir())
+            .arg("--isolate")
+            .env_remove("RUST_LOG")
+            .output()
+            .expect("failed to execute image_processor binary");
+
+        assert!(output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("DEBUG blur_plugin] Blurring 100x100 image with radius 1"),
+            "stderr: {}",
+            stderr
+        );
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_timeout_cancels_plugin_in_process() {
+        let started = std::time::Instant::now();
+        let (output, _dir, output_path) =
+            run_sample("panic_plugin", r#"{"mode": "wait"}"#, &["--timeout", "1"]);
+
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("timed out after 1s"), "stderr: {}", stderr);
+        assert!(stderr.contains("cancelled"), "stderr: {}", stderr);
+        assert!(
+            !output_path.exists(),
+            "a cancelled run must not write output"
+        );
+        assert!(started.elapsed() < std::time::Duration::from_secs(30));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_ctrl_c_cancels_plugin() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let output_path = temp_dir.path().join("output.png");
+
+        for extra_args in [&[][..], &["--isolate"][..]] {
+            let child = Command::new(get_binary_path())
+                .arg("--input")
+                .arg(get_test_images_dir().join("sample.png"))
+                .arg("--output")
+                .arg(&output_path)
+                .args(["--plugin", "panic_plugin"])
+                .args(["--params-json", r#"{"mode": "wait"}"#])
+                .arg("--plugin-path")
+                .arg(get_plugin_dir())
+                .args(extra_args)
+                .stderr(std::process::Stdio::piped())
+                .spawn()
+                .expect("failed to execute image_processor binary");
+
+            // Give the host time to install its handler and start the plugin
+            std::thread::sleep(std::time::Duration::from_secs(1));
+            // SAFETY: kill only sends a signal to the child started above.
+            let sent = unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) };
+            assert_eq!(sent, 0, "failed to send SIGINT");
+
+            let output = child
+                .wait_with_output()
+                .expect("failed to wait for image_processor");
+            assert_eq!(output.status.code(), Some(1));
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(stderr.contains("interrupted"), "stderr: {}", stderr);
+            assert!(
+                !output_path.exists(),
+                "an interrupted run must not write output"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_isolate_memory_limit() {
+        let (output, _dir, _output_path) = run_sample(
+            "panic_plugin",
+            r#"{"mode": "allocate", "megabytes": 2048}"#,
+            &["--isolate", "--memory-limit", "256"],
+        );
+
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("memory limit of 256 MiB"),
+            "stderr: {}",
+            stderr
+        );
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_rotate_plugin_swaps_dimensions() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let input_path = temp_dir.path().join("wide.png");
+        image::RgbaImage::from_fn(30, 20, |x, y| image::Rgba([x as u8, y as u8, 0, 255]))
+            .save(&input_path)
+            .expect("failed to write input image");
+        let params_path = temp_dir.path().join("rotate.json");
+        std::fs::write(&params_path, r#"{"angle": 90}"#).expect("failed to write params file");
+
+        for extra_args in [&[][..], &["--isolate"][..]] {
+            let output_path = temp_dir.path().join("rotated.png");
+            let output = Command::new(get_binary_path())
+                .arg("--input")
+                .arg(&input_path)
+                .arg("--output")
+                .arg(&output_path)
+                .arg("--plugin")
+                .arg("rotate_plugin")
+                .arg("--plugin-path")
+                .arg(get_plugin_dir())
+                .arg("--params")
+                .arg(&params_path)
+                .args(extra_args)
+                .output()
+                .expect("failed to execute image_processor binary");
+
+            assert!(
+                output.status.success(),
+                "rotate failed with {:?}: {}",
+                extra_args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            let rotated = image::open(&output_path)
+                .expect("failed to open output")
+                .to_rgba8();
+            assert_eq!(rotated.dimensions(), (20, 30));
+            // The bottom-left source pixel (0, 19) ends up in the top-left corner.
+            assert_eq!(rotated.get_pixel(0, 0).0, [0, 19, 0, 255]);
+        }
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_pipeline_steps_run_in_order() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let mirror_params = temp_dir.path().join("mirror.json");
+        std::fs::write(&mirror_params, r#"{"horizontal": true}"#)
+            .expect("failed to write params file");
+        let rotate_params = temp_dir.path().join("rotate.json");
+        std::fs::write(&rotate_params, r#"{"angle": 90}"#).expect("failed to write params file");
+        let input_path = temp_dir.path().join("wide.png");
+        image::RgbaImage::from_fn(30, 20, |x, y| image::Rgba([x as u8, y as u8, 0, 255]))
+            .save(&input_path)
+            .expect("failed to write input image");
+        let output_path = temp_dir.path().join("output.png");
+
+        let output = Command::new(get_binary_path())
+            .arg("--input")
+            .arg(&input_path)
+            .arg("--output")
+            .arg(&output_path)
+            .arg("--plugin-path")
+            .arg(get_plugin_dir())
+            .arg("--step")
+            .arg(format!("mirror_plugin={}", mirror_params.display()))
+            .arg("--step")
+            .arg(format!("rotate_plugin={}", rotate_params.display()))
+            .output()
+            .expect("failed to execute image_processor binary");
+
+        assert!(
+            output.status.success(),
+            "pipeline failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let result = image::open(&output_path)
+            .expect("failed to open output")
+            .to_rgba8();
+        assert_eq!(result.dimensions(), (20, 30));
+        // After the mirror, (29, 19) is bottom-left; the rotation moves it to the top-left.
+        assert_eq!(result.get_pixel(0, 0).0, [29, 19, 0, 255]);
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_pipeline_file_runs_enabled_steps() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let output_path = temp_dir.path().join("output.png");
+
+        let output = Command::new(get_binary_path())
+            .arg("--input")
+            .arg(get_test_images_dir().join("sample.png"))
+            .arg("--output")
+            .arg(&output_path)
+            .arg("--plugin-path")
+            .arg(get_plugin_dir())
+            .arg("--pipeline")
+            .arg(get_test_images_dir().join("pipeline.json"))
+            .output()
+            .expect("failed to execute image_processor binary");
+
+        assert!(
+            output.status.success(),
+            "pipeline failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(output_path.exists());
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_pipeline_file_invalid_step_leaves_no_output() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let pipeline_path = temp_dir.path().join("pipeline.json");
+        std::fs::write(
+            &pipeline_path,
+            r#"{"steps": [
+                {"name": "flip", "plugin": "mirror_plugin", "params": {"horizontal": true}},
+                {"name": "soften", "plugin": "blur_plugin", "params": {"radius": "big"}}
+            ]}"#,
+        )
+        .expect("failed to write pipeline file");
+        let output_path = temp_dir.path().join("output.png");
+
+        let output = Command::new(get_binary_path())
+            .arg("--input")
+            .arg(get_test_images_dir().join("sample.png"))
+            .arg("--output")
+            .arg(&output_path)
+            .arg("--plugin-path")
+            .arg(get_plugin_dir())
+            .arg("--pipeline")
+            .arg(&pipeline_path)
+            .output()
+            .expect("failed to execute image_processor binary");
+
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("Pipeline step 2 'soften' (blur_plugin) failed"),
+            "stderr: {}",
+            stderr
+        );
+        assert!(!output_path.exists());
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_batch_directory_preserves_paths_and_reports_failures() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let input_dir = temp_dir.path().join("in");
+        let output_dir = temp_dir.path().join("out");
+        std::fs::create_dir_all(input_dir.join("nested")).expect("failed to create input dirs");
+        let sample = get_test_images_dir().join("sample.png");
+        std::fs::copy(&sample, input_dir.join("a.png")).expect("failed to copy sample");
+        std::fs::copy(&sample, input_dir.join("nested/b.png")).expect("failed to copy sample");
+        std::fs::write(input_dir.join("broken.png"), b"not a png").expect("failed to write file");
+
+        let run_batch = || {
+            Command::new(get_binary_path())
+                .arg("--input")
+                .arg(&input_dir)
+                .arg("--output")
+                .arg(&output_dir)
+                .arg("--plugin")
+                .arg("mirror_plugin")
+                .arg("--plugin-path")
+                .arg(get_plugin_dir())
+                .arg("--params")
+                .arg(get_test_images_dir().join("mirror_params.json"))
+                .output()
+                .expect("failed to execute image_processor binary")
+        };
+
+        let output = run_batch();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(output.status.code(), Some(1), "stdout: {}", stdout);
+        assert!(stdout.contains("Processed: 2"), "stdout: {}", stdout);
+        assert!(stdout.contains("Failed:    1"), "stdout: {}", stdout);
+        assert!(stdout.contains("broken.png"), "stdout: {}", stdout);
+        assert!(output_dir.join("a.png").exists());
+        assert!(output_dir.join("nested/b.png").exists());
+
+        // Second run: the two good outputs are up to date.
+        let output = run_batch();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("Processed: 0"), "stdout: {}", stdout);
+        assert!(stdout.contains("Skipped:   2"), "stdout: {}", stdout);
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_batch_rerun_with_changed_param_regenerates_output() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let input_dir = temp_dir.path().join("in");
+        let output_dir = temp_dir.path().join("out");
+        std::fs::create_dir_all(&input_dir).expect("failed to create input dir");
+        std::fs::copy(
+            get_test_images_dir().join("sample.png"),
+            input_dir.join("a.png"),
+        )
+        .expect("failed to copy sample");
+
+        let run_batch = |radius: &str| {
+            let output = Command::new(get_binary_path())
+                .arg("--input")
+                .arg(&input_dir)
+                .arg("--output")
+                .arg(&output_dir)
+                .args(["--plugin", "blur_plugin"])
+                .args(["--param", &format!("radius={}", radius)])
+                .arg("--plugin-path")
+                .arg(get_plugin_dir())
+                .output()
+                .expect("failed to execute image_processor binary");
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(output.status.success(), "stdout: {}", stdout);
+            assert!(stdout.contains("Processed: 1"), "stdout: {}", stdout);
+            std::fs::read(output_dir.join("a.png")).expect("output should exist")
+        };
+
+        let narrow = run_batch("1");
+        let wide = run_batch("4");
+        assert_ne!(narrow, wide, "the rerun must apply the new radius");
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_batch_output_format_sets_extension() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let input_dir = temp_dir.path().join("in");
+        let output_dir = temp_dir.path().join("out");
+        std::fs::create_dir_all(&input_dir).expect("failed to create input dir");
+        std::fs::copy(
+            get_test_images_dir().join("sample.png"),
+            input_dir.join("a.png"),
+        )
+        .expect("failed to copy sample");
+
+        let output = Command::new(get_binary_path())
+            .arg("--input")
+            .arg(&input_dir)
+            .arg("--output")
+            .arg(&output_dir)
+            .args(["--output-format", "jpg"])
+            .args(["--plugin", "mirror_plugin"])
+            .arg("--params")
+            .arg(get_test_images_dir().join("mirror_params.json"))
+            .arg("--plugin-path")
+            .arg(get_plugin_dir())
+            .output()
+            .expect("failed to execute image_processor binary");
+
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(!output_dir.join("a.png").exists());
+        let bytes = std::fs::read(output_dir.join("a.jpg")).expect("output should be a.jpg");
+        assert_eq!(bytes[..3], [0xff, 0xd8, 0xff], "JPEG magic bytes");
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_batch_glob_selects_matching_files() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let input_dir = temp_dir.path().join("in");
+        let output_dir = temp_dir.path().join("out");
+        std::fs::create_dir_all(input_dir.join("keep")).expect("failed to create input dirs");
+        let sample = get_test_images_dir().join("sample.png");
+        std::fs::copy(&sample, input_dir.join("keep/a.png")).expect("failed to copy sample");
+        std::fs::copy(&sample, input_dir.join("skip.png")).expect("failed to copy sample");
+
+        let output = Command::new(get_binary_path())
+            .arg("--input")
+            .arg(input_dir.join("keep/*.png"))
+            .arg("--output")
+            .arg(&output_dir)
+            .arg("--plugin")
+            .arg("blur_plugin")
+            .arg("--plugin-path")
+            .arg(get_plugin_dir())
+            .arg("--params")
+            .arg(get_test_images_dir().join("blur_params.json"))
+            .output()
+            .expect("failed to execute image_processor binary");
+
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(output_dir.join("a.png").exists());
+        assert!(!output_dir.join("skip.png").exists());
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_parallel_pairs_match_sequential_output() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let sample = get_test_images_dir().join("sample.png");
+        let broken = temp_dir.path().join("broken.png");
+        std::fs::write(&broken, b"not a png").expect("failed to write file");
+
+        let run_pairs = |jobs: &str, prefix: &str| {
+            let mut command = Command::new(get_binary_path());
+            for (index, input) in [&sample, &broken, &sample].into_iter().enumerate() {
+                command
+                    .arg("--input")
+                    .arg(input)
+                    .arg("--output")
+                    .arg(temp_dir.path().join(format!("{}{}.png", prefix, index)));
+            }
+            command
+                .arg("--plugin")
+                .arg("blur_plugin")
+                .arg("--plugin-path")
+                .arg(get_plugin_dir())
+                .arg("--params")
+                .arg(get_test_images_dir().join("blur_params.json"))
+                .arg("--jobs")
+                .arg(jobs)
+                .output()
+                .expect("failed to execute image_processor binary")
+        };
+
+        let sequential = run_pairs("1", "seq");
+        let parallel = run_pairs("3", "par");
+
+        assert_eq!(parallel.status.code(), Some(1));
+        let stdout = String::from_utf8_lossy(&parallel.stdout);
+        assert!(stdout.contains("Processed: 2"), "stdout: {}", stdout);
+        assert!(stdout.contains("Failed:    1"), "stdout: {}", stdout);
+        assert_eq!(stdout, String::from_utf8_lossy(&sequential.stdout));
+        for index in [0, 2] {
+            let sequential_image = std::fs::read(temp_dir.path().join(format!("seq{}.png", index)))
+                .expect("sequential output should exist");
+            let parallel_image = std::fs::read(temp_dir.path().join(format!("par{}.png", index)))
+                .expect("parallel output should exist");
+            assert_eq!(sequential_image, parallel_image);
+        }
+        assert!(!temp_dir.path().join("par1.png").exists());
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_list_plugins_reports_invalid_libraries() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let junk_name = if cfg!(target_os = "windows") {
+            "junk_plugin.dll"
+        } else if cfg!(target_os = "macos") {
+            "libjunk_plugin.dylib"
+        } else {
+            "libjunk_plugin.so"
+        };
+        std::fs::write(temp_dir.path().join(junk_name), b"not a library")
+            .expect("failed to write file");
+
+        let output = Command::new(get_binary_path())
+            .arg("list-plugins")
+            .arg("--plugin-path")
+            .arg(get_plugin_dir())
+            .arg("--plugin-path")
+            .arg(temp_dir.path())
+            .output()
+            .expect("failed to execute image_processor binary");
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "stdout: {}", stdout);
+        assert!(
+            stdout.contains("blur_plugin 0.1.0 (ABI v12)"),
+            "stdout: {}",
+            stdout
+        );
+        assert!(
+            stdout.contains("Parameters: radius: integer = 1"),
+            "stdout: {}",
+            stdout
+        );
+        assert!(stdout.contains(junk_name), "stdout: {}", stdout);
+        assert!(stdout.contains("1 invalid"), "stdout: {}", stdout);
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_plugin_path_from_environment_is_logged_with_verbose() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let output_path = temp_dir.path().join("output.png");
+
+        let output = Command::new(get_binary_path())
+            .arg("-v")
+            .arg("--input")
+            .arg(get_test_images_dir().join("sample.png"))
+            .arg("--output")
+            .arg(&output_path)
+            .arg("--plugin")
+            .arg("mirror_plugin")
+            .arg("--params")
+            .arg(get_test_images_dir().join("mirror_params.json"))
+            .env("IMAGE_PROCESSOR_PLUGIN_PATH", get_plugin_dir())
+            .env_remove("RUST_LOG")
+            .output()
+            .expect("failed to execute image_processor binary");
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "stderr: {}", stderr);
+        assert!(
+            stderr.contains("Using plugin mirror_plugin from")
+                && stderr.contains("(IMAGE_PROCESSOR_PLUGIN_PATH)"),
+            "stderr: {}",
+            stderr
+        );
+        assert!(output_path.exists());
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_plugin_path_from_config_file() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let config_dir = temp_dir.path().join("image_processor");
+        std::fs::create_dir_all(&config_dir).expect("failed to create config directory");
+        let plugin_dir = get_plugin_dir()
+            .canonicalize()
+            .expect("plugin directory should exist");
+        let config = serde_json::json!({ "plugin_path": [plugin_dir] });
+        std::fs::write(config_dir.join("config.json"), config.to_string())
+            .expect("failed to write config");
+
+        let output = Command::new(get_binary_path())
+            .arg("info")
+            .arg("-v")
+            .arg("--plugin")
+            .arg("blur_plugin")
+            .env("XDG_CONFIG_HOME", temp_dir.path())
+            .env_remove("IMAGE_PROCESSOR_PLUGIN_PATH")
+            .env_remove("RUST_LOG")
+            .output()
+            .expect("failed to execute image_processor binary");
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "stderr: {}", stderr);
+        assert!(stderr.contains("(config file"), "stderr: {}", stderr);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("Name:        blur_plugin"));
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_missing_plugin_lists_searched_directories() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+
+        let output = Command::new(get_binary_path())
+            .arg("info")
+            .arg("--plugin")
+            .arg("nonexistent_plugin")
+            .arg("--plugin-path")
+            .arg(temp_dir.path())
+            .output()
+            .expect("failed to execute image_processor binary");
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success());
+        assert!(
+            stderr.contains("Plugin library not found"),
+            "stderr: {}",
+            stderr
+        );
+        assert!(stderr.contains("(--plugin-path)"), "stderr: {}", stderr);
+        assert!(
+            stderr.contains("(executable directory)"),
+            "stderr: {}",
+            stderr
+        );
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_inline_params_override_params_file() {
+        // The file asks for a vertical flip; the overrides turn it into a horizontal one.
+        let (expected, _expected_dir, expected_path) =
+            run_sample("mirror_plugin", r#"{"horizontal": true}"#, &[]);
+        let (output, _temp_dir, output_path) = run_sample(
+            "mirror_plugin",
+            r#"{"vertical": true}"#,
+            &[
+                "--params-json",
+                r#"{"vertical": false}"#,
+                "--param",
+                "horizontal=true",
+            ],
+        );
+
+        assert!(expected.status.success());
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let expected_img = image::open(&expected_path).expect("failed to open expected image");
+        let output_img = image::open(&output_path).expect("failed to open output image");
+        assert_eq!(output_img.to_rgba8(), expected_img.to_rgba8());
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_param_with_wrong_type_is_rejected() {
+        let (output, _temp_dir, output_path) =
+            run_sample("blur_plugin", "{}", &["--param", "radius=wide"]);
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success());
+        assert!(
+            stderr.contains("parameter 'radius' must be of type integer, got 'wide'"),
+            "stderr: {}",
+            stderr
+        );
+        assert!(!output_path.exists());
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_isolate_rejects_bad_params_before_processing() {
+        let (output, _temp_dir, output_path) =
+            run_sample("blur_plugin", r#"{"radius": "wide"}"#, &["--isolate", "-v"]);
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success());
+        assert!(
+            stderr.contains("Invalid parameters for plugin"),
+            "stderr: {}",
+            stderr
+        );
+        assert!(
+            !stderr.contains("isolated worker"),
+            "no worker should be started: {}",
+            stderr
+        );
+        assert!(!output_path.exists());
+
+        let (output, _temp_dir, _) = run_sample(
+            "blur_plugin",
+            "{}",
+            &["--isolate", "--param", "radius=wide"],
+        );
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success());
+        assert!(
+            stderr.contains("parameter 'radius' must be of type integer, got 'wide'"),
+            "stderr: {}",
+            stderr
+        );
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_stdin_to_stdout_matches_file_output() {
+        use std::io::Write;
+        use std::process::Stdio;
+
+        let (expected, _expected_dir, expected_path) =
+            run_sample("mirror_plugin", r#"{"horizontal": true}"#, &[]);
+        assert!(expected.status.success());
+
+        let mut child = Command::new(get_binary_path())
+            .args(["--input", "-", "--output", "-", "--output-format", "png"])
+            .args(["--plugin", "mirror_plugin", "--param", "horizontal=true"])
+            .arg("--plugin-path")
+            .arg(get_plugin_dir())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("failed to execute image_processor binary");
+        let input = std::fs::read(get_test_images_dir().join("sample.png"))
+            .expect("failed to read sample image");
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(&input)
+            .expect("failed to write stdin");
+        let output = child
+            .wait_with_output()
+            .expect("failed to wait for process");
+
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let piped_img =
+            image::load_from_memory(&output.stdout).expect("stdout should hold a PNG image");
+        let expected_img = image::open(&expected_path).expect("failed to open expected image");
+        assert_eq!(piped_img.to_rgba8(), expected_img.to_rgba8());
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_stdout_requires_output_format() {
+        let output = Command::new(get_binary_path())
+            .arg("--input")
+            .arg(get_test_images_dir().join("sample.png"))
+            .args(["--output", "-", "--plugin", "mirror_plugin"])
+            .arg("--plugin-path")
+            .arg(get_plugin_dir())
+            .output()
+            .expect("failed to execute image_processor binary");
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success());
+        assert!(
+            stderr.contains("--output-format is required"),
+            "stderr: {}",
+            stderr
+        );
+        assert!(output.stdout.is_empty());
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_output_format_and_jpeg_quality() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let encode = |quality: &str| {
+            // No extension: the format comes from --output-format alone
+            let output_path = temp_dir.path().join(format!("quality{}", quality));
+            let output = Command::new(get_binary_path())
+                .arg("--input")
+                .arg(get_test_images_dir().join("sample.png"))
+                .arg("--output")
+                .arg(&output_path)
+                .args(["--output-format", "jpg", "--jpeg-quality", quality])
+                .args(["--plugin", "blur_plugin"])
+                .arg("--plugin-path")
+                .arg(get_plugin_dir())
+                .output()
+                .expect("failed to execute image_processor binary");
+            assert!(
+                output.status.success(),
+                "stderr: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            std::fs::read(&output_path).expect("output should exist")
+        };
+
+        let low = encode("5");
+        let high = encode("100");
+
+        assert_eq!(
+            image::guess_format(&low).ok(),
+            Some(image::ImageFormat::Jpeg)
+        );
+        assert!(
+            low.len() < high.len(),
+            "quality 5 ({} bytes) should be smaller than quality 100 ({} bytes)",
+            low.len(),
+            high.len()
+        );
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_16_bit_image_keeps_its_depth() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let input_path = temp_dir.path().join("deep.png");
+        // Values that are not multiples of 257, so an 8-bit round trip would change them
+        let input = image::ImageBuffer::from_fn(3, 2, |x, y| {
+            image::Rgba([x as u16 * 1000 + 1, y as u16 * 3000 + 7, 12345, 65535])
+        });
+        image::DynamicImage::ImageRgba16(input.clone())
+            .save(&input_path)
+            .expect("failed to write input image");
+
+        for extra_args in [&[][..], &["--isolate"][..]] {
+            let output_path = temp_dir.path().join("flipped.png");
+            let output = Command::new(get_binary_path())
+                .arg("--input")
+                .arg(&input_path)
+                .arg("--output")
+                .arg(&output_path)
+                .args(["--plugin", "mirror_plugin"])
+                .args(["--params-json", r#"{"horizontal": true}"#])
+                .arg("--plugin-path")
+                .arg(get_plugin_dir())
+                .args(extra_args)
+                .output()
+                .expect("failed to execute image_processor binary");
+            assert!(
+                output.status.success(),
+                "stderr: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+
+            let flipped = image::open(&output_path).expect("output should be a valid image");
+            assert_eq!(
+                flipped.color(),
+                image::ColorType::Rgba16,
+                "{:?}",
+                extra_args
+            );
+            assert_eq!(
+                flipped.into_rgba16(),
+                image::imageops::flip_horizontal(&input),
+                "{:?}",
+                extra_args
+            );
+        }
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_grayscale_image_stays_single_channel() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let input_path = temp_dir.path().join("mask.png");
+        let input = image::GrayImage::from_fn(4, 3, |x, y| image::Luma([(x * 60 + y) as u8]));
+        input
+            .save(&input_path)
+            .expect("failed to write input image");
+
+        for extra_args in [&[][..], &["--isolate"][..]] {
+            let output_path = temp_dir.path().join("flipped.png");
+            let output = Command::new(get_binary_path())
+                .arg("--input")
+                .arg(&input_path)
+                .arg("--output")
+                .arg(&output_path)
+                .args(["--plugin", "mirror_plugin"])
+                .args(["--params-json", r#"{"vertical": true}"#])
+                .arg("--plugin-path")
+                .arg(get_plugin_dir())
+                .args(extra_args)
+                .output()
+                .expect("failed to execute image_processor binary");
+            assert!(
+                output.status.success(),
+                "stderr: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+
+            let flipped = image::open(&output_path).expect("output should be a valid image");
+            assert_eq!(flipped.color(), image::ColorType::L8, "{:?}", extra_args);
+            assert_eq!(
+                flipped.into_luma8(),
+                image::imageops::flip_vertical(&input),
+                "{:?}",
+                extra_args
+            );
+        }
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_region_limits_the_plugin_to_a_rectangle() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let input_path = temp_dir.path().join("stripes.png");
+        let input = image::RgbImage::from_fn(8, 6, |x, _| {
+            if x % 2 == 0 {
+                image::Rgb([0, 0, 0])
+            } else {
+                image::Rgb([255, 255, 255])
+            }
+        });
+        input
+            .save(&input_path)
+            .expect("failed to write input image");
+
+        for extra_args in [&[][..], &["--isolate"][..]] {
+            let output_path = temp_dir.path().join("blurred.png");
+            let output = Command::new(get_binary_path())
+                .arg("--input")
+                .arg(&input_path)
+                .arg("--output")
+                .arg(&output_path)
+                .args(["--plugin", "blur_plugin"])
+                .args(["--region", "2,1,4,3"])
+                .arg("--plugin-path")
+                .arg(get_plugin_dir())
+                .args(extra_args)
+                .output()
+                .expect("failed to execute image_processor binary");
+            assert!(
+                output.status.success(),
+                "stderr: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+
+            let blurred = image::open(&output_path)
+                .expect("output should be a valid image")
+                .into_rgb8();
+            for (x, y, pixel) in blurred.enumerate_pixels() {
+                let inside = (2..6).contains(&x) && (1..4).contains(&y);
+                assert_eq!(
+                    pixel == input.get_pixel(x, y),
+                    !inside,
+                    "pixel {},{} {:?}",
+                    x,
+                    y,
+                    extra_args
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_region_outside_image_fails() {
+        let (output, _temp_dir, output_path) =
+            run_sample("mirror_plugin", "{}", &["--region", "0,0,100000,1"]);
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("does not fit in the"), "stderr: {}", stderr);
+        assert!(!output_path.exists());
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_tiles_match_whole_image_blur() {
+        let temp_dir = TempDir::new().expect("failed to create temp directory");
+        let input_path = temp_dir.path().join("noise.png");
+        image::RgbImage::from_fn(20, 15, |x, y| {
+            let value = ((x * 37 + y * 91) % 256) as u8;
+            image::Rgb([value, 255 - value, value / 2])
+        })
+        .save(&input_path)
+        .expect("failed to write input image");
+
+        let blur = |output_path: &Path, extra_args: &[&str]| {
+            let output = Command::new(get_binary_path())
+                .arg("--input")
+                .arg(&input_path)
+                .arg("--output")
+                .arg(output_path)
+                .args(["--plugin", "blur_plugin"])
+                .args(["--params-json", r#"{"radius":2,"iterations":2}"#])
+                .arg("--plugin-path")
+                .arg(get_plugin_dir())
+                .args(extra_args)
+                .output()
+                .expect("failed to execute image_processor binary");
+            assert!(
+                output.status.success(),
+                "stderr: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            image::open(output_path)
+                .expect("output should be a valid image")
+                .into_rgb8()
+        };
+
+        let whole = blur(&temp_dir.path().join("whole.png"), &[]);
+        let tiled = blur(&temp_dir.path().join("tiled.png"), &["--tile-size", "6"]);
+        assert_eq!(tiled, whole);
+
+        let isolated = blur(
+            &temp_dir.path().join("isolated.bmp"),
+            &["--tile-size", "7", "--tile-overlap", "4", "--isolate"],
+        );
+        assert_eq!(isolated, whole);
+    }
+
+    #[test]
+    #[ignore] // Requires: cargo build --all
+    fn test_tiles_need_plugins_that_declare_a_halo() {
+        let (output, _temp_dir, output_path) =
+            run_sample("mirror_plugin", "{}", &["--tile-size", "64"]);
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("cannot process tiles"),
+            "stderr: {}",
+            stderr
+        );
+        assert!(!output_path.exists());
+    }
 }
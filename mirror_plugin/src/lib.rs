@@ -1,16 +1,161 @@
-use log::error;
+use log::{LevelFilter, Log, Metadata, Record, debug, error};
 use serde::Deserialize;
-use std::ffi::{CStr, c_char};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{self, UnwindSafe};
+use std::sync::{Mutex, PoisonError};
 
 /// Error codes returned by the mirror plugin.
+///
+/// Values match the well-known codes shared by all plugins (see `describe_error_code`
+/// in `image_processor`'s plugin loader). The human-readable reason for a failure is
+/// available through `plugin_last_error`.
 #[repr(i32)]
 pub enum MirrorError {
     Success = 0,
     ParseError = -1,
     SizeOverflow = -2,
+    Panic = -4,
+    UnsupportedFormat = -5,
+    InvalidStride = -6,
+}
+
+/// Version of the host/plugin ABI this plugin implements.
+/// Must match a version supported by `image_processor`'s plugin loader.
+const PLUGIN_ABI_VERSION: u32 = 12;
+
+/// Pixel format codes of `process_pixels`. Must match `PixelFormat` in `image_processor`.
+const PIXEL_FORMAT_RGBA8: u32 = 0;
+const PIXEL_FORMAT_RGBA16: u32 = 1;
+const PIXEL_FORMAT_RGBA32F: u32 = 2;
+const PIXEL_FORMAT_L8: u32 = 3;
+const PIXEL_FORMAT_LA8: u32 = 4;
+const PIXEL_FORMAT_RGB8: u32 = 5;
+
+/// Returns the size of one pixel in a format, or `None` for an unknown format code.
+fn bytes_per_pixel(format: u32) -> Option<usize> {
+    match format {
+        PIXEL_FORMAT_L8 => Some(1),
+        PIXEL_FORMAT_LA8 => Some(2),
+        PIXEL_FORMAT_RGB8 => Some(3),
+        PIXEL_FORMAT_RGBA8 => Some(4),
+        PIXEL_FORMAT_RGBA16 => Some(8),
+        PIXEL_FORMAT_RGBA32F => Some(16),
+        _ => None,
+    }
+}
+
+thread_local! {
+    /// Message describing the last failed `process_image` call on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
 }
 
+/// Host callback receiving one `log` record: its level (1 = error to 5 = trace), target and
+/// message as null-terminated strings.
+type LogCallback = unsafe extern "C" fn(u32, *const c_char, *const c_char);
+
+/// Log callback the host installed. Unlike the other callbacks it is shared by all threads,
+/// since `log` has a single global logger.
+static HOST_LOG: Mutex<Option<LogCallback>> = Mutex::new(None);
+
+/// `log` backend that forwards records to the host's logger.
+struct HostLogger;
+
+impl Log for HostLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        let callback = *HOST_LOG.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(callback) = callback.filter(|_| self.enabled(record.metadata())) else {
+            return;
+        };
+        // Interior null bytes cannot be represented in a C string; drop them.
+        let target = CString::new(record.target().replace('\0', "")).unwrap_or_default();
+        let message = CString::new(record.args().to_string().replace('\0', "")).unwrap_or_default();
+        // SAFETY: The host keeps the callback valid for as long as the library is loaded, and
+        // both strings outlive the call.
+        unsafe { callback(record.level() as u32, target.as_ptr(), message.as_ptr()) };
+    }
+
+    fn flush(&self) {}
+}
+
+/// Logs an error, records it for `plugin_last_error` and returns its code.
+fn fail(code: MirrorError, message: String) -> i32 {
+    error!("{}", message);
+    // Interior null bytes cannot be represented in a C string; drop them.
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+    code as i32
+}
+
+/// Runs `body`, converting a panic into `MirrorError::Panic` so it never unwinds into the host.
+fn catch_panic(body: impl FnOnce() -> i32 + UnwindSafe) -> i32 {
+    match panic::catch_unwind(body) {
+        Ok(code) => code,
+        Err(payload) => {
+            let reason = payload
+                .downcast_ref::<&str>()
+                .map(|reason| reason.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic payload".to_string());
+            fail(MirrorError::Panic, format!("panicked: {}", reason))
+        }
+    }
+}
+
+/// Static plugin metadata returned by `plugin_info`.
+/// Layout must match `RawPluginInfo` in `image_processor`'s plugin loader.
+#[repr(C)]
+pub struct PluginInfo {
+    pub name: *const c_char,
+    pub version: *const c_char,
+    pub description: *const c_char,
+    pub author: *const c_char,
+    /// Comma-separated list of supported operations.
+    pub operations: *const c_char,
+}
+
+// SAFETY: PluginInfo only points to immutable 'static string literals, so sharing it
+// between threads is sound.
+unsafe impl Sync for PluginInfo {}
+
+static PLUGIN_INFO: PluginInfo = PluginInfo {
+    name: concat!(env!("CARGO_PKG_NAME"), "\0").as_ptr().cast(),
+    version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast(),
+    description: concat!(env!("CARGO_PKG_DESCRIPTION"), "\0").as_ptr().cast(),
+    author: concat!(env!("CARGO_PKG_AUTHORS"), "\0").as_ptr().cast(),
+    operations: c"mirror_horizontal,mirror_vertical".as_ptr(),
+};
+
+/// JSON Schema describing `Params`, returned by `plugin_params_schema`.
+/// Keep in sync with the struct below.
+const PARAMS_SCHEMA: &str = concat!(
+    r#"{
+    "$schema": "https://json-schema.org/draft/2020-12/schema",
+    "title": "mirror_plugin parameters",
+    "type": "object",
+    "properties": {
+        "horizontal": {
+            "type": "boolean",
+            "default": false,
+            "description": "Flip the image left to right"
+        },
+        "vertical": {
+            "type": "boolean",
+            "default": false,
+            "description": "Flip the image top to bottom"
+        }
+    },
+    "additionalProperties": false
+}"#,
+    "\0"
+);
+
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 struct Params {
     #[serde(default)]
     horizontal: bool,
@@ -18,6 +163,65 @@ struct Params {
     vertical: bool,
 }
 
+/// Returns the plugin ABI version. The host checks this before resolving any other symbol.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+/// Returns a pointer to the plugin's static metadata descriptor.
+/// The pointer stays valid for as long as the library is loaded.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_info() -> *const PluginInfo {
+    &PLUGIN_INFO
+}
+
+/// Returns the JSON Schema of the plugin's parameters as a static null-terminated string.
+/// The pointer stays valid for as long as the library is loaded.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_params_schema() -> *const c_char {
+    PARAMS_SCHEMA.as_ptr().cast()
+}
+
+/// Returns the pixel formats `process_pixels` and `process_strided` accept, one bit (`1 << code`) per format.
+/// Flipping only moves whole pixels, so every format works.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_pixel_formats() -> u32 {
+    1 << PIXEL_FORMAT_RGBA8
+        | 1 << PIXEL_FORMAT_RGBA16
+        | 1 << PIXEL_FORMAT_RGBA32F
+        | 1 << PIXEL_FORMAT_L8
+        | 1 << PIXEL_FORMAT_LA8
+        | 1 << PIXEL_FORMAT_RGB8
+}
+
+/// Returns the message describing why the last `process_image` call on the calling thread
+/// failed, or null if it succeeded. The pointer stays valid until the next `process_image`
+/// call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Sends this plugin's `log` records up to `max_level` (0 = off, 1 = error to 5 = trace) to
+/// the host's `callback`, or drops them again when `callback` is null.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_set_log_callback(callback: Option<LogCallback>, max_level: u32) {
+    *HOST_LOG.lock().unwrap_or_else(PoisonError::into_inner) = callback;
+    // Only the first call installs the logger; later ones replace the callback and level
+    let _ = log::set_logger(&HostLogger);
+    log::set_max_level(
+        LevelFilter::iter()
+            .nth(max_level as usize)
+            .unwrap_or(LevelFilter::Trace),
+    );
+}
+
 /// Processes an image by applying horizontal and/or vertical flip transformations.
 ///
 /// # Safety
@@ -32,6 +236,89 @@ pub unsafe extern "C" fn process_image(
     height: u32,
     rgba_data: *mut u8,
     params: *const c_char,
+) -> i32 {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+
+    // SAFETY: The arguments are forwarded unchanged, so the caller's guarantees for
+    // process_image carry over to apply_mirror.
+    catch_panic(|| unsafe { apply_mirror(width, height, 4, None, rgba_data, params) })
+}
+
+/// Processes an image in any format reported by `plugin_pixel_formats`.
+///
+/// # Safety
+///
+/// The caller must ensure:
+/// - `data` is a valid pointer to a buffer of exactly `width * height` pixels of `format`
+/// - `params` is a valid null-terminated C string
+/// - The buffer remains valid for the duration of this call
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn process_pixels(
+    width: u32,
+    height: u32,
+    format: u32,
+    data: *mut u8,
+    params: *const c_char,
+) -> i32 {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+
+    let Some(pixel_size) = bytes_per_pixel(format) else {
+        return fail(
+            MirrorError::UnsupportedFormat,
+            format!("unsupported pixel format code {}", format),
+        );
+    };
+    // SAFETY: The arguments are forwarded unchanged and pixel_size matches `format`, so the
+    // caller's guarantees for process_pixels carry over to apply_mirror.
+    catch_panic(|| unsafe { apply_mirror(width, height, pixel_size, None, data, params) })
+}
+
+/// Processes an image whose rows start `stride` bytes apart, in any format reported by
+/// `plugin_pixel_formats`.
+///
+/// # Safety
+///
+/// The caller must ensure:
+/// - `data` is a valid pointer to `height` rows of `width` pixels of `format`, each row
+///   starting `stride` bytes after the previous one (`stride * (height - 1) + width * bytes
+///   per pixel` bytes in total); the bytes between rows are left untouched
+/// - `params` is a valid null-terminated C string
+/// - The buffer remains valid for the duration of this call
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn process_strided(
+    width: u32,
+    height: u32,
+    format: u32,
+    stride: usize,
+    data: *mut u8,
+    params: *const c_char,
+) -> i32 {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+
+    let Some(pixel_size) = bytes_per_pixel(format) else {
+        return fail(
+            MirrorError::UnsupportedFormat,
+            format!("unsupported pixel format code {}", format),
+        );
+    };
+    // SAFETY: The arguments are forwarded unchanged and pixel_size matches `format`, so the
+    // caller's guarantees for process_strided carry over to apply_mirror.
+    catch_panic(|| unsafe { apply_mirror(width, height, pixel_size, Some(stride), data, params) })
+}
+
+/// Flips an image of `pixel_size`-byte pixels in place. Rows start `stride` bytes apart, or
+/// are tightly packed for `None`. Panics are caught by the callers.
+///
+/// # Safety
+///
+/// Same contract as `process_strided`, with packed rows for a `None` stride.
+unsafe fn apply_mirror(
+    width: u32,
+    height: u32,
+    pixel_size: usize,
+    stride: Option<usize>,
+    rgba_data: *mut u8,
+    params: *const c_char,
 ) -> i32 {
     // SAFETY: params is a valid null-terminated C string passed by the host.
     // The plugin loader guarantees this pointer is valid for the duration of this call.
@@ -41,10 +328,16 @@ pub unsafe extern "C" fn process_image(
     let params: Params = match serde_json::from_str(params_str) {
         Ok(p) => p,
         Err(e) => {
-            error!("mirror_plugin: failed to parse params JSON: {}", e);
-            return MirrorError::ParseError as i32;
+            return fail(
+                MirrorError::ParseError,
+                format!("failed to parse params JSON: {}", e),
+            );
         }
     };
+    debug!(
+        "Flipping {}x{} image (horizontal={}, vertical={})",
+        width, height, params.horizontal, params.vertical
+    );
 
     // Early return if no flip requested
     if !params.horizontal && !params.vertical {
@@ -53,30 +346,49 @@ pub unsafe extern "C" fn process_image(
 
     let width_usize = width as usize;
     let height_usize = height as usize;
-    let len = match width_usize
-        .checked_mul(height_usize)
-        .and_then(|n| n.checked_mul(4))
+    let Some(row_bytes) = width_usize.checked_mul(pixel_size) else {
+        return fail(
+            MirrorError::SizeOverflow,
+            "size overflow calculating row bytes".to_string(),
+        );
+    };
+    let stride = stride.unwrap_or(row_bytes);
+    if stride < row_bytes {
+        return fail(
+            MirrorError::InvalidStride,
+            format!(
+                "stride {} is shorter than a row of {} bytes",
+                stride, row_bytes
+            ),
+        );
+    }
+    let len = match stride
+        .checked_mul(height_usize.saturating_sub(1))
+        .and_then(|n| n.checked_add(if height == 0 { 0 } else { row_bytes }))
     {
         Some(len) => len,
         None => {
-            error!("mirror_plugin: size overflow calculating buffer length");
-            return MirrorError::SizeOverflow as i32;
+            return fail(
+                MirrorError::SizeOverflow,
+                "size overflow calculating buffer length".to_string(),
+            );
         }
     };
 
-    // SAFETY: rgba_data is a valid pointer to a buffer of exactly width * height * 4 bytes,
-    // owned by the host. The plugin loader guarantees this buffer is valid and properly
-    // aligned for the duration of this call. We only access indices within bounds.
+    // SAFETY: rgba_data is a valid pointer to a buffer of exactly len bytes,
+    // owned by the host. The plugin loader guarantees this buffer is valid for the duration
+    // of this call. We only access indices within bounds and never touch the bytes between
+    // rows.
     let data = unsafe { std::slice::from_raw_parts_mut(rgba_data, len) };
 
     // Horizontal flip: swap pixels within each row
     if params.horizontal {
         for y in 0..height_usize {
             for x in 0..width_usize / 2 {
-                let left_idx = (y * width_usize + x) * 4;
-                let right_idx = (y * width_usize + (width_usize - 1 - x)) * 4;
-                // Swap 4 bytes (RGBA) at a time
-                for i in 0..4 {
+                let left_idx = y * stride + x * pixel_size;
+                let right_idx = y * stride + (width_usize - 1 - x) * pixel_size;
+                // Swap one whole pixel at a time
+                for i in 0..pixel_size {
                     data.swap(left_idx + i, right_idx + i);
                 }
             }
@@ -85,16 +397,9 @@ pub unsafe extern "C" fn process_image(
 
     // Vertical flip: swap rows
     if params.vertical {
-        let row_bytes = match width_usize.checked_mul(4) {
-            Some(rb) => rb,
-            None => {
-                error!("mirror_plugin: size overflow calculating row bytes");
-                return MirrorError::SizeOverflow as i32;
-            }
-        };
         for y in 0..height_usize / 2 {
-            let top_start = y * row_bytes;
-            let bottom_start = (height_usize - 1 - y) * row_bytes;
+            let top_start = y * stride;
+            let bottom_start = (height_usize - 1 - y) * stride;
             // Swap each byte in the row
             for i in 0..row_bytes {
                 data.swap(top_start + i, bottom_start + i);
@@ -356,4 +661,188 @@ mod tests {
         );
         assert_eq!(result, MirrorError::Success as i32);
     }
+
+    #[test]
+    fn test_process_pixels_flips_wide_pixels() {
+        // 2x1 RGBA16 image: each pixel is 8 bytes
+        let left: Vec<u8> = (0..8).collect();
+        let right: Vec<u8> = (8..16).collect();
+        let mut data = [left.clone(), right.clone()].concat();
+        let params = CString::new(r#"{"horizontal": true}"#).expect("CString creation failed");
+
+        // SAFETY: data holds 2 * 1 RGBA16 pixels and params is a valid C string.
+        let result = unsafe {
+            process_pixels(
+                2,
+                1,
+                PIXEL_FORMAT_RGBA16,
+                data.as_mut_ptr(),
+                params.as_ptr(),
+            )
+        };
+
+        assert_eq!(result, MirrorError::Success as i32);
+        assert_eq!(data, [right, left].concat());
+    }
+
+    #[test]
+    fn test_process_strided_leaves_padding_untouched() {
+        // 2x2 L8 image in rows of 3 bytes: the third byte of each row is padding
+        let mut data = vec![1, 2, 99, 3, 4];
+        let params = CString::new(r#"{"horizontal": true, "vertical": true}"#)
+            .expect("CString creation failed");
+
+        // SAFETY: data holds 2 rows of 2 L8 pixels, 3 bytes apart, and params is a valid C
+        // string.
+        let result = unsafe {
+            process_strided(2, 2, PIXEL_FORMAT_L8, 3, data.as_mut_ptr(), params.as_ptr())
+        };
+
+        assert_eq!(result, MirrorError::Success as i32);
+        assert_eq!(data, vec![4, 3, 99, 2, 1]);
+    }
+
+    #[test]
+    fn test_process_strided_rejects_short_stride() {
+        let mut data = vec![0u8; 8];
+        let params = CString::new(r#"{"horizontal": true}"#).expect("CString creation failed");
+
+        // SAFETY: The plugin rejects the stride before touching the buffer.
+        let result = unsafe {
+            process_strided(
+                2,
+                1,
+                PIXEL_FORMAT_RGBA8,
+                4,
+                data.as_mut_ptr(),
+                params.as_ptr(),
+            )
+        };
+
+        assert_eq!(result, MirrorError::InvalidStride as i32);
+    }
+
+    #[test]
+    fn test_process_pixels_rejects_unknown_format() {
+        let mut data = vec![0u8; 4];
+        let params = CString::new("{}").expect("CString creation failed");
+
+        // SAFETY: The plugin rejects the format before touching the buffer.
+        let result = unsafe { process_pixels(1, 1, 99, data.as_mut_ptr(), params.as_ptr()) };
+
+        assert_eq!(result, MirrorError::UnsupportedFormat as i32);
+        assert_eq!(plugin_pixel_formats(), 0b11_1111);
+    }
+
+    #[test]
+    fn test_plugin_abi_version() {
+        assert_eq!(plugin_abi_version(), PLUGIN_ABI_VERSION);
+    }
+
+    #[test]
+    fn test_plugin_info_descriptor() {
+        // SAFETY: plugin_info returns a pointer to a 'static descriptor whose fields are
+        // null-terminated string literals.
+        let (name, operations) = unsafe {
+            let info = &*plugin_info();
+            (
+                CStr::from_ptr(info.name).to_str().expect("valid UTF-8"),
+                CStr::from_ptr(info.operations)
+                    .to_str()
+                    .expect("valid UTF-8"),
+            )
+        };
+
+        assert_eq!(name, "mirror_plugin");
+        assert_eq!(operations, "mirror_horizontal,mirror_vertical");
+    }
+
+    #[test]
+    fn test_params_schema_is_valid_json() {
+        // SAFETY: plugin_params_schema returns a pointer to a static null-terminated string.
+        let schema = unsafe { CStr::from_ptr(plugin_params_schema()) }
+            .to_str()
+            .expect("valid UTF-8");
+        let schema: serde_json::Value = serde_json::from_str(schema).expect("valid JSON");
+
+        let properties = schema["properties"].as_object().expect("properties object");
+        assert_eq!(
+            properties.len(),
+            2,
+            "schema must declare every Params field"
+        );
+        assert_eq!(schema["additionalProperties"], false);
+    }
+
+    #[test]
+    fn test_unknown_param_rejected() {
+        let mut data = create_4x4_test_image();
+        let original = data.clone();
+
+        let result = call_process_image(4, 4, &mut data, r#"{"horizontl": true}"#);
+
+        assert_eq!(result, MirrorError::ParseError as i32);
+        assert_eq!(data, original);
+    }
+
+    fn last_error() -> Option<String> {
+        let ptr = plugin_last_error();
+        if ptr.is_null() {
+            return None;
+        }
+        // SAFETY: ptr is non-null and points to the thread-local CString, which is not
+        // modified until the next process_image call.
+        Some(
+            unsafe { CStr::from_ptr(ptr) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+
+    #[test]
+    fn test_last_error_reports_parse_failure() {
+        let mut data = create_4x4_test_image();
+        let result = call_process_image(4, 4, &mut data, "not valid json {{{");
+
+        assert_eq!(result, MirrorError::ParseError as i32);
+        let message = last_error().expect("failure should set last error");
+        assert!(
+            message.contains("failed to parse params JSON"),
+            "unexpected message: {}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_last_error_cleared_on_success() {
+        let mut data = create_4x4_test_image();
+        call_process_image(4, 4, &mut data, "not valid json {{{");
+        assert!(last_error().is_some());
+
+        let result = call_process_image(4, 4, &mut data, "{}");
+
+        assert_eq!(result, MirrorError::Success as i32);
+        assert!(last_error().is_none());
+    }
+
+    #[test]
+    fn test_catch_panic_converts_panic_to_error_code() {
+        let result = catch_panic(|| panic!("index out of bounds"));
+
+        assert_eq!(result, MirrorError::Panic as i32);
+        let message = last_error().expect("panic should set last error");
+        assert!(
+            message.contains("panicked: index out of bounds"),
+            "unexpected message: {}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_catch_panic_passes_through_result() {
+        assert_eq!(
+            catch_panic(|| MirrorError::Success as i32),
+            MirrorError::Success as i32
+        );
+    }
 }
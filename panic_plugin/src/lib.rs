@@ -0,0 +1,340 @@
+//! Test plugin used by `image_processor` to prove that a misbehaving plugin cannot take the
+//! host down. By default `process_image` panics, which the standard panic guard turns into
+//! `PanicError::Panic`; the other modes crash, hang or allocate for `--isolate` tests, or
+//! wait to be cancelled.
+
+use log::{LevelFilter, Log, Metadata, Record, error};
+use serde::Deserialize;
+use std::cell::{Cell, RefCell};
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::panic::{self, UnwindSafe};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+/// Error codes returned by the panic plugin.
+#[repr(i32)]
+pub enum PanicError {
+    Success = 0,
+    ParseError = -1,
+    Panic = -4,
+    Cancelled = -7,
+}
+
+/// How `process_image` should misbehave.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum Mode {
+    /// Panic inside the panic guard.
+    #[default]
+    Panic,
+    /// Abort the process, which only `--isolate` survives.
+    Abort,
+    /// Never return, to exercise timeouts.
+    Hang,
+    /// Return only once the host cancels the call, to exercise cooperative cancellation.
+    Wait,
+    /// Allocate and touch `megabytes` of memory, to exercise memory limits.
+    Allocate,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Params {
+    #[serde(default)]
+    mode: Mode,
+    #[serde(default = "default_megabytes")]
+    megabytes: usize,
+}
+
+fn default_megabytes() -> usize {
+    1024
+}
+
+/// Version of the host/plugin ABI this plugin implements.
+/// Must match a version supported by `image_processor`'s plugin loader.
+const PLUGIN_ABI_VERSION: u32 = 12;
+
+/// Host callback returning true once the current call should stop, with the host's context.
+type CancelCallback = unsafe extern "C" fn(*mut c_void) -> bool;
+
+thread_local! {
+    /// Message describing the last failed `process_image` call on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+
+    /// Cancellation callback the host installed for calls on this thread.
+    static CANCEL: Cell<Option<(CancelCallback, *mut c_void)>> = const { Cell::new(None) };
+}
+
+/// Host callback receiving one `log` record: its level (1 = error to 5 = trace), target and
+/// message as null-terminated strings.
+type LogCallback = unsafe extern "C" fn(u32, *const c_char, *const c_char);
+
+/// Log callback the host installed. Unlike the other callbacks it is shared by all threads,
+/// since `log` has a single global logger.
+static HOST_LOG: Mutex<Option<LogCallback>> = Mutex::new(None);
+
+/// `log` backend that forwards records to the host's logger.
+struct HostLogger;
+
+impl Log for HostLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        let callback = *HOST_LOG.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(callback) = callback.filter(|_| self.enabled(record.metadata())) else {
+            return;
+        };
+        // Interior null bytes cannot be represented in a C string; drop them.
+        let target = CString::new(record.target().replace('\0', "")).unwrap_or_default();
+        let message = CString::new(record.args().to_string().replace('\0', "")).unwrap_or_default();
+        // SAFETY: The host keeps the callback valid for as long as the library is loaded, and
+        // both strings outlive the call.
+        unsafe { callback(record.level() as u32, target.as_ptr(), message.as_ptr()) };
+    }
+
+    fn flush(&self) {}
+}
+
+/// Logs an error, records it for `plugin_last_error` and returns its code.
+fn fail(code: PanicError, message: String) -> i32 {
+    error!("{}", message);
+    // Interior null bytes cannot be represented in a C string; drop them.
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+    code as i32
+}
+
+/// Runs `body`, converting a panic into `PanicError::Panic` so it never unwinds into the host.
+fn catch_panic(body: impl FnOnce() -> i32 + UnwindSafe) -> i32 {
+    match panic::catch_unwind(body) {
+        Ok(code) => code,
+        Err(payload) => {
+            let reason = payload
+                .downcast_ref::<&str>()
+                .map(|reason| reason.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic payload".to_string());
+            fail(PanicError::Panic, format!("panicked: {}", reason))
+        }
+    }
+}
+
+/// Static plugin metadata returned by `plugin_info`.
+/// Layout must match `RawPluginInfo` in `image_processor`'s plugin loader.
+#[repr(C)]
+pub struct PluginInfo {
+    pub name: *const c_char,
+    pub version: *const c_char,
+    pub description: *const c_char,
+    pub author: *const c_char,
+    /// Comma-separated list of supported operations.
+    pub operations: *const c_char,
+}
+
+// SAFETY: PluginInfo only points to immutable 'static string literals, so sharing it
+// between threads is sound.
+unsafe impl Sync for PluginInfo {}
+
+static PLUGIN_INFO: PluginInfo = PluginInfo {
+    name: concat!(env!("CARGO_PKG_NAME"), "\0").as_ptr().cast(),
+    version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast(),
+    description: concat!(env!("CARGO_PKG_DESCRIPTION"), "\0").as_ptr().cast(),
+    author: concat!(env!("CARGO_PKG_AUTHORS"), "\0").as_ptr().cast(),
+    operations: c"panic".as_ptr(),
+};
+
+/// JSON Schema describing `Params`, returned by `plugin_params_schema`.
+/// Keep in sync with the struct above.
+const PARAMS_SCHEMA: &str = concat!(
+    r#"{
+    "$schema": "https://json-schema.org/draft/2020-12/schema",
+    "title": "panic_plugin parameters",
+    "type": "object",
+    "properties": {
+        "mode": {
+            "type": "string",
+            "enum": ["panic", "abort", "hang", "wait", "allocate"],
+            "default": "panic",
+            "description": "How to misbehave"
+        },
+        "megabytes": {
+            "type": "integer",
+            "minimum": 0,
+            "default": 1024,
+            "description": "Memory to allocate in allocate mode"
+        }
+    },
+    "additionalProperties": false
+}"#,
+    "\0"
+);
+
+/// Returns the plugin ABI version. The host checks this before resolving any other symbol.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+/// Returns a pointer to the plugin's static metadata descriptor.
+/// The pointer stays valid for as long as the library is loaded.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_info() -> *const PluginInfo {
+    &PLUGIN_INFO
+}
+
+/// Returns the JSON Schema of the plugin's parameters as a static null-terminated string.
+/// The pointer stays valid for as long as the library is loaded.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_params_schema() -> *const c_char {
+    PARAMS_SCHEMA.as_ptr().cast()
+}
+
+/// Returns the message describing why the last `process_image` call on the calling thread
+/// failed, or null if it succeeded. The pointer stays valid until the next `process_image`
+/// call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Sends this plugin's `log` records up to `max_level` (0 = off, 1 = error to 5 = trace) to
+/// the host's `callback`, or drops them again when `callback` is null.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_set_log_callback(callback: Option<LogCallback>, max_level: u32) {
+    *HOST_LOG.lock().unwrap_or_else(PoisonError::into_inner) = callback;
+    // Only the first call installs the logger; later ones replace the callback and level
+    let _ = log::set_logger(&HostLogger);
+    log::set_max_level(
+        LevelFilter::iter()
+            .nth(max_level as usize)
+            .unwrap_or(LevelFilter::Trace),
+    );
+}
+
+/// Installs the host's cancellation callback for calls on the calling thread, or removes it
+/// when `callback` is null. `context` is passed back to every call of `callback`.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_set_cancel_callback(
+    callback: Option<CancelCallback>,
+    context: *mut c_void,
+) {
+    CANCEL.with(|cancel| cancel.set(callback.map(|callback| (callback, context))));
+}
+
+/// Asks the host whether the current call should stop.
+fn cancelled() -> bool {
+    CANCEL.with(Cell::get).is_some_and(|(callback, context)| {
+        // SAFETY: The host keeps the callback and its context valid for as long as they are
+        // installed, and this runs on the thread they were installed for.
+        unsafe { callback(context) }
+    })
+}
+
+/// Misbehaves according to the `mode` parameter without touching the image.
+///
+/// # Safety
+///
+/// The caller must ensure `params` is a valid null-terminated C string. The image buffer is
+/// never dereferenced.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn process_image(
+    _width: u32,
+    _height: u32,
+    _rgba_data: *mut u8,
+    params: *const c_char,
+) -> i32 {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+
+    // SAFETY: params is a valid null-terminated C string passed by the host.
+    let params_str = unsafe { CStr::from_ptr(params) }.to_str().unwrap_or("");
+    let params: Params = match serde_json::from_str(params_str) {
+        Ok(p) => p,
+        Err(e) => {
+            return fail(
+                PanicError::ParseError,
+                format!("failed to parse params JSON: {}", e),
+            );
+        }
+    };
+
+    catch_panic(|| match params.mode {
+        Mode::Panic => panic!("deliberate panic for testing"),
+        Mode::Abort => std::process::abort(),
+        Mode::Hang => loop {
+            std::thread::sleep(Duration::from_secs(1));
+        },
+        Mode::Wait => {
+            while !cancelled() {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            fail(PanicError::Cancelled, "cancelled by the host".to_string())
+        }
+        Mode::Allocate => {
+            let memory = vec![1u8; params.megabytes * 1024 * 1024];
+            std::hint::black_box(&memory);
+            PanicError::Success as i32
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_image_reports_panic() {
+        let mut data = vec![0u8; 4];
+        let params = CString::new("{}").expect("CString creation failed");
+
+        // SAFETY: data and params are valid for the duration of the call.
+        let result = unsafe { process_image(1, 1, data.as_mut_ptr(), params.as_ptr()) };
+
+        assert_eq!(result, PanicError::Panic as i32);
+        // SAFETY: the failed call above left a thread-local message behind.
+        let message = unsafe { CStr::from_ptr(plugin_last_error()) }.to_string_lossy();
+        assert!(message.contains("deliberate panic for testing"));
+    }
+
+    #[test]
+    fn test_params_default_mode_is_panic() {
+        let params: Params = serde_json::from_str("{}").expect("valid JSON");
+        assert!(matches!(params.mode, Mode::Panic));
+        assert_eq!(params.megabytes, 1024);
+    }
+
+    #[test]
+    fn test_wait_mode_returns_once_cancelled() {
+        unsafe extern "C" fn always(_context: *mut c_void) -> bool {
+            true
+        }
+
+        let mut data = vec![0u8; 4];
+        let params = CString::new(r#"{"mode": "wait"}"#).expect("CString creation failed");
+
+        plugin_set_cancel_callback(Some(always), std::ptr::null_mut());
+        // SAFETY: data and params are valid for the duration of the call.
+        let result = unsafe { process_image(1, 1, data.as_mut_ptr(), params.as_ptr()) };
+        plugin_set_cancel_callback(None, std::ptr::null_mut());
+
+        assert_eq!(result, PanicError::Cancelled as i32);
+    }
+
+    #[test]
+    fn test_allocate_mode_succeeds_for_small_sizes() {
+        let mut data = vec![0u8; 4];
+        let params = CString::new(r#"{"mode": "allocate", "megabytes": 1}"#)
+            .expect("CString creation failed");
+
+        // SAFETY: data and params are valid for the duration of the call.
+        let result = unsafe { process_image(1, 1, data.as_mut_ptr(), params.as_ptr()) };
+
+        assert_eq!(result, PanicError::Success as i32);
+    }
+}
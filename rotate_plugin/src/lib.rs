@@ -0,0 +1,807 @@
+use log::{LevelFilter, Log, Metadata, Record, error};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{self, UnwindSafe};
+use std::sync::{Mutex, PoisonError};
+
+/// Error codes returned by the rotate plugin.
+///
+/// Values match the well-known codes shared by all plugins (see `describe_error_code`
+/// in `image_processor`'s plugin loader); `SizeChange` is plugin-specific. The
+/// human-readable reason for a failure is available through `plugin_last_error`.
+#[repr(i32)]
+pub enum RotateError {
+    Success = 0,
+    ParseError = -1,
+    SizeOverflow = -2,
+    InvalidAngle = -3,
+    Panic = -4,
+    UnsupportedFormat = -5,
+    /// `process_image` was asked for a rotation that changes the image size.
+    SizeChange = -1000,
+}
+
+/// Version of the host/plugin ABI this plugin implements.
+/// Must match a version supported by `image_processor`'s plugin loader.
+const PLUGIN_ABI_VERSION: u32 = 12;
+
+/// Pixel format codes of `process_pixels` and `transform_pixels`. Must match `PixelFormat` in
+/// `image_processor`.
+const PIXEL_FORMAT_RGBA8: u32 = 0;
+const PIXEL_FORMAT_RGBA16: u32 = 1;
+const PIXEL_FORMAT_RGBA32F: u32 = 2;
+const PIXEL_FORMAT_L8: u32 = 3;
+const PIXEL_FORMAT_LA8: u32 = 4;
+const PIXEL_FORMAT_RGB8: u32 = 5;
+
+thread_local! {
+    /// Message describing the last failed call on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Host callback receiving one `log` record: its level (1 = error to 5 = trace), target and
+/// message as null-terminated strings.
+type LogCallback = unsafe extern "C" fn(u32, *const c_char, *const c_char);
+
+/// Log callback the host installed. Unlike the other callbacks it is shared by all threads,
+/// since `log` has a single global logger.
+static HOST_LOG: Mutex<Option<LogCallback>> = Mutex::new(None);
+
+/// `log` backend that forwards records to the host's logger.
+struct HostLogger;
+
+impl Log for HostLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        let callback = *HOST_LOG.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(callback) = callback.filter(|_| self.enabled(record.metadata())) else {
+            return;
+        };
+        // Interior null bytes cannot be represented in a C string; drop them.
+        let target = CString::new(record.target().replace('\0', "")).unwrap_or_default();
+        let message = CString::new(record.args().to_string().replace('\0', "")).unwrap_or_default();
+        // SAFETY: The host keeps the callback valid for as long as the library is loaded, and
+        // both strings outlive the call.
+        unsafe { callback(record.level() as u32, target.as_ptr(), message.as_ptr()) };
+    }
+
+    fn flush(&self) {}
+}
+
+/// Logs an error, records it for `plugin_last_error` and returns its code.
+fn fail(code: RotateError, message: String) -> i32 {
+    error!("{}", message);
+    // Interior null bytes cannot be represented in a C string; drop them.
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+    code as i32
+}
+
+/// Runs `body`, converting a panic into `RotateError::Panic` so it never unwinds into the host.
+fn catch_panic(body: impl FnOnce() -> i32 + UnwindSafe) -> i32 {
+    match panic::catch_unwind(body) {
+        Ok(code) => code,
+        Err(payload) => {
+            let reason = payload
+                .downcast_ref::<&str>()
+                .map(|reason| reason.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic payload".to_string());
+            fail(RotateError::Panic, format!("panicked: {}", reason))
+        }
+    }
+}
+
+/// Static plugin metadata returned by `plugin_info`.
+/// Layout must match `RawPluginInfo` in `image_processor`'s plugin loader.
+#[repr(C)]
+pub struct PluginInfo {
+    pub name: *const c_char,
+    pub version: *const c_char,
+    pub description: *const c_char,
+    pub author: *const c_char,
+    /// Comma-separated list of supported operations.
+    pub operations: *const c_char,
+}
+
+// SAFETY: PluginInfo only points to immutable 'static string literals, so sharing it
+// between threads is sound.
+unsafe impl Sync for PluginInfo {}
+
+static PLUGIN_INFO: PluginInfo = PluginInfo {
+    name: concat!(env!("CARGO_PKG_NAME"), "\0").as_ptr().cast(),
+    version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast(),
+    description: concat!(env!("CARGO_PKG_DESCRIPTION"), "\0").as_ptr().cast(),
+    author: concat!(env!("CARGO_PKG_AUTHORS"), "\0").as_ptr().cast(),
+    operations: c"rotate_90,rotate_180,rotate_270".as_ptr(),
+};
+
+/// JSON Schema describing `Params`, returned by `plugin_params_schema`.
+/// Keep in sync with the struct below.
+const PARAMS_SCHEMA: &str = concat!(
+    r#"{
+    "$schema": "https://json-schema.org/draft/2020-12/schema",
+    "title": "rotate_plugin parameters",
+    "type": "object",
+    "properties": {
+        "angle": {
+            "type": "integer",
+            "enum": [90, 180, 270],
+            "default": 90,
+            "description": "Clockwise rotation in degrees"
+        }
+    },
+    "additionalProperties": false
+}"#,
+    "\0"
+);
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Params {
+    #[serde(default = "default_angle")]
+    angle: u32,
+}
+
+fn default_angle() -> u32 {
+    90
+}
+
+/// Parses the params string and checks the angle.
+///
+/// # Safety
+///
+/// `params` must be a valid null-terminated C string.
+unsafe fn parse_params(params: *const c_char) -> Result<Params, i32> {
+    // SAFETY: params is a valid null-terminated C string passed by the host.
+    // The plugin loader guarantees this pointer is valid for the duration of this call.
+    let params_str = unsafe { CStr::from_ptr(params) }.to_str().unwrap_or("");
+
+    let params: Params = serde_json::from_str(params_str).map_err(|e| {
+        fail(
+            RotateError::ParseError,
+            format!("failed to parse params JSON: {}", e),
+        )
+    })?;
+
+    if !matches!(params.angle, 90 | 180 | 270) {
+        return Err(fail(
+            RotateError::InvalidAngle,
+            format!("angle {} is not one of 90, 180, 270", params.angle),
+        ));
+    }
+
+    Ok(params)
+}
+
+/// Returns the size of an image of `width` x `height` pixels after rotating it by `angle`.
+fn rotated_dimensions(width: u32, height: u32, angle: u32) -> (u32, u32) {
+    if angle == 180 {
+        (width, height)
+    } else {
+        (height, width)
+    }
+}
+
+/// Returns the size of one pixel in a format, or `None` for an unknown format code.
+fn bytes_per_pixel(format: u32) -> Option<usize> {
+    match format {
+        PIXEL_FORMAT_L8 => Some(1),
+        PIXEL_FORMAT_LA8 => Some(2),
+        PIXEL_FORMAT_RGB8 => Some(3),
+        PIXEL_FORMAT_RGBA8 => Some(4),
+        PIXEL_FORMAT_RGBA16 => Some(8),
+        PIXEL_FORMAT_RGBA32F => Some(16),
+        _ => None,
+    }
+}
+
+/// Returns the buffer length of a `width` x `height` image of `pixel_size`-byte pixels, or
+/// `None` on overflow.
+fn buffer_len(width: u32, height: u32, pixel_size: usize) -> Option<usize> {
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|n| n.checked_mul(pixel_size))
+}
+
+/// Rotates `src` (`width` x `height` pixels of `pixel_size` bytes) clockwise by `angle` into
+/// `dst`, which must hold the rotated image.
+fn rotate(src: &[u8], width: usize, height: usize, pixel_size: usize, angle: u32, dst: &mut [u8]) {
+    for y in 0..height {
+        for x in 0..width {
+            let (dst_x, dst_y, dst_width) = match angle {
+                90 => (height - 1 - y, x, height),
+                180 => (width - 1 - x, height - 1 - y, width),
+                _ => (y, width - 1 - x, height),
+            };
+            let src_idx = (y * width + x) * pixel_size;
+            let dst_idx = (dst_y * dst_width + dst_x) * pixel_size;
+            dst[dst_idx..dst_idx + pixel_size].copy_from_slice(&src[src_idx..src_idx + pixel_size]);
+        }
+    }
+}
+
+/// Returns the plugin ABI version. The host checks this before resolving any other symbol.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+/// Returns a pointer to the plugin's static metadata descriptor.
+/// The pointer stays valid for as long as the library is loaded.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_info() -> *const PluginInfo {
+    &PLUGIN_INFO
+}
+
+/// Returns the JSON Schema of the plugin's parameters as a static null-terminated string.
+/// The pointer stays valid for as long as the library is loaded.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_params_schema() -> *const c_char {
+    PARAMS_SCHEMA.as_ptr().cast()
+}
+
+/// Returns the pixel formats `process_pixels` and `transform_pixels` accept, one bit
+/// (`1 << code`) per format. Rotation only moves whole pixels, so every format works.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_pixel_formats() -> u32 {
+    1 << PIXEL_FORMAT_RGBA8
+        | 1 << PIXEL_FORMAT_RGBA16
+        | 1 << PIXEL_FORMAT_RGBA32F
+        | 1 << PIXEL_FORMAT_L8
+        | 1 << PIXEL_FORMAT_LA8
+        | 1 << PIXEL_FORMAT_RGB8
+}
+
+/// Returns the message describing why the last call on the calling thread failed, or null
+/// if it succeeded. The pointer stays valid until the next call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Sends this plugin's `log` records up to `max_level` (0 = off, 1 = error to 5 = trace) to
+/// the host's `callback`, or drops them again when `callback` is null.
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_set_log_callback(callback: Option<LogCallback>, max_level: u32) {
+    *HOST_LOG.lock().unwrap_or_else(PoisonError::into_inner) = callback;
+    // Only the first call installs the logger; later ones replace the callback and level
+    let _ = log::set_logger(&HostLogger);
+    log::set_max_level(
+        LevelFilter::iter()
+            .nth(max_level as usize)
+            .unwrap_or(LevelFilter::Trace),
+    );
+}
+
+/// Reports the size of the image `transform_image` will produce for the given input size
+/// and params.
+///
+/// # Safety
+///
+/// The caller must ensure:
+/// - `params` is a valid null-terminated C string
+/// - `out_width` and `out_height` are valid pointers to writable `u32`s
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plugin_output_dimensions(
+    width: u32,
+    height: u32,
+    params: *const c_char,
+    out_width: *mut u32,
+    out_height: *mut u32,
+) -> i32 {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+
+    catch_panic(|| {
+        // SAFETY: params is forwarded unchanged from the host.
+        let params = match unsafe { parse_params(params) } {
+            Ok(params) => params,
+            Err(code) => return code,
+        };
+
+        let (new_width, new_height) = rotated_dimensions(width, height, params.angle);
+        // SAFETY: The caller guarantees both pointers are valid for writes.
+        unsafe {
+            *out_width = new_width;
+            *out_height = new_height;
+        }
+        RotateError::Success as i32
+    })
+}
+
+/// Rotates the input image into a separate output buffer allocated by the host.
+///
+/// # Safety
+///
+/// The caller must ensure:
+/// - `rgba_data` points to `width * height * 4` readable bytes
+/// - `out_data` points to `out_width * out_height * 4` writable bytes that do not overlap
+///   `rgba_data`, where `out_width` x `out_height` is the size reported by
+///   `plugin_output_dimensions` for the same input and params
+/// - `params` is a valid null-terminated C string
+/// - Both buffers remain valid for the duration of this call
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn transform_image(
+    width: u32,
+    height: u32,
+    rgba_data: *const u8,
+    out_width: u32,
+    out_height: u32,
+    out_data: *mut u8,
+    params: *const c_char,
+) -> i32 {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+
+    // SAFETY: The arguments are forwarded unchanged, so the caller's guarantees for
+    // transform_image carry over to apply_transform.
+    catch_panic(|| unsafe {
+        apply_transform(
+            width, height, 4, rgba_data, out_width, out_height, out_data, params,
+        )
+    })
+}
+
+/// Like `transform_image`, for any format reported by `plugin_pixel_formats`.
+///
+/// # Safety
+///
+/// Same contract as `transform_image`, with both buffers holding pixels of `format`.
+#[unsafe(no_mangle)]
+#[allow(clippy::too_many_arguments)] // Mirrors transform_image plus the format code
+pub unsafe extern "C" fn transform_pixels(
+    width: u32,
+    height: u32,
+    format: u32,
+    data: *const u8,
+    out_width: u32,
+    out_height: u32,
+    out_data: *mut u8,
+    params: *const c_char,
+) -> i32 {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+
+    let Some(pixel_size) = bytes_per_pixel(format) else {
+        return fail(
+            RotateError::UnsupportedFormat,
+            format!("unsupported pixel format code {}", format),
+        );
+    };
+    // SAFETY: The arguments are forwarded unchanged and pixel_size matches `format`, so the
+    // caller's guarantees for transform_pixels carry over to apply_transform.
+    catch_panic(|| unsafe {
+        apply_transform(
+            width, height, pixel_size, data, out_width, out_height, out_data, params,
+        )
+    })
+}
+
+/// Rotates an image of `pixel_size`-byte pixels into `out_data`. Panics are caught by the
+/// callers.
+///
+/// # Safety
+///
+/// Same contract as `transform_image`, with pixels of `pixel_size` bytes.
+#[allow(clippy::too_many_arguments)]
+unsafe fn apply_transform(
+    width: u32,
+    height: u32,
+    pixel_size: usize,
+    data: *const u8,
+    out_width: u32,
+    out_height: u32,
+    out_data: *mut u8,
+    params: *const c_char,
+) -> i32 {
+    // SAFETY: params is forwarded unchanged from the host.
+    let params = match unsafe { parse_params(params) } {
+        Ok(params) => params,
+        Err(code) => return code,
+    };
+
+    let expected = rotated_dimensions(width, height, params.angle);
+    if (out_width, out_height) != expected {
+        return fail(
+            RotateError::SizeChange,
+            format!(
+                "output buffer is {}x{}, expected {}x{}",
+                out_width, out_height, expected.0, expected.1
+            ),
+        );
+    }
+    let Some(len) = buffer_len(width, height, pixel_size) else {
+        return fail(
+            RotateError::SizeOverflow,
+            "size overflow calculating buffer length".to_string(),
+        );
+    };
+
+    // SAFETY: The caller guarantees data holds width * height pixels of readable bytes and
+    // out_data holds the same number of writable bytes (the rotated image has the same pixel
+    // count), and that the buffers do not overlap.
+    let (src, dst) = unsafe {
+        (
+            std::slice::from_raw_parts(data, len),
+            std::slice::from_raw_parts_mut(out_data, len),
+        )
+    };
+    rotate(
+        src,
+        width as usize,
+        height as usize,
+        pixel_size,
+        params.angle,
+        dst,
+    );
+
+    RotateError::Success as i32
+}
+
+/// Rotates the image in place. Only possible when the size does not change (180 degrees, or
+/// a square image); hosts that support `transform_image` never call this for other cases.
+///
+/// # Safety
+///
+/// The caller must ensure:
+/// - `rgba_data` is a valid pointer to a buffer of exactly `width * height * 4` bytes
+/// - `params` is a valid null-terminated C string
+/// - The buffer remains valid for the duration of this call
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn process_image(
+    width: u32,
+    height: u32,
+    rgba_data: *mut u8,
+    params: *const c_char,
+) -> i32 {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+
+    // SAFETY: The arguments are forwarded unchanged, so the caller's guarantees for
+    // process_image carry over to apply_in_place.
+    catch_panic(|| unsafe { apply_in_place(width, height, 4, rgba_data, params) })
+}
+
+/// Like `process_image`, for any format reported by `plugin_pixel_formats`.
+///
+/// # Safety
+///
+/// Same contract as `process_image`, with `data` holding `width * height` pixels of `format`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn process_pixels(
+    width: u32,
+    height: u32,
+    format: u32,
+    data: *mut u8,
+    params: *const c_char,
+) -> i32 {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+
+    let Some(pixel_size) = bytes_per_pixel(format) else {
+        return fail(
+            RotateError::UnsupportedFormat,
+            format!("unsupported pixel format code {}", format),
+        );
+    };
+    // SAFETY: The arguments are forwarded unchanged and pixel_size matches `format`, so the
+    // caller's guarantees for process_pixels carry over to apply_in_place.
+    catch_panic(|| unsafe { apply_in_place(width, height, pixel_size, data, params) })
+}
+
+/// Rotates an image of `pixel_size`-byte pixels in place. Panics are caught by the callers.
+///
+/// # Safety
+///
+/// Same contract as `process_image`, with `data` holding `width * height * pixel_size` bytes.
+unsafe fn apply_in_place(
+    width: u32,
+    height: u32,
+    pixel_size: usize,
+    data: *mut u8,
+    params: *const c_char,
+) -> i32 {
+    // SAFETY: params is forwarded unchanged from the host.
+    let params = match unsafe { parse_params(params) } {
+        Ok(params) => params,
+        Err(code) => return code,
+    };
+
+    if rotated_dimensions(width, height, params.angle) != (width, height) {
+        return fail(
+            RotateError::SizeChange,
+            format!(
+                "rotating a {}x{} image by {} degrees changes its size; use transform_image",
+                width, height, params.angle
+            ),
+        );
+    }
+    let Some(len) = buffer_len(width, height, pixel_size) else {
+        return fail(
+            RotateError::SizeOverflow,
+            "size overflow calculating buffer length".to_string(),
+        );
+    };
+
+    // SAFETY: data is a valid pointer to a buffer of exactly len bytes, owned by the host for
+    // the duration of this call.
+    let data = unsafe { std::slice::from_raw_parts_mut(data, len) };
+    let original = data.to_vec();
+    rotate(
+        &original,
+        width as usize,
+        height as usize,
+        pixel_size,
+        params.angle,
+        data,
+    );
+
+    RotateError::Success as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a 3x2 test image where pixel (x, y) has RGBA = (x, y, 0, 255).
+    fn create_3x2_test_image() -> Vec<u8> {
+        let mut data = Vec::new();
+        for y in 0..2 {
+            for x in 0..3 {
+                data.extend_from_slice(&[x, y, 0, 255]);
+            }
+        }
+        data
+    }
+
+    fn get_pixel(data: &[u8], width: usize, x: usize, y: usize) -> (u8, u8) {
+        let idx = (y * width + x) * 4;
+        (data[idx], data[idx + 1])
+    }
+
+    fn call_output_dimensions(width: u32, height: u32, params_json: &str) -> (i32, u32, u32) {
+        let params = CString::new(params_json).expect("CString creation failed");
+        let (mut out_width, mut out_height) = (0, 0);
+        // SAFETY: params is a valid C string and both out pointers refer to local u32s.
+        let result = unsafe {
+            plugin_output_dimensions(
+                width,
+                height,
+                params.as_ptr(),
+                &mut out_width,
+                &mut out_height,
+            )
+        };
+        (result, out_width, out_height)
+    }
+
+    fn call_transform_image(
+        width: u32,
+        height: u32,
+        data: &[u8],
+        params_json: &str,
+    ) -> (i32, u32, u32, Vec<u8>) {
+        let (result, out_width, out_height) = call_output_dimensions(width, height, params_json);
+        assert_eq!(result, RotateError::Success as i32);
+        let mut output = vec![0u8; (out_width * out_height * 4) as usize];
+        let params = CString::new(params_json).expect("CString creation failed");
+        // SAFETY: data holds width * height * 4 bytes, output holds the reported output size,
+        // and params is a valid C string.
+        let result = unsafe {
+            transform_image(
+                width,
+                height,
+                data.as_ptr(),
+                out_width,
+                out_height,
+                output.as_mut_ptr(),
+                params.as_ptr(),
+            )
+        };
+        (result, out_width, out_height, output)
+    }
+
+    fn call_process_image(width: u32, height: u32, data: &mut [u8], params_json: &str) -> i32 {
+        let params = CString::new(params_json).expect("CString creation failed");
+        // SAFETY: data is a valid slice of width * height * 4 bytes, and params is a valid
+        // null-terminated C string.
+        unsafe { process_image(width, height, data.as_mut_ptr(), params.as_ptr()) }
+    }
+
+    fn last_error() -> Option<String> {
+        let ptr = plugin_last_error();
+        if ptr.is_null() {
+            return None;
+        }
+        // SAFETY: ptr is non-null and points to the thread-local CString, which is not
+        // modified until the next call on this thread.
+        Some(
+            unsafe { CStr::from_ptr(ptr) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+
+    #[test]
+    fn test_output_dimensions() {
+        assert_eq!(call_output_dimensions(3, 2, r#"{"angle": 90}"#), (0, 2, 3));
+        assert_eq!(call_output_dimensions(3, 2, r#"{"angle": 180}"#), (0, 3, 2));
+        assert_eq!(call_output_dimensions(3, 2, r#"{"angle": 270}"#), (0, 2, 3));
+        assert_eq!(call_output_dimensions(3, 2, "{}"), (0, 2, 3));
+    }
+
+    #[test]
+    fn test_transform_rotate_90() {
+        let data = create_3x2_test_image();
+        let (result, width, height, output) = call_transform_image(3, 2, &data, r#"{"angle": 90}"#);
+
+        assert_eq!(result, RotateError::Success as i32);
+        assert_eq!((width, height), (2, 3));
+        // Bottom-left corner of the source becomes the top-left corner.
+        assert_eq!(get_pixel(&output, 2, 0, 0), (0, 1));
+        assert_eq!(get_pixel(&output, 2, 1, 0), (0, 0));
+        assert_eq!(get_pixel(&output, 2, 0, 2), (2, 1));
+        assert_eq!(get_pixel(&output, 2, 1, 2), (2, 0));
+    }
+
+    #[test]
+    fn test_transform_pixels_rotates_float_pixels() {
+        // 2x1 RGBA32F image: each pixel is 16 bytes
+        let left: Vec<u8> = (0..16).collect();
+        let right: Vec<u8> = (16..32).collect();
+        let data = [left.clone(), right.clone()].concat();
+        let mut output = vec![0u8; 32];
+        let params = CString::new(r#"{"angle": 90}"#).expect("CString creation failed");
+
+        // SAFETY: data holds 2 * 1 RGBA32F pixels, output holds the rotated 1x2 image and
+        // params is a valid C string.
+        let result = unsafe {
+            transform_pixels(
+                2,
+                1,
+                PIXEL_FORMAT_RGBA32F,
+                data.as_ptr(),
+                1,
+                2,
+                output.as_mut_ptr(),
+                params.as_ptr(),
+            )
+        };
+
+        assert_eq!(result, RotateError::Success as i32);
+        // Rotating a row clockwise puts its left end on top
+        assert_eq!(output, [left, right].concat());
+    }
+
+    #[test]
+    fn test_process_pixels_rejects_unknown_format() {
+        let mut data = vec![0u8; 4];
+        let params = CString::new(r#"{"angle": 180}"#).expect("CString creation failed");
+
+        // SAFETY: The plugin rejects the format before touching the buffer.
+        let result = unsafe { process_pixels(1, 1, 7, data.as_mut_ptr(), params.as_ptr()) };
+
+        assert_eq!(result, RotateError::UnsupportedFormat as i32);
+        assert!(
+            last_error()
+                .expect("message recorded")
+                .contains("pixel format")
+        );
+    }
+
+    #[test]
+    fn test_transform_rotate_270() {
+        let data = create_3x2_test_image();
+        let (result, _, _, output) = call_transform_image(3, 2, &data, r#"{"angle": 270}"#);
+
+        assert_eq!(result, RotateError::Success as i32);
+        // Top-right corner of the source becomes the top-left corner.
+        assert_eq!(get_pixel(&output, 2, 0, 0), (2, 0));
+        assert_eq!(get_pixel(&output, 2, 1, 2), (0, 1));
+    }
+
+    #[test]
+    fn test_transform_rejects_wrong_output_size() {
+        let data = create_3x2_test_image();
+        let mut output = vec![0u8; data.len()];
+        let params = CString::new(r#"{"angle": 90}"#).expect("CString creation failed");
+        // SAFETY: Both buffers hold 3 * 2 * 4 bytes and params is a valid C string.
+        let result = unsafe {
+            transform_image(
+                3,
+                2,
+                data.as_ptr(),
+                3,
+                2,
+                output.as_mut_ptr(),
+                params.as_ptr(),
+            )
+        };
+
+        assert_eq!(result, RotateError::SizeChange as i32);
+        assert!(output.iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_process_image_rotates_180_in_place() {
+        let mut data = create_3x2_test_image();
+        let result = call_process_image(3, 2, &mut data, r#"{"angle": 180}"#);
+
+        assert_eq!(result, RotateError::Success as i32);
+        assert_eq!(get_pixel(&data, 3, 0, 0), (2, 1));
+        assert_eq!(get_pixel(&data, 3, 2, 1), (0, 0));
+    }
+
+    #[test]
+    fn test_process_image_rejects_size_change() {
+        let mut data = create_3x2_test_image();
+        let original = data.clone();
+
+        let result = call_process_image(3, 2, &mut data, r#"{"angle": 90}"#);
+
+        assert_eq!(result, RotateError::SizeChange as i32);
+        assert_eq!(data, original);
+        let message = last_error().expect("failure should set last error");
+        assert!(message.contains("changes its size"), "got: {}", message);
+    }
+
+    #[test]
+    fn test_invalid_angle_rejected() {
+        let (result, _, _) = call_output_dimensions(3, 2, r#"{"angle": 45}"#);
+
+        assert_eq!(result, RotateError::InvalidAngle as i32);
+        let message = last_error().expect("failure should set last error");
+        assert!(message.contains("angle 45"), "got: {}", message);
+    }
+
+    #[test]
+    fn test_unknown_param_rejected() {
+        let (result, _, _) = call_output_dimensions(3, 2, r#"{"angel": 90}"#);
+        assert_eq!(result, RotateError::ParseError as i32);
+    }
+
+    #[test]
+    fn test_plugin_abi_version() {
+        assert_eq!(plugin_abi_version(), PLUGIN_ABI_VERSION);
+    }
+
+    #[test]
+    fn test_plugin_info_descriptor() {
+        // SAFETY: plugin_info returns a pointer to a 'static descriptor whose fields are
+        // null-terminated string literals.
+        let (name, operations) = unsafe {
+            let info = &*plugin_info();
+            (
+                CStr::from_ptr(info.name).to_str().expect("valid UTF-8"),
+                CStr::from_ptr(info.operations)
+                    .to_str()
+                    .expect("valid UTF-8"),
+            )
+        };
+
+        assert_eq!(name, "rotate_plugin");
+        assert_eq!(operations, "rotate_90,rotate_180,rotate_270");
+    }
+
+    #[test]
+    fn test_params_schema_is_valid_json() {
+        // SAFETY: plugin_params_schema returns a pointer to a static null-terminated string.
+        let schema = unsafe { CStr::from_ptr(plugin_params_schema()) }
+            .to_str()
+            .expect("valid UTF-8");
+        let schema: serde_json::Value = serde_json::from_str(schema).expect("valid JSON");
+
+        let properties = schema["properties"].as_object().expect("properties object");
+        assert_eq!(
+            properties.len(),
+            1,
+            "schema must declare every Params field"
+        );
+        assert_eq!(schema["additionalProperties"], false);
+    }
+}